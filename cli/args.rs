@@ -1,4 +1,9 @@
-use clap::{command, Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use clap::{command, Args, Parser, Subcommand};
+
+use crate::test_reports::ReportFormat;
 
 #[derive(Parser, PartialEq, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -12,9 +17,116 @@ pub enum Commands {
     Run {
         /// JavaScript file containing the Station Module to run
         file: String,
+
+        /// Load an import map from the given JSON file. Import maps allow you to import modules
+        /// using bare specifiers like `multiformats`.
+        #[arg(long)]
+        import_map: Option<PathBuf>,
+
+        #[command(flatten)]
+        inspect: InspectArgs,
+
+        /// Restart the module when any of the files it loaded changes.
+        #[arg(long)]
+        watch: bool,
+
+        #[command(flatten)]
+        remote: RemoteImportArgs,
+    },
+    /// Run tests using the built-in `zinnia:test` runner. Each test file runs in a fresh runtime.
+    Test {
+        /// Test files, directories or glob patterns. Directories are searched for files matching
+        /// `*.test.{js,ts}` or `test/**/*.{js,ts}`. Defaults to the current working directory.
+        files: Vec<String>,
+
+        /// Run only tests with a name containing this text. Use `/regex/` to match tests using a
+        /// regular expression. Names of nested tests include the names of their `describe` groups
+        /// separated by " > ".
+        #[arg(long)]
+        filter: Option<String>,
+
+        /// Write a machine-readable test report in the given format to the file specified by
+        /// `--report-file`.
+        #[arg(long, value_enum, requires = "report_file")]
+        report_format: Option<ReportFormat>,
+
+        /// Path of the file where to write the test report.
+        #[arg(long, requires = "report_format")]
+        report_file: Option<PathBuf>,
+
+        /// Re-run the tests when any of the files loaded by the test files changes.
+        #[arg(long)]
+        watch: bool,
+
+        #[command(flatten)]
+        remote: RemoteImportArgs,
+    },
+    /// Bundle a module with all its dependencies into a single ES module file that can be deployed
+    /// to Filecoin Station. Imports of built-in modules like `zinnia:test` are preserved.
+    Bundle {
+        /// The entry point of the module. The module can import files inside the directory
+        /// of the entry point only.
+        entry: String,
+
+        /// Path of the output file.
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Append an inline source map to the output file.
+        #[arg(long)]
+        source_map: bool,
+
+        /// Load an import map from the given JSON file.
+        #[arg(long)]
+        import_map: Option<PathBuf>,
+
+        #[command(flatten)]
+        remote: RemoteImportArgs,
     },
 }
 
+/// The default address of the inspector server, the same as used by Node.js and Deno.
+const DEFAULT_INSPECT_ADDR: &str = "127.0.0.1:9229";
+
+/// Options for debugging the module using Chrome DevTools or VS Code.
+#[derive(Args, PartialEq, Debug)]
+pub struct InspectArgs {
+    /// Activate the V8 inspector on HOST:PORT (default: 127.0.0.1:9229).
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_INSPECT_ADDR,
+        conflicts_with = "inspect_brk"
+    )]
+    pub inspect: Option<SocketAddr>,
+
+    /// Activate the V8 inspector on HOST:PORT (default: 127.0.0.1:9229), wait for a debugger to
+    /// attach and pause before the module is evaluated.
+    #[arg(
+        long,
+        value_name = "HOST:PORT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_INSPECT_ADDR
+    )]
+    pub inspect_brk: Option<SocketAddr>,
+}
+
+/// Options for importing remote modules from `https:` URLs.
+#[derive(Args, PartialEq, Debug)]
+pub struct RemoteImportArgs {
+    /// The lockfile pinning integrity hashes of remote modules. New remote modules are added to
+    /// the lockfile unless `--frozen` is specified.
+    #[arg(long, default_value = "zinnia.lock")]
+    pub lock: PathBuf,
+
+    /// Refuse to load remote modules that are not pinned in the lockfile.
+    #[arg(long)]
+    pub frozen: bool,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -27,9 +139,244 @@ mod tests {
             args,
             CliArgs {
                 command: Commands::Run {
-                    file: "mod.js".to_string()
+                    file: "mod.js".to_string(),
+                    import_map: None,
+                    inspect: default_inspect_args(),
+                    watch: false,
+                    remote: default_remote_args(),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn run_js_with_import_map() {
+        let args = CliArgs::parse_from(["zinnia", "run", "--import-map", "deps.json", "mod.js"]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Run {
+                    file: "mod.js".to_string(),
+                    import_map: Some(PathBuf::from("deps.json")),
+                    inspect: default_inspect_args(),
+                    watch: false,
+                    remote: default_remote_args(),
                 }
             },
         );
     }
+
+    #[test]
+    fn run_js_frozen() {
+        let args =
+            CliArgs::parse_from(["zinnia", "run", "--frozen", "--lock", "deps.lock", "mod.js"]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Run {
+                    file: "mod.js".to_string(),
+                    import_map: None,
+                    inspect: default_inspect_args(),
+                    watch: false,
+                    remote: RemoteImportArgs {
+                        lock: PathBuf::from("deps.lock"),
+                        frozen: true,
+                    },
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn run_js_inspect() {
+        let args = CliArgs::parse_from(["zinnia", "run", "--inspect", "mod.js"]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Run {
+                    file: "mod.js".to_string(),
+                    import_map: None,
+                    inspect: InspectArgs {
+                        inspect: Some("127.0.0.1:9229".parse().unwrap()),
+                        inspect_brk: None,
+                    },
+                    watch: false,
+                    remote: default_remote_args(),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn run_js_inspect_brk_with_address() {
+        let args = CliArgs::parse_from(["zinnia", "run", "--inspect-brk=127.0.0.1:9333", "mod.js"]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Run {
+                    file: "mod.js".to_string(),
+                    import_map: None,
+                    inspect: InspectArgs {
+                        inspect: None,
+                        inspect_brk: Some("127.0.0.1:9333".parse().unwrap()),
+                    },
+                    watch: false,
+                    remote: default_remote_args(),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn inspect_conflicts_with_inspect_brk() {
+        let result =
+            CliArgs::try_parse_from(["zinnia", "run", "--inspect", "--inspect-brk", "mod.js"]);
+        assert!(
+            result.is_err(),
+            "--inspect together with --inspect-brk should be rejected"
+        );
+    }
+
+    #[test]
+    fn run_js_watch() {
+        let args = CliArgs::parse_from(["zinnia", "run", "--watch", "mod.js"]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Run {
+                    file: "mod.js".to_string(),
+                    import_map: None,
+                    inspect: default_inspect_args(),
+                    watch: true,
+                    remote: default_remote_args(),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        let args = CliArgs::parse_from(["zinnia", "test", "lib.test.js", "test/**/*.js"]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Test {
+                    files: vec!["lib.test.js".to_string(), "test/**/*.js".to_string()],
+                    filter: None,
+                    report_format: None,
+                    report_file: None,
+                    watch: false,
+                    remote: default_remote_args(),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let args = CliArgs::parse_from(["zinnia", "test", "--filter", "/^fetch/"]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Test {
+                    files: vec![],
+                    filter: Some("/^fetch/".to_string()),
+                    report_format: None,
+                    report_file: None,
+                    watch: false,
+                    remote: default_remote_args(),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_watch() {
+        let args = CliArgs::parse_from(["zinnia", "test", "--watch"]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Test {
+                    files: vec![],
+                    filter: None,
+                    report_format: None,
+                    report_file: None,
+                    watch: true,
+                    remote: default_remote_args(),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let args = CliArgs::parse_from([
+            "zinnia",
+            "test",
+            "--report-format",
+            "junit",
+            "--report-file",
+            "junit.xml",
+        ]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Test {
+                    files: vec![],
+                    filter: None,
+                    report_format: Some(ReportFormat::Junit),
+                    report_file: Some(PathBuf::from("junit.xml")),
+                    watch: false,
+                    remote: default_remote_args(),
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn bundle() {
+        let args = CliArgs::parse_from([
+            "zinnia",
+            "bundle",
+            "src/main.js",
+            "-o",
+            "dist/module.js",
+            "--source-map",
+        ]);
+        assert_eq!(
+            args,
+            CliArgs {
+                command: Commands::Bundle {
+                    entry: "src/main.js".to_string(),
+                    output: PathBuf::from("dist/module.js"),
+                    source_map: true,
+                    import_map: None,
+                    remote: default_remote_args(),
+                }
+            },
+        );
+    }
+
+    fn default_inspect_args() -> InspectArgs {
+        InspectArgs {
+            inspect: None,
+            inspect_brk: None,
+        }
+    }
+
+    fn default_remote_args() -> RemoteImportArgs {
+        RemoteImportArgs {
+            lock: PathBuf::from("zinnia.lock"),
+            frozen: false,
+        }
+    }
+
+    #[test]
+    fn test_report_format_requires_file() {
+        let result = CliArgs::try_parse_from(["zinnia", "test", "--report-format", "tap"]);
+        assert!(
+            result.is_err(),
+            "--report-format without --report-file should be rejected"
+        );
+    }
 }
@@ -1,19 +1,29 @@
 mod args;
+mod test_files;
+mod test_reports;
+mod watch;
 
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use args::{CliArgs, Commands};
+use args::{CliArgs, Commands, InspectArgs, RemoteImportArgs};
 use clap::Parser;
 
-use zinnia_runtime::anyhow::{Context, Error, Result};
+use zinnia_runtime::anyhow::{anyhow, Context, Error, Result};
 use zinnia_runtime::deno_core::error::JsError;
+use zinnia_runtime::deno_core::ModuleSpecifier;
 use zinnia_runtime::fmt_errors::format_js_error;
 use zinnia_runtime::{
-    colors, lassie, lassie_config, resolve_path, run_js_module, BootstrapOptions, ConsoleReporter,
+    bundle_module, colors, get_module_root, lassie, lassie_config, read_import_map, resolve_path,
+    run_js_module, BootstrapOptions, BundleOptions, ConsoleReporter, KvStoreConfig, LoadedFiles,
+    ModuleOutput, RemoteModulesConfig, TestResults, DEFAULT_KV_QUOTA, DEFAULT_WATCHDOG_TIMEOUT,
 };
 
+use crate::test_files::find_test_files;
+use crate::test_reports::{write_report, FileReport, ReportFormat};
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     env_logger::init();
@@ -22,88 +32,374 @@ async fn main() {
     colors::enable_ansi(); // For Windows 10
 
     match main_impl().await {
-        Ok(_) => (),
+        Ok(0) => (),
+        Ok(exit_code) => std::process::exit(exit_code),
         Err(err) => exit_with_error(err),
     }
 }
 
-async fn main_impl() -> Result<()> {
+/// Run the command, return the process exit code.
+async fn main_impl() -> Result<i32> {
     let cli_args = CliArgs::parse_from(std::env::args());
     match cli_args.command {
-        Commands::Run { file } => {
-            run_module(file).await?;
-
-            Ok(())
+        Commands::Run {
+            file,
+            import_map,
+            inspect,
+            watch: false,
+            remote,
+        } => {
+            let RunOutput { module_output, .. } =
+                run_module(file, import_map, &inspect, &remote).await?;
+            Ok(module_output.process_exit_code())
+        }
+        Commands::Run {
+            file,
+            import_map,
+            inspect,
+            watch: true,
+            remote,
+        } => watch_module(file, import_map, &inspect, &remote).await,
+        Commands::Test {
+            files,
+            filter,
+            report_format,
+            report_file,
+            watch,
+            remote,
+        } => {
+            let report = report_format.zip(report_file);
+            let lassie_daemon = start_lassie_daemon()?;
+            if !watch {
+                return run_tests(&files, &filter, &report, &remote, &lassie_daemon, None).await;
+            }
+            let is_tty = colors::is_tty();
+            watch::run_and_watch(is_tty, vec![], |loaded_files| {
+                run_tests(
+                    &files,
+                    &filter,
+                    &report,
+                    &remote,
+                    &lassie_daemon,
+                    Some(loaded_files),
+                )
+            })
+            .await
+        }
+        Commands::Bundle {
+            entry,
+            output,
+            source_map,
+            import_map,
+            remote,
+        } => {
+            bundle(entry, output, source_map, import_map, &remote).await?;
+            Ok(0)
         }
     }
 }
 
 #[allow(dead_code)]
 struct RunOutput {
-    module_output: (),
+    module_output: ModuleOutput,
     // for testing
     lassie_daemon: Arc<lassie::Daemon>,
 }
 
-async fn run_module(file: String) -> Result<RunOutput> {
+async fn run_module(
+    file: String,
+    import_map: Option<PathBuf>,
+    inspect: &InspectArgs,
+    remote: &RemoteImportArgs,
+) -> Result<RunOutput> {
     let main_module = resolve_path(
         &file,
         &std::env::current_dir().context("unable to get current working directory")?,
     )?;
+    let import_map = import_map.as_deref().map(read_import_map).transpose()?;
+
+    let lassie_daemon = start_lassie_daemon()?;
+    let kv_store_dir = project_kv_store_dir(&main_module)?;
+    let runtime_config = BootstrapOptions {
+        import_map,
+        inspect: inspect.inspect.or(inspect.inspect_brk),
+        inspect_brk: inspect.inspect_brk.is_some(),
+        ..build_runtime_config(Arc::clone(&lassie_daemon), remote, Some(kv_store_dir))
+    };
+    let module_output = run_js_module(&main_module, &runtime_config).await?;
 
-    let lassie_daemon = Arc::new(
-        lassie::Daemon::start(lassie::DaemonConfig {
-            // This configuration applies to `zinnia` CLI only. The `zinniad` daemon running
-            // inside Station uses a different temp_dir config based on the env var
-            // `CACHE_ROOT` provided by the Station.
-            //
-            // By default, Lassie stores its temporary files in the system temp directory.
-            // That's good enough for now. We can improve this later based on user feedback,
-            // for example:
-            // - we can honour CACHE_ROOT
-            // - we can default to something like
-            //   `~/.cache/zinnia/lassie` on Unix,
-            //   `%APPLOCALDATA%\zinnia\lassie' on Windows.
-            //
-            // Important: if we tell Lassie to use a specific temp dir that's not
-            // automatically cleaned by the operating system, we will need to clean any
-            // leftover files ourselves. See the GH issue for deleting leftover files
-            // when `zinniad` starts: https://github.com/filecoin-station/zinnia/issues/245
-            temp_dir: None,
-            ..lassie_config()
-        })
-        .context("cannot initialize the IPFS retrieval client Lassie")?,
+    Ok(RunOutput {
+        module_output,
+        lassie_daemon,
+    })
+}
+
+/// Run the module and restart it whenever any of the files it loaded changes.
+async fn watch_module(
+    file: String,
+    import_map: Option<PathBuf>,
+    inspect: &InspectArgs,
+    remote: &RemoteImportArgs,
+) -> Result<i32> {
+    let main_module = resolve_path(
+        &file,
+        &std::env::current_dir().context("unable to get current working directory")?,
+    )?;
+
+    // Lassie can run only once per process, we share the same instance by all restarts
+    let lassie_daemon = start_lassie_daemon()?;
+    let is_tty = colors::is_tty();
+    let kv_store_dir = project_kv_store_dir(&main_module)?;
+    let extra_files = import_map.iter().cloned().collect();
+    watch::run_and_watch(is_tty, extra_files, |loaded_files| {
+        let main_module = &main_module;
+        let import_map = &import_map;
+        let kv_store_dir = kv_store_dir.clone();
+        let lassie_daemon = Arc::clone(&lassie_daemon);
+        async move {
+            let runtime_config = BootstrapOptions {
+                // Read the import map on each restart to pick up its changes
+                import_map: import_map.as_deref().map(read_import_map).transpose()?,
+                inspect: inspect.inspect.or(inspect.inspect_brk),
+                inspect_brk: inspect.inspect_brk.is_some(),
+                loaded_files: Some(loaded_files),
+                ..build_runtime_config(lassie_daemon, remote, Some(kv_store_dir))
+            };
+            let output = run_js_module(main_module, &runtime_config).await?;
+            Ok(output.process_exit_code())
+        }
+    })
+    .await
+}
+
+async fn run_tests(
+    paths: &[String],
+    filter: &Option<String>,
+    report: &Option<(ReportFormat, PathBuf)>,
+    remote: &RemoteImportArgs,
+    lassie_daemon: &Arc<lassie::Daemon>,
+    loaded_files: Option<LoadedFiles>,
+) -> Result<i32> {
+    let cwd = std::env::current_dir().context("unable to get current working directory")?;
+    let test_files = find_test_files(paths, &cwd)?;
+    if test_files.is_empty() {
+        return Err(anyhow!("No test files found."));
+    }
+
+    let started = Instant::now();
+    let mut results = TestResults::default();
+    let mut failed_files = Vec::new();
+    let mut file_reports = Vec::new();
+
+    for test_file in &test_files {
+        let main_module = ModuleSpecifier::from_file_path(test_file)
+            .map_err(|_| anyhow!("Invalid test file path: {}", test_file.display()))?;
+
+        // Each test file runs in a fresh runtime with an empty KV store, tests must not modify
+        // the data of `zinnia run` or depend on data stored by other test files
+        let kv_store_dir = tempfile::tempdir().context("cannot create the KV store directory")?;
+        let runtime_config = BootstrapOptions {
+            test_filter: filter.clone(),
+            loaded_files: loaded_files.clone(),
+            ..build_runtime_config(
+                Arc::clone(lassie_daemon),
+                remote,
+                Some(kv_store_dir.path().to_path_buf()),
+            )
+        };
+        match run_js_module(&main_module, &runtime_config).await {
+            Ok(output) => {
+                let exit_code = output.process_exit_code();
+                if exit_code != 0 {
+                    failed_files.push(test_file);
+                }
+
+                // Report non-zero exit codes unless they were caused by failed tests
+                let tests_failed = matches!(&output.test_results, Some(r) if r.failed > 0);
+                let error = (exit_code != 0 && !tests_failed)
+                    .then(|| format!("The test file exited with code {exit_code}."));
+                if let Some(error) = &error {
+                    eprintln!("\n{}: {error}", colors::red_bold("error"));
+                }
+
+                // Files without any tests are not included in the report
+                if let Some(file_results) = output.test_results {
+                    results.add(&file_results);
+                    file_reports.push(FileReport::new(
+                        display_relative(test_file, &cwd),
+                        file_results,
+                        error,
+                    ));
+                }
+            }
+            Err(err) => {
+                let error = format_error(&err);
+                let error = error.trim_start_matches("error: ");
+                eprintln!("\n{}: {error}", colors::red_bold("error"));
+                failed_files.push(test_file);
+                file_reports.push(FileReport::new(
+                    display_relative(test_file, &cwd),
+                    TestResults::default(),
+                    Some(error.to_string()),
+                ));
+            }
+        }
+    }
+
+    if !failed_files.is_empty() {
+        println!("\n{}", colors::white_on_red(" FAILED FILES "));
+        for file in &failed_files {
+            println!("  {}", display_relative(file, &cwd));
+        }
+    }
+
+    let skipped = match results.skipped {
+        0 => String::new(),
+        n => format!(" | {n} skipped"),
+    };
+    println!(
+        "\n{} | {} files | {} passed | {} failed{} {}\n",
+        if failed_files.is_empty() {
+            colors::green("ok").to_string()
+        } else {
+            colors::red("FAIL").to_string()
+        },
+        test_files.len(),
+        results.passed,
+        results.failed,
+        skipped,
+        colors::gray(format!("({}ms)", started.elapsed().as_millis())),
     );
 
-    let runtime_config = BootstrapOptions {
+    if let Some((format, report_file)) = report {
+        let duration_ms = started.elapsed().as_millis() as u64;
+        write_report(*format, &file_reports, duration_ms, &cwd.join(report_file))?;
+    }
+
+    Ok(if failed_files.is_empty() { 0 } else { 1 })
+}
+
+fn start_lassie_daemon() -> Result<Arc<lassie::Daemon>> {
+    let lassie_daemon = lassie::Daemon::start(lassie::DaemonConfig {
+        // This configuration applies to `zinnia` CLI only. The `zinniad` daemon running
+        // inside Station uses a different temp_dir config based on the env var
+        // `CACHE_ROOT` provided by the Station.
+        //
+        // By default, Lassie stores its temporary files in the system temp directory.
+        // That's good enough for now. We can improve this later based on user feedback,
+        // for example:
+        // - we can honour CACHE_ROOT
+        // - we can default to something like
+        //   `~/.cache/zinnia/lassie` on Unix,
+        //   `%APPLOCALDATA%\zinnia\lassie' on Windows.
+        //
+        // Important: if we tell Lassie to use a specific temp dir that's not
+        // automatically cleaned by the operating system, we will need to clean any
+        // leftover files ourselves. See the GH issue for deleting leftover files
+        // when `zinniad` starts: https://github.com/filecoin-station/zinnia/issues/245
+        temp_dir: None,
+        ..lassie_config()
+    })
+    .context("cannot initialize the IPFS retrieval client Lassie")?;
+
+    Ok(Arc::new(lassie_daemon))
+}
+
+/// `zinnia run` keeps the KV data in the project directory next to the main module, zinniad uses
+/// the Station's state directory.
+fn project_kv_store_dir(main_module: &ModuleSpecifier) -> Result<PathBuf> {
+    Ok(get_module_root(main_module)?.join(".zinnia").join("kv"))
+}
+
+fn build_runtime_config(
+    lassie_daemon: Arc<lassie::Daemon>,
+    remote: &RemoteImportArgs,
+    kv_store_dir: Option<PathBuf>,
+) -> BootstrapOptions {
+    BootstrapOptions {
         zinnia_version: env!("CARGO_PKG_VERSION"),
+        // Remote imports are allowed during development only, zinniad does not support them
+        remote_modules: Some(remote_modules_config(remote)),
+        kv_store: kv_store_dir.map(|dir| KvStoreConfig {
+            dir,
+            quota: DEFAULT_KV_QUOTA,
+        }),
+        watchdog_timeout: Some(DEFAULT_WATCHDOG_TIMEOUT),
         ..BootstrapOptions::new(
             format!("zinnia/{}", env!("CARGO_PKG_VERSION")),
             Rc::new(ConsoleReporter::new(Duration::from_millis(500))),
-            Arc::clone(&lassie_daemon),
+            lassie_daemon,
             None,
         )
+    }
+}
+
+async fn bundle(
+    entry: String,
+    output: PathBuf,
+    source_map: bool,
+    import_map: Option<PathBuf>,
+    remote: &RemoteImportArgs,
+) -> Result<()> {
+    let entry = resolve_path(
+        &entry,
+        &std::env::current_dir().context("unable to get current working directory")?,
+    )?;
+    let options = BundleOptions {
+        agent_version: format!("zinnia/{}", env!("CARGO_PKG_VERSION")),
+        import_map: import_map.as_deref().map(read_import_map).transpose()?,
+        remote_modules: Some(remote_modules_config(remote)),
+        inline_source_map: source_map,
     };
+    let code = bundle_module(&entry, options).await?;
 
-    #[allow(clippy::let_unit_value)]
-    let module_output = run_js_module(&main_module, &runtime_config).await?;
+    if let Some(dir) = output.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Cannot create directory {}", dir.display()))?;
+    }
+    std::fs::write(&output, code)
+        .with_context(|| format!("Cannot write the bundle to {}", output.display()))?;
+    println!("Bundled {entry} to {}", output.display());
+    Ok(())
+}
 
-    Ok(RunOutput {
-        module_output,
-        lassie_daemon,
-    })
+fn remote_modules_config(remote: &RemoteImportArgs) -> RemoteModulesConfig {
+    RemoteModulesConfig {
+        cache_dir: remote_modules_cache_dir(),
+        lockfile: remote.lock.clone(),
+        frozen: remote.frozen,
+    }
+}
+
+/// Remote modules are cached in `ZINNIA_CACHE_DIR` when set, otherwise in the user's cache
+/// directory, e.g. `~/.cache/zinnia/remote` on Linux.
+fn remote_modules_cache_dir() -> PathBuf {
+    match std::env::var_os("ZINNIA_CACHE_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::cache_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("zinnia")
+            .join("remote"),
+    }
+}
+
+fn display_relative(path: &Path, cwd: &Path) -> String {
+    path.strip_prefix(cwd).unwrap_or(path).display().to_string()
+}
+
+fn format_error(error: &Error) -> String {
+    match error.downcast_ref::<JsError>() {
+        Some(e) => format_js_error(e),
+        None => format!("{error:?}"),
+    }
 }
 
 fn exit_with_error(error: Error) {
     // Inspired by unwrap_or_exit<T> from Deno's `cli/main.rs`
     // https://github.com/denoland/deno/blob/34bfa2cb2c1f0f74a94ced8fc164e81cc91cb9f4/cli/main.rs
-    let mut error_string = format!("{error:?}");
+    let error_string = format_error(&error);
     let error_code = 1;
 
-    if let Some(e) = error.downcast_ref::<JsError>() {
-        error_string = format_js_error(e);
-    }
-
     eprintln!(
         "{}: {}",
         colors::red_bold("error"),
@@ -128,10 +424,20 @@ mod tests {
             .write_str("/* no-op */")
             .expect("cannot write to dummy.js");
 
-        let RunOutput { lassie_daemon, .. } =
-            run_module(mod_js.path().to_string_lossy().to_string())
-                .await
-                .expect("cannot run dummy.js");
+        let RunOutput { lassie_daemon, .. } = run_module(
+            mod_js.path().to_string_lossy().to_string(),
+            None,
+            &InspectArgs {
+                inspect: None,
+                inspect_brk: None,
+            },
+            &RemoteImportArgs {
+                lock: PathBuf::from("zinnia.lock"),
+                frozen: false,
+            },
+        )
+        .await
+        .expect("cannot run dummy.js");
 
         assert!(
             lassie_daemon.access_token().is_some(),
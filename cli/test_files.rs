@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use walkdir::{DirEntry, WalkDir};
+
+use zinnia_runtime::anyhow::{anyhow, Context, Result};
+
+/// Files considered as tests when searching a directory.
+const DEFAULT_PATTERNS: &[&str] = &["**/*.test.{js,ts}", "**/test/**/*.{js,ts}"];
+
+/// Find test files to run.
+///
+/// Each item in `paths` can be a file, a directory to search for test files (see
+/// `DEFAULT_PATTERNS`) or a glob pattern matched against paths relative to `cwd`. When `paths` is
+/// empty, we search the current working directory.
+pub fn find_test_files(paths: &[String], cwd: &Path) -> Result<Vec<PathBuf>> {
+    let mut test_files = Vec::new();
+
+    if paths.is_empty() {
+        test_files.extend(walk(cwd, &build_globset(DEFAULT_PATTERNS)?));
+    }
+
+    for arg in paths {
+        let path = cwd.join(arg);
+        let found = if path.is_file() {
+            vec![path]
+        } else if path.is_dir() {
+            walk(&path, &build_globset(DEFAULT_PATTERNS)?)
+        } else {
+            walk(cwd, &build_globset(&[arg.as_str()])?)
+        };
+
+        if found.is_empty() {
+            return Err(anyhow!("No test files found matching {arg:?}"));
+        }
+        test_files.extend(found);
+    }
+
+    test_files.sort();
+    test_files.dedup();
+    Ok(test_files)
+}
+
+fn build_globset(patterns: &[&str]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            // `*` should not match path separators, only `**` should
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob pattern {pattern:?}"))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+/// List all files in `root` with a path (relative to `root`) matching the globset.
+fn walk(root: &Path, globset: &GlobSet) -> Vec<PathBuf> {
+    WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|entry| entry.depth() == 0 || !is_ignored(entry))
+        // Skip entries we cannot read, e.g. because of insufficient permissions
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.strip_prefix(root)
+                .map(|relative| globset.is_match(relative))
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+fn is_ignored(entry: &DirEntry) -> bool {
+    let name = entry.file_name().to_string_lossy();
+    name.starts_with('.') || name == "node_modules"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use pretty_assertions::assert_eq;
+
+    fn setup_project() -> assert_fs::TempDir {
+        let project = assert_fs::TempDir::new().unwrap();
+        for file in [
+            "lib.js",
+            "lib.test.js",
+            "src/util.js",
+            "src/util.test.js",
+            "src/types.test.ts",
+            "src/types.d.ts",
+            "test/smoke.js",
+            "test/helpers/setup.js",
+            "test/fixture.json",
+            "node_modules/dep/dep.test.js",
+            ".git/hooks/hook.test.js",
+        ] {
+            project.child(file).touch().unwrap();
+        }
+        project
+    }
+
+    fn relative(project: &assert_fs::TempDir, files: Vec<PathBuf>) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| {
+                f.strip_prefix(project.path())
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_test_files_in_cwd() {
+        let project = setup_project();
+        let files = find_test_files(&[], project.path()).unwrap();
+        assert_eq!(
+            relative(&project, files),
+            [
+                "lib.test.js",
+                "src/types.test.ts",
+                "src/util.test.js",
+                "test/helpers/setup.js",
+                "test/smoke.js"
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_test_files_in_directory() {
+        let project = setup_project();
+        let files = find_test_files(&["src".into()], project.path()).unwrap();
+        assert_eq!(
+            relative(&project, files),
+            ["src/types.test.ts", "src/util.test.js"]
+        );
+    }
+
+    #[test]
+    fn accepts_files_and_globs() {
+        let project = setup_project();
+        let files =
+            find_test_files(&["lib.js".into(), "test/*.js".into()], project.path()).unwrap();
+        assert_eq!(relative(&project, files), ["lib.js", "test/smoke.js"]);
+    }
+
+    #[test]
+    fn rejects_patterns_without_matches() {
+        let project = setup_project();
+        let err = find_test_files(&["spec/**/*.js".into()], project.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"No test files found matching "spec/**/*.js""#
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use clap::ValueEnum;
+use console_static_text::ansi::strip_ansi_codes;
+
+use zinnia_runtime::anyhow::{Context, Result};
+use zinnia_runtime::deno_core::serde_json::{self, json};
+use zinnia_runtime::{TestOutcome, TestResults, TestStatus};
+
+/// Machine-readable formats of test reports.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReportFormat {
+    /// JUnit XML, supported by most CI servers
+    Junit,
+    /// Test Anything Protocol, version 13
+    Tap,
+    /// JSON summary including all test outcomes
+    Json,
+}
+
+/// Results of a single test file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReport {
+    /// Path of the test file, relative to the working directory
+    pub path: String,
+    pub results: TestResults,
+    /// The error preventing the test runner from running tests in the file, e.g. a syntax error.
+    pub error: Option<String>,
+}
+
+impl FileReport {
+    /// Create the report, removing ANSI colors from error messages.
+    pub fn new(path: String, mut results: TestResults, error: Option<String>) -> Self {
+        for test in &mut results.tests {
+            if let Some(error) = &mut test.error {
+                *error = strip_ansi_codes(error).to_string();
+            }
+        }
+        Self {
+            path,
+            results,
+            error: error.map(|e| strip_ansi_codes(&e).to_string()),
+        }
+    }
+}
+
+/// Write the test report in the given format to `report_file`.
+pub fn write_report(
+    format: ReportFormat,
+    files: &[FileReport],
+    duration_ms: u64,
+    report_file: &Path,
+) -> Result<()> {
+    let content = match format {
+        ReportFormat::Junit => format_junit(files, duration_ms),
+        ReportFormat::Tap => format_tap(files),
+        ReportFormat::Json => format_json(files, duration_ms),
+    };
+    std::fs::write(report_file, content)
+        .with_context(|| format!("cannot write test report to {}", report_file.display()))
+}
+
+fn format_junit(files: &[FileReport], duration_ms: u64) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        r#"<testsuites name="zinnia test" tests="{}" failures="{}" errors="{}" skipped="{}" time="{}">"#,
+        files
+            .iter()
+            .map(|f| f.results.tests.len() + usize::from(f.error.is_some()))
+            .sum::<usize>(),
+        files.iter().map(|f| f.results.failed).sum::<u32>(),
+        files.iter().filter(|f| f.error.is_some()).count(),
+        files.iter().map(|f| f.results.skipped).sum::<u32>(),
+        seconds(duration_ms),
+    );
+
+    for file in files {
+        let path = escape_xml(&file.path);
+        let _ = writeln!(
+            xml,
+            r#"  <testsuite name="{path}" tests="{}" failures="{}" errors="{}" skipped="{}" time="{}">"#,
+            file.results.tests.len() + usize::from(file.error.is_some()),
+            file.results.failed,
+            u32::from(file.error.is_some()),
+            file.results.skipped,
+            seconds(file.results.duration_ms),
+        );
+
+        if let Some(error) = &file.error {
+            let _ = writeln!(xml, r#"    <testcase name="{path}" classname="{path}">"#);
+            let _ = writeln!(
+                xml,
+                r#"      <error message="{}">{}</error>"#,
+                escape_xml(first_line(error)),
+                escape_xml(error),
+            );
+            let _ = writeln!(xml, "    </testcase>");
+        }
+
+        for test in &file.results.tests {
+            let _ = write!(
+                xml,
+                r#"    <testcase name="{}" classname="{path}" file="{path}""#,
+                escape_xml(&test.name)
+            );
+            if let Some(location) = &test.location {
+                let _ = write!(xml, r#" line="{}""#, location.line_number);
+            }
+            let _ = write!(xml, r#" time="{}""#, seconds(test.duration_ms));
+
+            match (test.status, &test.error) {
+                (TestStatus::Passed, _) => {
+                    let _ = writeln!(xml, "/>");
+                }
+                (TestStatus::Skipped, _) => {
+                    let _ = writeln!(xml, ">\n      <skipped/>\n    </testcase>");
+                }
+                (TestStatus::Failed, error) => {
+                    let error = error.as_deref().unwrap_or_default();
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                        escape_xml(first_line(error)),
+                        escape_xml(error),
+                    );
+                }
+            }
+        }
+
+        let _ = writeln!(xml, "  </testsuite>");
+    }
+
+    let _ = writeln!(xml, "</testsuites>");
+    xml
+}
+
+fn format_tap(files: &[FileReport]) -> String {
+    let entries: Vec<(String, Option<&TestOutcome>, Option<&String>)> = files
+        .iter()
+        .flat_map(|file| {
+            let load_error = file
+                .error
+                .as_ref()
+                .map(|error| (file.path.clone(), None, Some(error)));
+            let tests = file.results.tests.iter().map(|test| {
+                (
+                    format!("{} > {}", file.path, test.name),
+                    Some(test),
+                    test.error.as_ref(),
+                )
+            });
+            load_error.into_iter().chain(tests)
+        })
+        .collect();
+
+    let mut tap = format!("TAP version 13\n1..{}\n", entries.len());
+    for (ix, (name, test, error)) in entries.into_iter().enumerate() {
+        let ok = match test {
+            Some(test) => test.status != TestStatus::Failed,
+            None => false,
+        };
+        let _ = write!(
+            tap,
+            "{} {} - {}",
+            if ok { "ok" } else { "not ok" },
+            ix + 1,
+            // `#` starts a directive in TAP
+            name.replace('#', "\\#")
+        );
+        if test.map(|t| t.status) == Some(TestStatus::Skipped) {
+            let _ = write!(tap, " # SKIP");
+        }
+        let _ = writeln!(tap);
+
+        // YAML diagnostics block
+        let mut details = Vec::new();
+        if let Some(test) = test {
+            if test.status != TestStatus::Skipped {
+                details.push(format!("duration_ms: {}", test.duration_ms));
+            }
+            if let Some(location) = &test.location {
+                details.push(format!(
+                    "location: \"{}:{}:{}\"",
+                    location.file_name, location.line_number, location.column_number
+                ));
+            }
+        }
+        if let Some(error) = error {
+            let mut block = String::from("error: |-");
+            for line in error.lines() {
+                let _ = write!(block, "\n    {line}");
+            }
+            details.push(block);
+        }
+        if !details.is_empty() {
+            let _ = writeln!(tap, "  ---");
+            for detail in details {
+                let _ = writeln!(tap, "  {detail}");
+            }
+            let _ = writeln!(tap, "  ...");
+        }
+    }
+    tap
+}
+
+fn format_json(files: &[FileReport], duration_ms: u64) -> String {
+    let files: Vec<_> = files
+        .iter()
+        .map(|file| {
+            json!({
+                "path": file.path,
+                "error": file.error,
+                "passed": file.results.passed,
+                "failed": file.results.failed,
+                "skipped": file.results.skipped,
+                "durationMs": file.results.duration_ms,
+                "tests": file.results.tests,
+            })
+        })
+        .collect();
+
+    let report = json!({
+        "passed": files.iter().map(|f| f["passed"].as_u64().unwrap_or_default()).sum::<u64>(),
+        "failed": files.iter().map(|f| f["failed"].as_u64().unwrap_or_default()).sum::<u64>(),
+        "skipped": files.iter().map(|f| f["skipped"].as_u64().unwrap_or_default()).sum::<u64>(),
+        "durationMs": duration_ms,
+        "files": files,
+    });
+    // Serializing `serde_json::Value` cannot fail
+    serde_json::to_string_pretty(&report).unwrap() + "\n"
+}
+
+fn seconds(duration_ms: u64) -> String {
+    format!("{:.3}", duration_ms as f64 / 1000.0)
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters are not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use zinnia_runtime::TestLocation;
+
+    fn sample_report() -> Vec<FileReport> {
+        let location = |line_number| {
+            Some(TestLocation {
+                file_name: "file:///project/smoke.test.js".into(),
+                line_number,
+                column_number: 1,
+            })
+        };
+
+        vec![
+            FileReport {
+                path: "smoke.test.js".into(),
+                results: TestResults {
+                    passed: 1,
+                    failed: 1,
+                    skipped: 1,
+                    duration_ms: 15,
+                    tests: vec![
+                        TestOutcome {
+                            name: "api > GET <html>".into(),
+                            status: TestStatus::Passed,
+                            duration_ms: 12,
+                            location: location(3),
+                            error: None,
+                        },
+                        TestOutcome {
+                            name: "fails".into(),
+                            status: TestStatus::Failed,
+                            duration_ms: 2,
+                            location: location(7),
+                            error: Some("Error: boom\n    at fn".into()),
+                        },
+                        TestOutcome {
+                            name: "skipped".into(),
+                            status: TestStatus::Skipped,
+                            duration_ms: 0,
+                            location: location(11),
+                            error: None,
+                        },
+                    ],
+                },
+                error: None,
+            },
+            FileReport {
+                path: "broken.test.js".into(),
+                results: TestResults::default(),
+                error: Some("Uncaught SyntaxError: Unexpected token".into()),
+            },
+        ]
+    }
+
+    #[test]
+    fn strips_ansi_codes_from_errors() {
+        let mut results = TestResults::default();
+        results.tests.push(TestOutcome {
+            name: "fails".into(),
+            status: TestStatus::Failed,
+            duration_ms: 0,
+            location: None,
+            error: Some("\x1b[31mError\x1b[0m: boom".into()),
+        });
+        let report = FileReport::new(
+            "smoke.test.js".into(),
+            results,
+            Some("\x1b[1;31merror\x1b[0m".into()),
+        );
+        assert_eq!(
+            report.results.tests[0].error.as_deref(),
+            Some("Error: boom")
+        );
+        assert_eq!(report.error.as_deref(), Some("error"));
+    }
+
+    #[test]
+    fn formats_junit() {
+        assert_eq!(
+            format_junit(&sample_report(), 1234),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="zinnia test" tests="4" failures="1" errors="1" skipped="1" time="1.234">
+  <testsuite name="smoke.test.js" tests="3" failures="1" errors="0" skipped="1" time="0.015">
+    <testcase name="api &gt; GET &lt;html&gt;" classname="smoke.test.js" file="smoke.test.js" line="3" time="0.012"/>
+    <testcase name="fails" classname="smoke.test.js" file="smoke.test.js" line="7" time="0.002">
+      <failure message="Error: boom">Error: boom
+    at fn</failure>
+    </testcase>
+    <testcase name="skipped" classname="smoke.test.js" file="smoke.test.js" line="11" time="0.000">
+      <skipped/>
+    </testcase>
+  </testsuite>
+  <testsuite name="broken.test.js" tests="1" failures="0" errors="1" skipped="0" time="0.000">
+    <testcase name="broken.test.js" classname="broken.test.js">
+      <error message="Uncaught SyntaxError: Unexpected token">Uncaught SyntaxError: Unexpected token</error>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+
+    #[test]
+    fn formats_tap() {
+        assert_eq!(
+            format_tap(&sample_report()),
+            r#"TAP version 13
+1..4
+ok 1 - smoke.test.js > api > GET <html>
+  ---
+  duration_ms: 12
+  location: "file:///project/smoke.test.js:3:1"
+  ...
+not ok 2 - smoke.test.js > fails
+  ---
+  duration_ms: 2
+  location: "file:///project/smoke.test.js:7:1"
+  error: |-
+    Error: boom
+        at fn
+  ...
+ok 3 - smoke.test.js > skipped # SKIP
+  ---
+  location: "file:///project/smoke.test.js:11:1"
+  ...
+not ok 4 - broken.test.js
+  ---
+  error: |-
+    Uncaught SyntaxError: Unexpected token
+  ...
+"#
+        );
+    }
+
+    #[test]
+    fn formats_json() {
+        let report: serde_json::Value =
+            serde_json::from_str(&format_json(&sample_report(), 1234)).unwrap();
+        assert_eq!(report["passed"], 1);
+        assert_eq!(report["failed"], 1);
+        assert_eq!(report["skipped"], 1);
+        assert_eq!(report["durationMs"], 1234);
+        assert_eq!(
+            report["files"][0]["tests"][1],
+            json!({
+                "name": "fails",
+                "status": "failed",
+                "durationMs": 2,
+                "location": {
+                    "fileName": "file:///project/smoke.test.js",
+                    "lineNumber": 7,
+                    "columnNumber": 1,
+                },
+                "error": "Error: boom\n    at fn",
+            })
+        );
+        assert_eq!(
+            report["files"][1]["error"],
+            "Uncaught SyntaxError: Unexpected token"
+        );
+    }
+}
@@ -45,6 +45,176 @@ Zinnia.jobCompleted();
     Ok(())
 }
 
+#[test]
+fn exit_with_code() -> Result<(), Box<dyn std::error::Error>> {
+    let mod_js = assert_fs::NamedTempFile::new("exit-mod.js")?;
+    mod_js.write_str(
+        r#"
+console.log("before exit");
+Zinnia.exit(3);
+console.log("after exit");
+"#,
+    )?;
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .args(["run", &mod_js.path().display().to_string()])
+        .output()?;
+
+    assert_eq!(output.status.code(), Some(3), "exit code");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before exit\n");
+
+    Ok(())
+}
+
+#[test]
+fn run_with_import_map() -> Result<(), Box<dyn std::error::Error>> {
+    let project = assert_fs::TempDir::new()?;
+    project
+        .child("vendor/greeter.js")
+        .write_str(r#"export const greet = (name) => `Hello, ${name}!`;"#)?;
+    project
+        .child("import_map.json")
+        .write_str(r#"{ "imports": { "greeter": "./vendor/greeter.js" } }"#)?;
+    project.child("main.js").write_str(
+        r#"
+import { greet } from "greeter";
+console.log(greet("Zinnia"));
+"#,
+    )?;
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .current_dir(project.path())
+        .args(["run", "--import-map", "import_map.json", "main.js"])
+        .output()?;
+
+    assert_eq!(
+        CmdResult::from(&output),
+        CmdResult {
+            exit_ok: true,
+            stdout: "Hello, Zinnia!\n".into(),
+            stderr: "".into(),
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn frozen_rejects_unpinned_remote_modules() -> Result<(), Box<dyn std::error::Error>> {
+    let project = assert_fs::TempDir::new()?;
+    project
+        .child("main.js")
+        .write_str(r#"import { x } from "https://example.com/mod.js";"#)?;
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .env("ZINNIA_CACHE_DIR", project.path().join("cache"))
+        .current_dir(project.path())
+        .args(["run", "--frozen", "main.js"])
+        .output()?;
+
+    let result = CmdResult::from(&output);
+    assert!(!result.exit_ok, "zinnia run should fail\n{result:?}");
+    assert!(
+        result
+            .stderr
+            .contains("The remote module is not pinned in the lockfile zinnia.lock"),
+        "stderr should explain the error\n{result:?}"
+    );
+    assert!(
+        !project.path().join("zinnia.lock").exists(),
+        "--frozen should not create the lockfile"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bundle_module() -> Result<(), Box<dyn std::error::Error>> {
+    let project = assert_fs::TempDir::new()?;
+    project
+        .child("src/lib.ts")
+        .write_str(r#"export const greet = (name: string): string => `Hello, ${name}!`;"#)?;
+    project
+        .child("src/config.json")
+        .write_str(r#"{ "name": "Zinnia" }"#)?;
+    project.child("src/main.js").write_str(
+        r#"
+import { assert } from "zinnia:assert";
+import { greet } from "./lib.ts";
+import config from "./config.json" with { type: "json" };
+assert(config.name);
+console.log(greet(config.name));
+"#,
+    )?;
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .current_dir(project.path())
+        .args(["bundle", "src/main.js", "-o", "dist/module.js"])
+        .output()?;
+    let result = CmdResult::from(&output);
+    assert!(result.exit_ok, "zinnia bundle should succeed\n{result:?}");
+
+    let bundle = std::fs::read_to_string(project.path().join("dist/module.js"))?;
+    assert!(
+        bundle.contains(r#"from "zinnia:assert""#),
+        "built-in modules should stay external\n{bundle}"
+    );
+    assert!(
+        !bundle.contains("./lib.ts"),
+        "local modules should be inlined\n{bundle}"
+    );
+
+    // The bundle must work on its own
+    std::fs::remove_dir_all(project.path().join("src"))?;
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .current_dir(project.path())
+        .args(["run", "dist/module.js"])
+        .output()?;
+    assert_eq!(
+        CmdResult::from(&output),
+        CmdResult {
+            exit_ok: true,
+            stdout: "Hello, Zinnia!\n".into(),
+            stderr: "".into(),
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bundle_rejects_files_outside_module_root() -> Result<(), Box<dyn std::error::Error>> {
+    let project = assert_fs::TempDir::new()?;
+    project
+        .child("shared.js")
+        .write_str("export const answer = 42;")?;
+    project
+        .child("src/main.js")
+        .write_str(r#"import { answer } from "../shared.js"; console.log(answer);"#)?;
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .current_dir(project.path())
+        .args(["bundle", "src/main.js", "-o", "dist/module.js"])
+        .output()?;
+
+    let result = CmdResult::from(&output);
+    assert!(!result.exit_ok, "zinnia bundle should fail\n{result:?}");
+    assert!(
+        result
+            .stderr
+            .contains("Cannot import files outside of the module root directory"),
+        "stderr should explain the error\n{result:?}"
+    );
+
+    Ok(())
+}
+
 #[test]
 fn report_js_stack_trace() -> Result<(), Box<dyn std::error::Error>> {
     let mod_js = assert_fs::NamedTempFile::new("error-mod.js")?;
@@ -94,6 +264,225 @@ function fail() {
     Ok(())
 }
 
+#[test]
+fn report_ts_stack_trace() -> Result<(), Box<dyn std::error::Error>> {
+    let mod_ts = assert_fs::NamedTempFile::new("error-mod.ts")?;
+    mod_ts.write_str(
+        r#"
+interface Options {
+  message: string;
+}
+
+fail({ message: "boom!" });
+
+function fail({ message }: Options): never {
+  throw new Error(message);
+}
+"#,
+    )?;
+
+    let mod_ts_str = mod_ts.path().display().to_string();
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .args(["run", &mod_ts_str])
+        .output()?;
+
+    let mod_url = resolve_path(
+        &mod_ts_str,
+        &std::env::current_dir().context("unable to get current working directory")?,
+    )?;
+
+    let expected_stderr = format!(
+        r#"
+  error: Uncaught Error: boom!
+  throw new Error(message);
+        ^
+    at fail ({mod_url}:9:9)
+    at {mod_url}:6:1
+"#
+    )
+    .trim_start()
+    .to_string();
+
+    assert_eq!(
+        CmdResult::from(&output),
+        CmdResult {
+            exit_ok: false,
+            stdout: "".into(),
+            stderr: expected_stderr,
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn kv_store_is_kept_next_to_the_module() -> Result<(), Box<dyn std::error::Error>> {
+    let project = assert_fs::TempDir::new()?;
+    let cwd = assert_fs::TempDir::new()?;
+    project
+        .child("main.js")
+        .write_str(r#"await Zinnia.kv.set("answer", 42);"#)?;
+    project.child("kv.test.js").write_str(
+        r#"
+import { test } from "zinnia:test";
+import { assertEquals } from "zinnia:assert";
+test("tests start with an empty store", async () => {
+  assertEquals(await Zinnia.kv.get("answer"), undefined);
+});
+"#,
+    )?;
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .current_dir(cwd.path())
+        .args([
+            "run",
+            &project.child("main.js").path().display().to_string(),
+        ])
+        .output()?;
+    assert!(output.status.success(), "{:?}", CmdResult::from(&output));
+    assert!(project.child(".zinnia/kv").path().is_dir());
+    assert!(!cwd.child(".zinnia").path().exists());
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .current_dir(project.path())
+        .args(["test"])
+        .output()?;
+    assert!(output.status.success(), "{:?}", CmdResult::from(&output));
+
+    Ok(())
+}
+
+#[test]
+fn run_tests_in_multiple_files() -> Result<(), Box<dyn std::error::Error>> {
+    let project = assert_fs::TempDir::new()?;
+    project.child("lib.test.js").write_str(
+        r#"
+import { test } from "zinnia:test";
+test("passing test", () => {});
+"#,
+    )?;
+    project.child("test/smoke.js").write_str(
+        r#"
+import { test } from "zinnia:test";
+test("failing test", () => { throw new Error("boom"); });
+"#,
+    )?;
+    project
+        .child("lib.js")
+        .write_str("throw new Error('not a test file');")?;
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .current_dir(project.path())
+        .args(["test"])
+        .output()?;
+
+    // The test runner prints ANSI colors even when NO_COLOR is set
+    let result = CmdResult::from(&output).map_stdout(|s| strip_durations(&strip_ansi_codes(s)));
+    assert!(!result.exit_ok, "zinnia test should fail\n{result:?}");
+    assert!(
+        result
+            .stdout
+            .contains("lib.test.js\n  ✔ passing test (XXms)"),
+        "stdout should include results of lib.test.js\n{result:?}"
+    );
+    assert!(
+        result.stdout.contains("smoke.js\n  ✖ failing test (XXms)"),
+        "stdout should include results of test/smoke.js\n{result:?}"
+    );
+    assert!(
+        result
+            .stdout
+            .ends_with("FAIL | 2 files | 1 passed | 1 failed (XXms)\n\n"),
+        "stdout should end with the summary\n{result:?}"
+    );
+    assert!(
+        !result.stderr.contains("error"),
+        "failed tests should not be reported as an uncaught error\n{result:?}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn run_tests_matching_filter() -> Result<(), Box<dyn std::error::Error>> {
+    let project = assert_fs::TempDir::new()?;
+    project.child("api.test.js").write_str(
+        r#"
+import { describe, test } from "zinnia:test";
+describe("fetch", () => {
+  test("GET request", () => {});
+  test("POST request", () => {});
+});
+test("another GET request", () => { throw new Error("should have been filtered out"); });
+"#,
+    )?;
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .current_dir(project.path())
+        .args(["test", "--filter", "/^fetch > .* request$/"])
+        .output()?;
+
+    let result = CmdResult::from(&output).map_stdout(|s| strip_durations(&strip_ansi_codes(s)));
+    assert!(result.exit_ok, "zinnia test should pass\n{result:?}");
+    assert!(
+        !result.stdout.contains("another GET request"),
+        "stdout should not include tests filtered out\n{result:?}"
+    );
+    assert!(
+        result
+            .stdout
+            .ends_with("ok | 1 files | 2 passed | 0 failed (XXms)\n\n"),
+        "stdout should end with the summary\n{result:?}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn write_junit_test_report() -> Result<(), Box<dyn std::error::Error>> {
+    let project = assert_fs::TempDir::new()?;
+    project.child("smoke.test.js").write_str(
+        r#"
+import { test } from "zinnia:test";
+test("passing test", () => {});
+test("failing test", () => { throw new Error("boom"); });
+"#,
+    )?;
+
+    let output = Command::cargo_bin("zinnia")?
+        .env("NO_COLOR", "1")
+        .current_dir(project.path())
+        .args([
+            "test",
+            "--report-format",
+            "junit",
+            "--report-file",
+            "junit.xml",
+        ])
+        .output()?;
+
+    let result = CmdResult::from(&output);
+    assert!(!result.exit_ok, "zinnia test should fail\n{result:?}");
+
+    let report = std::fs::read_to_string(project.child("junit.xml").path())?;
+    assert!(
+        report.contains(r#"<testsuite name="smoke.test.js" tests="2" failures="1""#),
+        "the report should describe smoke.test.js\n{report}"
+    );
+    assert!(
+        report.contains(r#"<failure message="Error: boom">"#),
+        "the report should include the failure\n{report}"
+    );
+
+    Ok(())
+}
+
 // HELPERS
 
 #[derive(PartialEq)]
@@ -155,3 +544,19 @@ fn strip_timestamps(text: &str) -> String {
 
     TIME_PATTERN.replace_all(text, "[TIMESTAMP ").to_string()
 }
+
+fn strip_durations(text: &str) -> String {
+    lazy_static! {
+        static ref DURATION_PATTERN: Regex = Regex::new(r"\d+ms").unwrap();
+    }
+
+    DURATION_PATTERN.replace_all(text, "XXms").to_string()
+}
+
+fn strip_ansi_codes(text: &str) -> String {
+    lazy_static! {
+        static ref ANSI_PATTERN: Regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    }
+
+    ANSI_PATTERN.replace_all(text, "").to_string()
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use zinnia_runtime::anyhow::Result;
+use zinnia_runtime::{colors, LoadedFiles};
+
+/// How often to check the watched files for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Run the module via `run` and restart it whenever one of the files loaded by the module (or one
+/// of `extra_files`) changes. Runs until the process is terminated, e.g. via Ctrl+C.
+///
+/// `run` receives the collection where the runtime records the files it loads. When a file
+/// changes while the module is still running, the future returned by `run` is dropped, which
+/// tears down the runtime including the libp2p peer node.
+pub async fn run_and_watch<F, Fut>(
+    is_tty: bool,
+    extra_files: Vec<PathBuf>,
+    mut run: F,
+) -> Result<i32>
+where
+    F: FnMut(LoadedFiles) -> Fut,
+    Fut: Future<Output = Result<i32>>,
+{
+    loop {
+        if is_tty {
+            clear_screen();
+        }
+
+        let loaded_files = LoadedFiles::default();
+        for file in &extra_files {
+            loaded_files.insert(file.clone());
+        }
+        let mut changes = ChangeDetector::new(loaded_files.clone());
+
+        let run_outcome = tokio::select! {
+            result = run(loaded_files) => Some(result),
+            _ = changes.wait_for_change() => None,
+        };
+
+        let changed_file = match run_outcome {
+            // A file changed before the module finished
+            None => changes.last_change.take(),
+            Some(result) => {
+                match result {
+                    Ok(0) => eprintln!("\n{}", colors::green("Finished.")),
+                    Ok(code) => eprintln!(
+                        "\n{}",
+                        colors::red(format!("Finished with exit code {code}."))
+                    ),
+                    Err(err) => eprintln!(
+                        "\n{}: {}",
+                        colors::red_bold("error"),
+                        crate::format_error(&err).trim_start_matches("error: ")
+                    ),
+                }
+                eprintln!("{}", colors::gray("Watching for file changes..."));
+                changes.wait_for_change().await;
+                changes.last_change.take()
+            }
+        };
+
+        if let Some(path) = changed_file {
+            eprintln!(
+                "{}",
+                colors::gray(format!("File changed: {}. Restarting...", path.display()))
+            );
+        }
+    }
+}
+
+/// Detects changes of the watched files by polling their modification times. Polling works
+/// reliably with editors replacing files on save and with files that don't exist yet.
+struct ChangeDetector {
+    files: LoadedFiles,
+    /// The last known modification time of each file, `None` when the file does not exist.
+    mtimes: HashMap<PathBuf, Option<SystemTime>>,
+    last_change: Option<PathBuf>,
+}
+
+impl ChangeDetector {
+    fn new(files: LoadedFiles) -> Self {
+        Self {
+            files,
+            mtimes: HashMap::new(),
+            last_change: None,
+        }
+    }
+
+    /// Wait until one of the files changes. Files added to the collection while waiting are
+    /// watched from the moment they are first seen.
+    async fn wait_for_change(&mut self) {
+        loop {
+            for path in self.files.paths() {
+                let mtime = modified(&path);
+                match self.mtimes.insert(path.clone(), mtime) {
+                    Some(previous) if previous != mtime => {
+                        self.last_change = Some(path);
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn clear_screen() {
+    eprint!("\x1B[2J\x1B[1;1H");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[tokio::test]
+    async fn detects_changed_files() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let file = temp.child("mod.js");
+        file.write_str("console.log(1)").unwrap();
+
+        let files = LoadedFiles::default();
+        files.insert(file.path().to_path_buf());
+        let mut changes = ChangeDetector::new(files);
+
+        let wait = tokio::time::timeout(Duration::from_secs(2), changes.wait_for_change());
+        let modify = async {
+            tokio::time::sleep(POLL_INTERVAL * 2).await;
+            // Make sure the modification time changes even on file systems with 1s resolution
+            let later = SystemTime::now() + Duration::from_secs(10);
+            std::fs::File::options()
+                .write(true)
+                .open(file.path())
+                .unwrap()
+                .set_modified(later)
+                .unwrap();
+        };
+        let (result, _) = tokio::join!(wait, modify);
+        result.expect("the change was not detected");
+
+        assert_eq!(changes.last_change, Some(file.path().to_path_buf()));
+    }
+}
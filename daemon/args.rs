@@ -1,6 +1,12 @@
 use std::env;
 
 use clap::{command, Parser, Subcommand};
+use zinnia_runtime::{NetRule, DEFAULT_KV_QUOTA, DEFAULT_WATCHDOG_TIMEOUT};
+
+use crate::logger::LogFormat;
+
+/// The default heap limit of each module: 512 MiB.
+pub const DEFAULT_MAX_HEAP_SIZE: usize = 512 * 1024 * 1024;
 
 #[derive(Parser, PartialEq, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -21,12 +27,78 @@ pub struct CliArgs {
     #[arg(long, env, default_value_t = get_default_cache_dir(env::var), name = "CACHE DIR PATH")]
     pub cache_root: String,
 
-    /// List of modules to run, where each module is a single JS file. We don't make any assumptions
-    /// about the directory layout of modules. Paths are resolved relatively to the current working
+    /// How many times to restart a module that exited or crashed within 10 minutes before giving
+    /// up on the module. Set to 0 to disable restarts.
+    #[arg(long, env, default_value_t = 10, name = "MAX RESTARTS")]
+    pub max_restarts: u32,
+
+    /// The maximum size of the `Zinnia.kv` store of each module in bytes.
+    #[arg(long, env, default_value_t = DEFAULT_KV_QUOTA, name = "KV QUOTA BYTES")]
+    pub kv_quota: u64,
+
+    /// The maximum size of the JavaScript heap of each module in bytes. Modules exceeding the
+    /// limit are terminated and restarted.
+    #[arg(long, env, default_value_t = DEFAULT_MAX_HEAP_SIZE, name = "MAX HEAP SIZE BYTES")]
+    pub max_heap_size: usize,
+
+    /// Terminate modules that block the event loop for longer than this many seconds, e.g. by
+    /// running an infinite loop. Set to 0 to disable the watchdog.
+    #[arg(long, env, default_value_t = DEFAULT_WATCHDOG_TIMEOUT.as_secs(), name = "WATCHDOG TIMEOUT SECONDS")]
+    pub watchdog_timeout: u64,
+
+    /// Secret used to encrypt the libp2p identities of modules stored in the state directory.
+    /// Station must provide a random secret kept outside of the state directory. Changing the
+    /// secret gives all modules a new peer ID.
+    #[arg(long, env, hide_env_values = true, name = "PEER KEY SECRET")]
+    pub peer_key_secret: Secret,
+
+    /// Allow modules to access only these network destinations (comma-separated), e.g.
+    /// `example.com`, `*.example.com:443`, `1.2.3.0/24` or `/dns4/example.com/tcp/4001`.
+    /// All public destinations are allowed by default.
+    #[arg(long, env, value_delimiter = ',', name = "ALLOWED DESTINATIONS")]
+    pub allow_net: Vec<NetRule>,
+
+    /// Deny modules access to these network destinations (comma-separated). Takes precedence
+    /// over `--allow-net`.
+    #[arg(long, env, value_delimiter = ',', name = "DENIED DESTINATIONS")]
+    pub deny_net: Vec<NetRule>,
+
+    /// Allow modules to access private, loopback and link-local addresses. Without this flag,
+    /// such addresses are accessible only when listed in `--allow-net`.
+    #[arg(long, env)]
+    pub allow_private_net: bool,
+
+    /// Serve Prometheus metrics at `http://127.0.0.1:{PORT}/metrics`. The metrics endpoint is
+    /// disabled by default.
+    #[arg(long, env, name = "PORT")]
+    pub metrics_port: Option<u16>,
+
+    /// The format of log messages printed to stderr, including console logs of modules.
+    #[arg(long, env, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// List of modules to run. Each module is either a directory containing the module manifest
+    /// `zinnia.json`, or a single JS file. Paths are resolved relatively to the current working
     /// directory.
     pub files: Vec<String>,
 }
 
+/// A secret value that must not be printed, e.g. when logging the configuration.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(pub String);
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
 #[derive(Subcommand, PartialEq, Debug)]
 pub enum Commands {
     Run {
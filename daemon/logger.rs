@@ -0,0 +1,85 @@
+use std::io::Write;
+
+use clap::ValueEnum;
+use serde_json::json;
+
+/// Formats of log messages printed to stderr.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LogFormat {
+    /// Human-readable text, one or more lines per message
+    #[default]
+    Text,
+    /// One JSON object per line with the timestamp, level, module and message
+    Json,
+}
+
+/// Console logs of modules use the target `module:{name}`, see `StationReporter`.
+const MODULE_TARGET_PREFIX: &str = "module:";
+
+pub fn setup_logger(format: LogFormat) {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(log::LevelFilter::Info);
+    builder.parse_default_env();
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let timestamp = buf.timestamp_millis().to_string();
+            writeln!(buf, "{}", format_json_record(&timestamp, record))
+        });
+    }
+    builder.init();
+}
+
+/// Build the JSON record of the log message. Newlines in the message are escaped, therefore
+/// multi-line messages are printed as a single line.
+fn format_json_record(timestamp: &str, record: &log::Record) -> serde_json::Value {
+    let module = record.target().strip_prefix(MODULE_TARGET_PREFIX);
+    json!({
+        "timestamp": timestamp,
+        "level": record.level().as_str().to_lowercase(),
+        "module": module,
+        "message": record.args().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn formats_module_logs_as_json() {
+        let output = format_json_record(
+            "2024-01-02T03:04:05.678Z",
+            &log::Record::builder()
+                .level(log::Level::Warn)
+                .target("module:saturn")
+                .args(format_args!("first line\nsecond line"))
+                .build(),
+        )
+        .to_string();
+        assert!(!output.contains('\n'), "output must be a single line");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+            json!({
+                "timestamp": "2024-01-02T03:04:05.678Z",
+                "level": "warn",
+                "module": "saturn",
+                "message": "first line\nsecond line",
+            })
+        );
+    }
+
+    #[test]
+    fn formats_daemon_logs_without_module() {
+        let output = format_json_record(
+            "2024-01-02T03:04:05.678Z",
+            &log::Record::builder()
+                .level(log::Level::Info)
+                .target("zinniad")
+                .args(format_args!("Starting module"))
+                .build(),
+        );
+        assert_eq!(output["module"], serde_json::Value::Null);
+        assert_eq!(output["level"], "info");
+    }
+}
@@ -1,30 +1,39 @@
 mod args;
+mod logger;
+mod metrics_server;
+mod modules;
+mod peer_identity;
 mod state;
 mod station_reporter;
+mod supervisor;
 
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use args::CliArgs;
 use clap::Parser;
+use tokio::sync::mpsc;
 
 use zinnia_runtime::anyhow::{anyhow, Context, Error, Result};
-use zinnia_runtime::{
-    get_module_root, lassie, lassie_config, resolve_path, run_js_module, BootstrapOptions,
-};
+use zinnia_runtime::{lassie, lassie_config, Metrics, NetPermissions};
 
-use crate::station_reporter::{log_started_activity, StationReporter};
+use crate::logger::setup_logger;
+use crate::metrics_server::serve_metrics;
+use crate::modules::{spawn_module, ModuleConfig, ModuleExit, SharedConfig};
+use crate::station_reporter::{log_started_activity, JobCounters};
+use crate::supervisor::RestartPolicy;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    setup_logger();
     let cli_args = CliArgs::parse_from(std::env::args());
+    setup_logger(cli_args.log_format);
 
     match run(cli_args).await {
-        Ok(_) => (),
+        Ok(RunOutput { exit_code: 0, .. }) => (),
+        Ok(RunOutput { exit_code, .. }) => std::process::exit(exit_code),
         Err(err) => exit_with_error(err),
     }
 }
@@ -35,13 +44,45 @@ async fn run(config: CliArgs) -> Result<RunOutput> {
     if config.files.is_empty() {
         return Err(anyhow!("You must provide at least one module to run."));
     }
-    if config.files.len() > 1 {
-        return Err(anyhow!(
-            "We do not yet support running more than one module."
-        ));
+
+    let cwd = std::env::current_dir().context("unable to get current working directory")?;
+    let modules = config
+        .files
+        .iter()
+        .map(|file| ModuleConfig::resolve(file, &cwd))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Never derive the encryption key from public data like the Station ID, anybody knowing it
+    // could decrypt the key files
+    let peer_key_secret = config.peer_key_secret.0;
+    if peer_key_secret.is_empty() {
+        return Err(anyhow!("PEER_KEY_SECRET must not be empty."));
     }
 
-    let state_file = PathBuf::from(config.state_root).join("state.json");
+    let mut module_names = HashSet::new();
+    for module in &modules {
+        if !module_names.insert(module.name.as_str()) {
+            return Err(anyhow!(
+                "Module names must be unique. Found duplicate module name: {}",
+                module.name
+            ));
+        }
+    }
+
+    // Different module names can map to the same state directory, e.g. `mods/a` and `mods_a`
+    let mut state_dirs = HashMap::new();
+    for module in &modules {
+        if let Some(other) = state_dirs.insert(module.state_dir_name(), module.name.as_str()) {
+            return Err(anyhow!(
+                "Modules {other} and {} would share the state directory {}. Rename one of them.",
+                module.name,
+                module.state_dir_name()
+            ));
+        }
+    }
+
+    let state_root = PathBuf::from(config.state_root);
+    let state_file = state_root.join("state.json");
     log::debug!("Using state file: {}", state_file.display());
     let lassie_temp_dir = PathBuf::from(config.cache_root).join("lassie");
 
@@ -58,59 +99,88 @@ async fn run(config: CliArgs) -> Result<RunOutput> {
 
     log_started_activity();
 
-    let file = &config.files[0];
-
-    // TODO: configurable module name and version
-    // https://github.com/filecoin-station/zinnia/issues/147
-    let module_name = file.trim_end_matches(".js");
-
-    let main_module = resolve_path(
-        file,
-        &std::env::current_dir().context("unable to get current working directory")?,
+    let job_counters = JobCounters::load(
+        state_file,
+        Duration::from_millis(200),
+        modules.iter().map(|m| m.name.as_str()),
     )?;
-    let module_root = get_module_root(&main_module)?;
 
-    let runtime_config = BootstrapOptions {
-        zinnia_version: env!("CARGO_PKG_VERSION"),
-        agent_version: format!("zinniad/{} {module_name}", env!("CARGO_PKG_VERSION")),
+    let metrics = match config.metrics_port {
+        Some(port) => {
+            let metrics = Arc::new(Metrics::new());
+            let addr = serve_metrics(port, Arc::clone(&metrics))?;
+            log::info!("Serving Prometheus metrics at http://{addr}/metrics");
+            Some(metrics)
+        }
+        None => None,
+    };
+
+    let shared_config = SharedConfig {
         wallet_address: config.wallet_address,
         station_id: config.station_id,
-        reporter: Rc::new(StationReporter::new(
-            state_file,
-            Duration::from_millis(200),
-            module_name.into(),
-        )),
         lassie_daemon: Arc::clone(&lassie_daemon),
-        module_root: Some(module_root),
-        no_color: true,
-        is_tty: false,
-        rng_seed: None,
+        job_counters: Arc::new(Mutex::new(job_counters)),
+        restart_policy: RestartPolicy::new(config.max_restarts),
+        state_root,
+        kv_quota: config.kv_quota,
+        max_heap_size: config.max_heap_size,
+        watchdog_timeout: match config.watchdog_timeout {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        },
+        peer_key_secret,
+        metrics,
+        net_permissions: NetPermissions {
+            allow: config.allow_net,
+            deny: config.deny_net,
+            deny_private: !config.allow_private_net,
+            requested: vec![],
+        },
     };
 
-    // TODO: handle module exit and restart it
-    // https://github.com/filecoin-station/zinnia/issues/146
-    log::info!("Starting module {main_module}");
-    #[allow(clippy::let_unit_value)]
-    let module_output = run_js_module(&main_module, &runtime_config).await?;
+    // Each module runs in its own thread, the threads report back when the module stops
+    let (exit_sender, mut exit_receiver) = mpsc::unbounded_channel::<ModuleExit>();
+    for module in modules {
+        spawn_module(module, shared_config.clone(), exit_sender.clone())?;
+    }
+    drop(exit_sender);
+
+    // Modules are restarted by their threads, we get notified only after a thread gave up.
+    // The errors were already logged by the module supervisor.
+    let mut failed_modules = Vec::new();
+    let mut exit_code = 0;
+    while let Some(ModuleExit { name, result }) = exit_receiver.recv().await {
+        match result {
+            Ok(0) => {}
+            // Exit with the code of the first module that asked for a non-zero exit code
+            Ok(code) => {
+                if exit_code == 0 {
+                    exit_code = code;
+                }
+            }
+            Err(_) => failed_modules.push(name),
+        }
+    }
+
+    if !failed_modules.is_empty() {
+        return Err(anyhow!(
+            "The following modules failed: {}",
+            failed_modules.join(", ")
+        ));
+    }
 
     Ok(RunOutput {
-        module_output,
         lassie_daemon,
+        exit_code,
     })
 }
 
 #[allow(dead_code)]
 struct RunOutput {
-    module_output: (),
     // for testing
     lassie_daemon: Arc<lassie::Daemon>,
-}
-
-fn setup_logger() {
-    let mut builder = env_logger::Builder::new();
-    builder.filter_level(log::LevelFilter::Info);
-    builder.parse_default_env();
-    builder.init();
+    /// The exit code requested by the modules via `Zinnia.exitCode` or `Zinnia.exit()`.
+    exit_code: i32,
 }
 
 fn exit_with_error(error: Error) {
@@ -162,6 +232,10 @@ mod tests {
     use super::*;
     use assert_fs::prelude::*;
     use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use zinnia_runtime::DEFAULT_KV_QUOTA;
+
+    use crate::args::Secret;
+    use crate::logger::LogFormat;
 
     #[tokio::test]
     async fn lassie_auth_is_configured() {
@@ -180,6 +254,16 @@ mod tests {
             state_root: temp.join("state").to_string_lossy().into(),
             wallet_address: "f1test".to_string(),
             station_id: "a".repeat(88),
+            max_restarts: 0,
+            kv_quota: DEFAULT_KV_QUOTA,
+            max_heap_size: args::DEFAULT_MAX_HEAP_SIZE,
+            watchdog_timeout: 0,
+            peer_key_secret: Secret("test secret".to_string()),
+            allow_net: vec![],
+            deny_net: vec![],
+            allow_private_net: false,
+            metrics_port: None,
+            log_format: LogFormat::Text,
             files: vec![mod_js.path().to_string_lossy().to_string()],
         };
         let RunOutput { lassie_daemon, .. } = run(args).await.expect("cannot run dummy.js");
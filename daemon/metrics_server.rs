@@ -0,0 +1,90 @@
+use std::convert::Infallible;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use zinnia_runtime::anyhow::{Context, Result};
+use zinnia_runtime::Metrics;
+
+const CONTENT_TYPE_PROMETHEUS: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Serve the metrics in the Prometheus format at `http://127.0.0.1:{port}/metrics`. The server
+/// listens on the loopback interface only, the metrics are not meant to be public.
+///
+/// Returns the address the server is listening on, which is useful when `port` is 0.
+pub fn serve_metrics(port: u16, metrics: Arc<Metrics>) -> Result<SocketAddr> {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let make_service = make_service_fn(move |_conn| {
+        let metrics = Arc::clone(&metrics);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let metrics = Arc::clone(&metrics);
+                async move { Ok::<_, Infallible>(handle_request(&req, &metrics)) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)
+        .with_context(|| format!("cannot start the metrics server on {addr}"))?
+        .serve(make_service);
+    let local_addr = server.local_addr();
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            log::error!("The metrics server failed: {err}");
+        }
+    });
+
+    Ok(local_addr)
+}
+
+fn handle_request(req: &Request<Body>, metrics: &Metrics) -> Response<Body> {
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header(CONTENT_TYPE, CONTENT_TYPE_PROMETHEUS)
+            .body(Body::from(metrics.encode())),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("Not Found")),
+    };
+    response.expect("the response should be valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    async fn http_get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_metrics() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.module("saturn").job_completed();
+        let addr = serve_metrics(0, Arc::clone(&metrics)).unwrap();
+        assert!(addr.ip().is_loopback(), "unexpected address {addr}");
+
+        let response = http_get(addr, "/metrics").await;
+        assert!(
+            response.starts_with("HTTP/1.1 200 OK"),
+            "unexpected response: {response}"
+        );
+        assert!(
+            response.contains("zinnia_jobs_completed_total{module=\"saturn\"} 1"),
+            "unexpected response: {response}"
+        );
+
+        let response = http_get(addr, "/other").await;
+        assert_eq!(response.lines().next(), Some("HTTP/1.1 404 Not Found"));
+    }
+}
@@ -0,0 +1,316 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use zinnia_runtime::anyhow::{anyhow, Context, Result};
+use zinnia_runtime::deno_core::error::JsError;
+use zinnia_runtime::deno_core::ModuleSpecifier;
+use zinnia_runtime::import_map::ImportMap;
+use zinnia_runtime::{
+    get_module_root, lassie, read_import_map, resolve_path, run_js_module, BootstrapOptions,
+    KvStoreConfig, Metrics, ModuleManifest, NetPermissions,
+};
+
+use crate::peer_identity::{load_or_create_keypair, replace_keypair};
+use crate::station_reporter::{
+    log_error_activity, log_info_activity, JobCounters, StationReporter,
+};
+use crate::supervisor::{RestartPolicy, RestartTracker};
+
+/// A Zinnia module to run inside zinniad.
+#[derive(Debug, Clone)]
+pub struct ModuleConfig {
+    /// Name identifying the module in activity events and job counters.
+    pub name: String,
+    pub version: Option<String>,
+    pub main_module: ModuleSpecifier,
+    pub module_root: PathBuf,
+    /// The import map configured in the manifest.
+    pub import_map: Option<ImportMap>,
+    /// The manifest, when the module was loaded from a directory with `zinnia.json`.
+    pub manifest: Option<ModuleManifest>,
+}
+
+impl ModuleConfig {
+    /// Resolve a module, paths are resolved relatively to `cwd`.
+    ///
+    /// The `path` can point either to a module directory containing the manifest file
+    /// `zinnia.json`, or to the main JS file of a module.
+    pub fn resolve(path: &str, cwd: &Path) -> Result<Self> {
+        let module_dir = cwd.join(path);
+        if module_dir.is_dir() {
+            return Self::from_manifest(&module_dir);
+        }
+
+        // Modules without a manifest are named after their main file
+        let name = path.trim_end_matches(".js").to_string();
+
+        let main_module = resolve_path(path, cwd)?;
+        let module_root = get_module_root(&main_module)?;
+
+        Ok(Self {
+            name,
+            version: None,
+            main_module,
+            module_root,
+            import_map: None,
+            manifest: None,
+        })
+    }
+
+    fn from_manifest(module_dir: &Path) -> Result<Self> {
+        let manifest = ModuleManifest::load(module_dir)?;
+
+        // Resolve any symlinks inside the path to prevent modules from escaping our sandbox
+        let module_root = module_dir.canonicalize().with_context(|| {
+            format!(
+                "Cannot canonicalize module directory {}",
+                module_dir.display()
+            )
+        })?;
+        let main_path = manifest.main_path(&module_root);
+        let main_module = ModuleSpecifier::from_file_path(&main_path).map_err(|_| {
+            anyhow!(
+                "Invalid entry point {} of module {}",
+                main_path.display(),
+                manifest.name
+            )
+        })?;
+
+        let import_map = match manifest.import_map_path(&module_root) {
+            None => None,
+            Some(path) => {
+                // The import map must be inside the module directory like all module files
+                let canonical_path = path.canonicalize().with_context(|| {
+                    format!("Cannot canonicalize import map path {}", path.display())
+                })?;
+                if !canonical_path.starts_with(&module_root) {
+                    return Err(anyhow!(
+                        "The import map {} of module {} is outside of the module directory.",
+                        path.display(),
+                        manifest.name
+                    ));
+                }
+                Some(read_import_map(&canonical_path)?)
+            }
+        };
+
+        Ok(Self {
+            name: manifest.name.clone(),
+            version: manifest.version.clone(),
+            main_module,
+            module_root,
+            import_map,
+            manifest: Some(manifest),
+        })
+    }
+
+    /// The name of the directory inside `state_root` where the module keeps its data. Modules
+    /// without a manifest are named after their file path, we must not allow names like `../foo`.
+    pub fn state_dir_name(&self) -> String {
+        self.name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect()
+    }
+
+    /// The name and version of the module, e.g. `saturn-l2/1.2.3`.
+    pub fn name_and_version(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}/{version}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Configuration and resources shared by all modules running inside zinniad.
+#[derive(Clone)]
+pub struct SharedConfig {
+    pub wallet_address: String,
+    pub station_id: String,
+    pub lassie_daemon: Arc<lassie::Daemon>,
+    pub job_counters: Arc<Mutex<JobCounters>>,
+    pub restart_policy: RestartPolicy,
+    /// Modules keep their `Zinnia.kv` stores in `{state_root}/{module}`.
+    pub state_root: PathBuf,
+    /// The maximum size of the `Zinnia.kv` store of each module in bytes.
+    pub kv_quota: u64,
+    /// The maximum size of the JavaScript heap of each module in bytes.
+    pub max_heap_size: usize,
+    /// Terminate modules blocking the event loop for this long, the watchdog is disabled when
+    /// not set.
+    pub watchdog_timeout: Option<Duration>,
+    /// Secret used to encrypt the libp2p identities of modules, see `peer_identity`.
+    pub peer_key_secret: String,
+    /// Metrics exported via the Prometheus endpoint, not collected when the endpoint is disabled.
+    pub metrics: Option<Arc<Metrics>>,
+    /// Network policy configured by the operator, modules can restrict it further via
+    /// `permissions.net` in their manifest.
+    pub net_permissions: NetPermissions,
+}
+
+/// The outcome of a module that stopped running and won't be restarted again.
+#[derive(Debug)]
+pub struct ModuleExit {
+    pub name: String,
+    /// The exit code set by the module via `Zinnia.exitCode` or `Zinnia.exit()`, or the error
+    /// that crashed the module.
+    pub result: Result<i32>,
+}
+
+/// Run the module in a new thread with its own JS runtime (V8 isolate) and event loop.
+///
+/// The module is restarted whenever it exits or crashes, as configured by the restart policy.
+/// When we give up on restarting the module, the outcome of the last run is sent to `on_exit`.
+pub fn spawn_module(
+    module: ModuleConfig,
+    shared: SharedConfig,
+    on_exit: UnboundedSender<ModuleExit>,
+) -> Result<()> {
+    let thread_name = format!("module:{}", module.name);
+    std::thread::Builder::new()
+        .name(thread_name.clone())
+        .spawn(move || {
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .context("cannot create the event loop")?
+                    .block_on(supervise_module(&module, &shared))
+            }))
+            .unwrap_or_else(|_| Err(anyhow!("The module thread panicked.")));
+
+            // The receiver is gone only when zinniad is shutting down, there is nobody to notify
+            let _ = on_exit.send(ModuleExit {
+                name: module.name,
+                result,
+            });
+        })
+        .with_context(|| format!("cannot spawn thread {thread_name}"))?;
+
+    Ok(())
+}
+
+async fn supervise_module(module: &ModuleConfig, shared: &SharedConfig) -> Result<i32> {
+    let mut restarts = RestartTracker::new(shared.restart_policy);
+
+    loop {
+        let result = run_module(module, shared).await;
+        match &result {
+            Ok(0) => {
+                log::info!("Module {} exited", module.name);
+                log_info_activity(&format!("Module {} exited.", module.name));
+            }
+            Ok(code) => {
+                log::error!("Module {} exited with code {code}", module.name);
+                log_error_activity(&format!("Module {} exited with code {code}.", module.name));
+            }
+            Err(err) => {
+                log::error!("Module {} crashed: {err:?}", module.name);
+                // Activity messages are single-line, the full error is in the log above
+                let reason = match err.downcast_ref::<JsError>() {
+                    Some(js_error) => js_error.exception_message.clone(),
+                    None => err.to_string(),
+                };
+                let reason = reason.lines().next().unwrap_or_default();
+                log_error_activity(&format!("Module {} crashed: {reason}", module.name));
+            }
+        }
+
+        let Some(delay) = restarts.next_restart(Instant::now()) else {
+            let policy = restarts.policy();
+            if policy.max_restarts > 0 {
+                log_error_activity(&format!(
+                    "Module {} reached the limit of {} restarts in {:?}. Giving up.",
+                    module.name,
+                    restarts.recent_restarts(),
+                    policy.window,
+                ));
+            }
+            return result;
+        };
+
+        log_info_activity(&format!(
+            "Restarting module {} in {delay:?} (restart {} of {}).",
+            module.name,
+            restarts.recent_restarts(),
+            restarts.policy().max_restarts,
+        ));
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Run the module once, return its exit code.
+async fn run_module(module: &ModuleConfig, shared: &SharedConfig) -> Result<i32> {
+    let state_dir = shared.state_root.join(module.state_dir_name());
+    let key_file = state_dir.join("peer-key.json");
+    let peer_keypair = match load_or_create_keypair(&key_file, &shared.peer_key_secret) {
+        Ok(keypair) => keypair,
+        Err(err) => {
+            // Restarting the module would not help, e.g. when the secret was changed
+            log_error_activity(&format!(
+                "Module {} cannot load its peer identity, creating a new one: {err:#}",
+                module.name
+            ));
+            replace_keypair(&key_file, &shared.peer_key_secret)?
+        }
+    };
+    let requested_net = match &module.manifest {
+        Some(manifest) => manifest.permissions.net_rules()?,
+        None => vec![],
+    };
+
+    let runtime_config = BootstrapOptions {
+        zinnia_version: env!("CARGO_PKG_VERSION"),
+        agent_version: format!(
+            "zinniad/{} {}",
+            env!("CARGO_PKG_VERSION"),
+            module.name_and_version()
+        ),
+        wallet_address: shared.wallet_address.clone(),
+        station_id: shared.station_id.clone(),
+        reporter: Rc::new(StationReporter::new(
+            Arc::clone(&shared.job_counters),
+            module.name.clone(),
+        )),
+        lassie_daemon: Arc::clone(&shared.lassie_daemon),
+        module_root: Some(module.module_root.clone()),
+        import_map: module.import_map.clone(),
+        remote_modules: None,
+        loaded_files: None,
+        kv_store: Some(KvStoreConfig {
+            dir: state_dir,
+            quota: shared.kv_quota,
+        }),
+        max_heap_size: Some(shared.max_heap_size),
+        watchdog_timeout: shared.watchdog_timeout,
+        metrics: shared
+            .metrics
+            .as_ref()
+            .map(|metrics| metrics.module(&module.name)),
+        net_permissions: NetPermissions {
+            requested: requested_net,
+            ..shared.net_permissions.clone()
+        },
+        peer_keypair: Some(peer_keypair),
+        module_name: Some(module.name.clone()),
+        module_version: module.version.clone(),
+        test_filter: None,
+        inspect: None,
+        inspect_brk: false,
+        no_color: true,
+        is_tty: false,
+        rng_seed: None,
+    };
+
+    log::info!("Starting module {}", module.main_module);
+    let output = run_js_module(&module.main_module, &runtime_config).await?;
+    Ok(output.process_exit_code())
+}
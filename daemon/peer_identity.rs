@@ -0,0 +1,188 @@
+use std::io::Write;
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use atomicwrites::{AtomicFile, OverwriteBehavior};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zinnia_runtime::anyhow::{self, anyhow, Context, Result};
+use zinnia_runtime::zinnia_libp2p::Keypair;
+
+const KEY_FILE_VERSION: u32 = 1;
+
+/// The content of the key file. The keypair is encrypted using AES-256-GCM with a key derived
+/// from the secret configured via `PEER_KEY_SECRET`.
+#[derive(Serialize, Deserialize, Debug)]
+struct KeyFile {
+    version: u32,
+    /// Base64-encoded nonce.
+    nonce: String,
+    /// Base64-encoded keypair in the protobuf encoding, encrypted.
+    ciphertext: String,
+}
+
+/// Load the libp2p identity from the key file, or create a new identity and store it in the key
+/// file when the file does not exist yet. This allows the module to keep its peer ID across
+/// restarts.
+pub fn load_or_create_keypair(key_file: &Path, secret: &str) -> Result<Keypair> {
+    match std::fs::read_to_string(key_file) {
+        Ok(data) => decrypt_keypair(&data, secret)
+            .with_context(|| format!("Cannot load peer identity from {}", key_file.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let keypair = Keypair::generate_ed25519();
+            store_keypair(key_file, &keypair, secret)?;
+            log::debug!(
+                "Created new peer identity {} in {}",
+                keypair.public().to_peer_id(),
+                key_file.display()
+            );
+            Ok(keypair)
+        }
+        Err(err) => Err(anyhow::Error::new(err).context(format!(
+            "Cannot read peer identity from {}",
+            key_file.display()
+        ))),
+    }
+}
+
+/// Replace the key file we cannot load, e.g. after the secret was changed, with a new identity.
+/// The old file is kept as `{key_file}.invalid`, so that the operator can restore it.
+pub fn replace_keypair(key_file: &Path, secret: &str) -> Result<Keypair> {
+    let backup_file = key_file.with_extension("json.invalid");
+    std::fs::rename(key_file, &backup_file).with_context(|| {
+        format!(
+            "Cannot move the peer identity {} to {}",
+            key_file.display(),
+            backup_file.display()
+        )
+    })?;
+    load_or_create_keypair(key_file, secret)
+}
+
+fn store_keypair(key_file: &Path, keypair: &Keypair, secret: &str) -> Result<()> {
+    let data = encrypt_keypair(keypair, secret)?;
+
+    if let Some(parent) = key_file.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Cannot create directory {}", parent.display()))?;
+    }
+    AtomicFile::new(key_file, OverwriteBehavior::DisallowOverwrite)
+        .write(|f| f.write_all(data.as_bytes()))
+        .with_context(|| format!("Cannot write peer identity to {}", key_file.display()))?;
+    Ok(())
+}
+
+fn encrypt_keypair(keypair: &Keypair, secret: &str) -> Result<String> {
+    let plaintext = keypair
+        .to_protobuf_encoding()
+        .context("Cannot encode the peer identity")?;
+    let cipher = Aes256Gcm::new(&derive_key(secret));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|_| anyhow!("Cannot encrypt the peer identity"))?;
+
+    let key_file = KeyFile {
+        version: KEY_FILE_VERSION,
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    Ok(serde_json::to_string_pretty(&key_file)?)
+}
+
+fn decrypt_keypair(data: &str, secret: &str) -> Result<Keypair> {
+    let key_file = serde_json::from_str::<KeyFile>(data).context("Invalid key file")?;
+    if key_file.version != KEY_FILE_VERSION {
+        return Err(anyhow!("Unsupported key file version {}", key_file.version));
+    }
+    let nonce = BASE64.decode(key_file.nonce).context("Invalid nonce")?;
+    if nonce.len() != 12 {
+        return Err(anyhow!("Invalid nonce length {}", nonce.len()));
+    }
+    let ciphertext = BASE64
+        .decode(key_file.ciphertext)
+        .context("Invalid ciphertext")?;
+
+    let cipher = Aes256Gcm::new(&derive_key(secret));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| anyhow!("Cannot decrypt the peer identity, was the secret changed?"))?;
+    Keypair::from_protobuf_encoding(&plaintext).context("Invalid peer identity")
+}
+
+/// The secret is expected to be a random token provided by Station, we don't need a slow
+/// password-hashing function here.
+fn derive_key(secret: &str) -> Key<Aes256Gcm> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"zinniad peer identity\0");
+    hasher.update(secret.as_bytes());
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    #[test]
+    fn creates_and_loads_keypair() -> Result<()> {
+        let state_dir = tempdir()?;
+        let key_file = state_dir.path().join("module").join("peer-key.json");
+
+        let created = load_or_create_keypair(&key_file, "secret")?;
+        let loaded = load_or_create_keypair(&key_file, "secret")?;
+        assert_eq!(loaded.public().to_peer_id(), created.public().to_peer_id());
+        Ok(())
+    }
+
+    #[test]
+    fn stores_keypair_encrypted() -> Result<()> {
+        let state_dir = tempdir()?;
+        let key_file = state_dir.path().join("peer-key.json");
+        let keypair = load_or_create_keypair(&key_file, "secret")?;
+
+        let data = std::fs::read_to_string(&key_file)?;
+        let plaintext = BASE64.encode(keypair.to_protobuf_encoding()?);
+        assert!(
+            !data.contains(&plaintext),
+            "the key file must not contain the plaintext keypair"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_wrong_secret() -> Result<()> {
+        let state_dir = tempdir()?;
+        let key_file = state_dir.path().join("peer-key.json");
+        load_or_create_keypair(&key_file, "secret")?;
+
+        let err = load_or_create_keypair(&key_file, "other secret").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("Cannot decrypt the peer identity"),
+            "unexpected error: {err:#}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn replaces_unreadable_keypair() -> Result<()> {
+        let state_dir = tempdir()?;
+        let key_file = state_dir.path().join("peer-key.json");
+        let old = load_or_create_keypair(&key_file, "secret")?;
+
+        let new = replace_keypair(&key_file, "other secret")?;
+        assert_ne!(new.public().to_peer_id(), old.public().to_peer_id());
+        let loaded = load_or_create_keypair(&key_file, "other secret")?;
+        assert_eq!(loaded.public().to_peer_id(), new.public().to_peer_id());
+
+        // The old key file can be restored with the old secret
+        let backup =
+            load_or_create_keypair(&state_dir.path().join("peer-key.json.invalid"), "secret")?;
+        assert_eq!(backup.public().to_peer_id(), old.public().to_peer_id());
+        Ok(())
+    }
+}
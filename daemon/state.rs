@@ -1,5 +1,6 @@
 use atomicwrites::{AtomicFile, OverwriteBehavior};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::path::Path;
 use zinnia_runtime::anyhow::{self, Context, Result};
@@ -7,6 +8,10 @@ use zinnia_runtime::anyhow::{self, Context, Result};
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct State {
     pub total_jobs_completed: u64,
+    /// Jobs completed by each module, including modules that are no longer running. State files
+    /// created by older versions don't have this field.
+    #[serde(default)]
+    pub module_jobs_completed: BTreeMap<String, u64>,
 }
 
 impl State {
@@ -71,6 +76,10 @@ mod tests {
         let state_file = state_dir.path().join("state.json");
         let loaded = State::load(&state_file)?;
         assert_eq!(loaded.total_jobs_completed, 0, "total_jobs_completed");
+        assert!(
+            loaded.module_jobs_completed.is_empty(),
+            "module_jobs_completed"
+        );
         Ok(())
     }
 
@@ -80,10 +89,36 @@ mod tests {
         let state_file = state_dir.path().join("subdir").join("state.json");
         let state = State {
             total_jobs_completed: 1,
+            ..State::default()
         };
         state.store(&state_file)?;
         let loaded = State::load(&state_file)?;
         assert_eq!(loaded.total_jobs_completed, 1);
         Ok(())
     }
+
+    #[test]
+    fn persists_module_counters() -> Result<()> {
+        let state_dir = tempdir()?;
+        let state_file = state_dir.path().join("state.json");
+        let state = State {
+            total_jobs_completed: 3,
+            module_jobs_completed: BTreeMap::from([("saturn".into(), 2), ("checker".into(), 1)]),
+        };
+        state.store(&state_file)?;
+        let loaded = State::load(&state_file)?;
+        assert_eq!(loaded.module_jobs_completed, state.module_jobs_completed);
+        Ok(())
+    }
+
+    #[test]
+    fn loads_state_without_module_counters() -> Result<()> {
+        let state_dir = tempdir()?;
+        let state_file = state_dir.path().join("state.json");
+        std::fs::write(&state_file, r#"{"total_jobs_completed":42}"#)?;
+        let loaded = State::load(&state_file)?;
+        assert_eq!(loaded.total_jobs_completed, 42);
+        assert!(loaded.module_jobs_completed.is_empty());
+        Ok(())
+    }
 }
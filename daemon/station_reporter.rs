@@ -1,82 +1,155 @@
-use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::io::{stderr, stdout, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use serde_json::{json, Map};
-use zinnia_runtime::{JobCompletionTracker, LogLevel, Reporter};
+use serde_json::json;
+use zinnia_runtime::anyhow::Result;
+use zinnia_runtime::{Activity, JobCompletionTracker, LogLevel, Reporter};
 
 use crate::state::State;
 
-/// StationReporter reports activities to stdout as ND-JSON stream and all Console logs to stderr
-pub struct StationReporter {
-    tracker: RefCell<JobCompletionTracker>,
-    module_name: String,
-    log_target: String,
+/// Job counters shared by the reporters of all modules running inside this zinniad process.
+pub struct JobCounters {
+    tracker: JobCompletionTracker,
+    /// Counters of the modules running in this process, reported in `jobs-completed` events.
+    modules: BTreeMap<String, u64>,
+    /// Counters of modules that are not running anymore. We keep them in the state file so that
+    /// the counters don't start from zero when such module is added back.
+    stopped_modules: BTreeMap<String, u64>,
     state_file: PathBuf,
 }
 
-impl StationReporter {
-    /// Create a new instance.
+impl JobCounters {
+    /// Load the job counters from the state file and start tracking jobs completed by the given
+    /// modules.
+    ///
+    /// The total count includes jobs completed by modules that are no longer running.
     ///
     /// `job_report_delay` specifies how often the information about new jobs is printed.
-    pub fn new(state_file: PathBuf, job_report_delay: Duration, module_name: String) -> Self {
-        let log_target = format!("module:{module_name}");
-        let initial_job_count = State::load(&state_file)
-            // NOTE(bajtos) We are intentionally calling unwrap() to crash the process in case
-            // it's not possible to read the state file or parse the content.
-            .unwrap()
-            .total_jobs_completed;
-
-        let reporter = Self {
-            tracker: RefCell::new(JobCompletionTracker::new(
-                initial_job_count,
-                job_report_delay,
-            )),
-            module_name,
-            log_target,
+    pub fn load<'a>(
+        state_file: PathBuf,
+        job_report_delay: Duration,
+        module_names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Self> {
+        let State {
+            total_jobs_completed: initial_job_count,
+            module_jobs_completed: mut stopped_modules,
+        } = State::load(&state_file)?;
+
+        let modules = module_names
+            .into_iter()
+            .map(|name| {
+                let count = stopped_modules.remove(name).unwrap_or_default();
+                (name.to_string(), count)
+            })
+            .collect();
+
+        let counters = Self {
+            tracker: JobCompletionTracker::new(initial_job_count, job_report_delay),
+            modules,
+            stopped_modules,
             state_file,
         };
 
         // Report the initial job count to prevent Station Desktop from showing incorrect job count
         // until a Zinnia module completes the first job
-        reporter.print_jobs_completed(initial_job_count);
+        counters.print_jobs_completed(initial_job_count);
 
-        reporter
+        Ok(counters)
     }
 
-    fn print_jobs_completed(&self, total: u64) {
-        // TODO: print data from all modules
-        // https://github.com/filecoin-station/zinnia/issues/144
-        // modules: {"saturn": 100, "retrieval-checker": 23}}
-        let mut modules = Map::new();
-        modules.insert(self.module_name.clone(), json!(total));
-
-        let event = json!({
-            "type": "jobs-completed",
-            "total": total,
-            "modules": modules,
-        });
+    #[cfg(test)]
+    pub fn total(&self) -> u64 {
+        self.tracker.counter()
+    }
 
-        print_event(&event);
+    #[cfg(test)]
+    pub fn module(&self, module_name: &str) -> u64 {
+        self.modules.get(module_name).copied().unwrap_or_default()
     }
 
-    fn update_jobs_completed(&self, total: u64) {
-        // IMPORTANT: We must update the persisted state first and report the job counter to Station
-        // only after the persisted state was successfully updated. Otherwise, when the computer is
-        // out of disk space, Station will remember the higher job count we reported before
-        // crashing due to the `unwrap()` call below, but we will report a lower value after Station
-        // restarts us and we load the old counter from the state file.
-        let state = State {
-            total_jobs_completed: total,
-        };
-        state
-            .store(&self.state_file)
-            // NOTE(bajtos) We are intentionally calling unwrap() to crash the process in case
-            // we cannot store the state into the file.
-            .unwrap();
+    fn job_completed(&mut self, module_name: &str) {
+        *self.modules.entry(module_name.to_string()).or_default() += 1;
+
+        let Self {
+            tracker,
+            modules,
+            stopped_modules,
+            state_file,
+        } = self;
+        tracker.job_completed(|n| update_jobs_completed(state_file, modules, stopped_modules, n));
+    }
+
+    fn flush(&mut self) {
+        let Self {
+            tracker, modules, ..
+        } = self;
+        tracker.flush(|n| print_jobs_completed(modules, n));
+    }
 
-        self.print_jobs_completed(total)
+    fn print_jobs_completed(&self, total: u64) {
+        print_jobs_completed(&self.modules, total)
+    }
+}
+
+fn print_jobs_completed(modules: &BTreeMap<String, u64>, total: u64) {
+    let event = json!({
+        "type": "jobs-completed",
+        "total": total,
+        "modules": modules,
+    });
+
+    print_event(&event);
+}
+
+fn update_jobs_completed(
+    state_file: &Path,
+    modules: &BTreeMap<String, u64>,
+    stopped_modules: &BTreeMap<String, u64>,
+    total: u64,
+) {
+    // IMPORTANT: We must update the persisted state first and report the job counter to Station
+    // only after the persisted state was successfully updated. Otherwise, when the computer is
+    // out of disk space, Station will remember the higher job count we reported before
+    // crashing due to the `unwrap()` call below, but we will report a lower value after Station
+    // restarts us and we load the old counter from the state file.
+    let state = State {
+        total_jobs_completed: total,
+        module_jobs_completed: stopped_modules
+            .iter()
+            .chain(modules)
+            .map(|(name, count)| (name.clone(), *count))
+            .collect(),
+    };
+    state
+        .store(state_file)
+        // NOTE(bajtos) We are intentionally calling unwrap() to crash the process in case
+        // we cannot store the state into the file.
+        .unwrap();
+
+    print_jobs_completed(modules, total)
+}
+
+/// StationReporter reports activities to stdout as ND-JSON stream and all Console logs to stderr
+pub struct StationReporter {
+    counters: Arc<Mutex<JobCounters>>,
+    module_name: String,
+    log_target: String,
+}
+
+impl StationReporter {
+    /// Create a new instance reporting activities of the module `module_name`.
+    ///
+    /// The job counters are shared with reporters of other modules running in this process.
+    pub fn new(counters: Arc<Mutex<JobCounters>>, module_name: String) -> Self {
+        let log_target = format!("module:{module_name}");
+        Self {
+            counters,
+            module_name,
+            log_target,
+        }
     }
 }
 
@@ -97,7 +170,6 @@ pub fn log_started_activity() {
     print_event(&event);
 }
 
-#[allow(unused)]
 pub fn log_info_activity(msg: &str) {
     let event = json!({
         "type": "activity:info",
@@ -107,7 +179,6 @@ pub fn log_info_activity(msg: &str) {
     print_event(&event);
 }
 
-#[allow(unused)]
 pub fn log_error_activity(msg: &str) {
     let event = json!({
         "type": "activity:error",
@@ -117,11 +188,28 @@ pub fn log_error_activity(msg: &str) {
     print_event(&event);
 }
 
+/// Build the `activity:{level}` event. The `code` and `details` fields are included only when
+/// the module provided them.
+fn format_activity_event(module_name: &str, activity: &Activity) -> serde_json::Value {
+    let mut event = json!({
+        "type": format!("activity:{}", activity.level),
+        "module": module_name,
+        "message": activity.message,
+    });
+    if let Some(code) = &activity.code {
+        event["code"] = json!(code);
+    }
+    if let Some(details) = &activity.details {
+        event["details"] = details.clone();
+    }
+    event
+}
+
 impl Drop for StationReporter {
     fn drop(&mut self) {
-        self.tracker
-            .borrow_mut()
-            .flush(|n| self.print_jobs_completed(n));
+        // A module thread that panicked may have poisoned the lock, we still want to flush
+        let mut counters = self.counters.lock().unwrap_or_else(|err| err.into_inner());
+        counters.flush();
     }
 }
 
@@ -135,28 +223,16 @@ impl Reporter for StationReporter {
         let _ = stderr().flush();
     }
 
-    fn info_activity(&self, msg: &str) {
-        let event = json!({
-            "type": "activity:info",
-            "module": self.module_name,
-            "message": msg,
-        });
-        print_event(&event);
-    }
-
-    fn error_activity(&self, msg: &str) {
-        let event = json!({
-            "type": "activity:error",
-            "module": self.module_name,
-            "message": msg,
-        });
-        print_event(&event);
+    fn activity(&self, activity: &Activity) {
+        print_event(&format_activity_event(&self.module_name, activity));
     }
 
     fn job_completed(&self) {
-        self.tracker
-            .borrow_mut()
-            .job_completed(|n| self.update_jobs_completed(n));
+        // Don't let a module thread that panicked while holding the lock take down other modules
+        self.counters
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .job_completed(&self.module_name);
     }
 }
 
@@ -166,30 +242,122 @@ mod tests {
     use pretty_assertions::assert_eq;
     use tempfile::tempdir;
     use zinnia_runtime::anyhow::Result;
+    use zinnia_runtime::ActivityLevel;
 
     const NO_DELAY: Duration = Duration::from_millis(0);
 
+    fn load_counters(state_file: &Path, modules: &[&str]) -> Result<Arc<Mutex<JobCounters>>> {
+        let counters =
+            JobCounters::load(state_file.to_path_buf(), NO_DELAY, modules.iter().copied())?;
+        Ok(Arc::new(Mutex::new(counters)))
+    }
+
     #[test]
     fn persists_job_counter() -> Result<()> {
         let state_dir = tempdir()?;
         let state_file = state_dir.path().join("state.json");
-        let reporter = StationReporter::new(state_file.clone(), NO_DELAY, "test".into());
-        assert_eq!(reporter.tracker.borrow().counter(), 0, "initial count");
+        let counters = load_counters(&state_file, &["test"])?;
+        let reporter = StationReporter::new(counters.clone(), "test".into());
+        assert_eq!(counters.lock().unwrap().total(), 0, "initial count");
 
         reporter.job_completed();
         assert_eq!(
-            reporter.tracker.borrow().counter(),
+            counters.lock().unwrap().total(),
             1,
             "count after a job was completed"
         );
 
-        let reporter = StationReporter::new(state_file, NO_DELAY, "test".into());
+        let counters = load_counters(&state_file, &["test"])?;
         assert_eq!(
-            reporter.tracker.borrow().counter(),
+            counters.lock().unwrap().total(),
             1,
-            "count after creating a new reporter"
+            "count after loading the counters again"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn counts_jobs_per_module() -> Result<()> {
+        let state_dir = tempdir()?;
+        let state_file = state_dir.path().join("state.json");
+        let counters = load_counters(&state_file, &["saturn", "retrieval-checker"])?;
+        let saturn = StationReporter::new(counters.clone(), "saturn".into());
+        let checker = StationReporter::new(counters.clone(), "retrieval-checker".into());
+
+        saturn.job_completed();
+        checker.job_completed();
+        saturn.job_completed();
+
+        let counters = counters.lock().unwrap();
+        assert_eq!(counters.total(), 3, "total");
+        assert_eq!(counters.module("saturn"), 2, "saturn");
+        assert_eq!(counters.module("retrieval-checker"), 1, "retrieval-checker");
+        Ok(())
+    }
+
+    #[test]
+    fn formats_activity_events() {
+        let activity = Activity::new(ActivityLevel::Info, "Saturn Node is online");
+        assert_eq!(
+            format_activity_event("saturn", &activity),
+            json!({
+                "type": "activity:info",
+                "module": "saturn",
+                "message": "Saturn Node is online",
+            })
         );
 
+        let activity = Activity {
+            code: Some("SLOW_RETRIEVAL".into()),
+            details: Some(json!({ "cid": "bafy", "duration": 1500 })),
+            ..Activity::new(ActivityLevel::Warn, "Retrieval is slow")
+        };
+        assert_eq!(
+            format_activity_event("saturn", &activity),
+            json!({
+                "type": "activity:warn",
+                "module": "saturn",
+                "message": "Retrieval is slow",
+                "code": "SLOW_RETRIEVAL",
+                "details": { "cid": "bafy", "duration": 1500 },
+            })
+        );
+    }
+
+    #[test]
+    fn persists_module_counters() -> Result<()> {
+        let state_dir = tempdir()?;
+        let state_file = state_dir.path().join("state.json");
+        let counters = load_counters(&state_file, &["saturn", "retrieval-checker"])?;
+        let saturn = StationReporter::new(counters.clone(), "saturn".into());
+        let checker = StationReporter::new(counters.clone(), "retrieval-checker".into());
+        saturn.job_completed();
+        checker.job_completed();
+        checker.job_completed();
+
+        // Restart with the retrieval checker removed and a new module added
+        let counters = load_counters(&state_file, &["saturn", "ping"])?;
+        {
+            let counters = counters.lock().unwrap();
+            assert_eq!(counters.total(), 3, "total after removing a module");
+            assert_eq!(counters.module("saturn"), 1, "saturn");
+            assert_eq!(counters.module("ping"), 0, "ping");
+            assert_eq!(
+                counters.modules.keys().collect::<Vec<_>>(),
+                ["ping", "saturn"],
+                "running modules"
+            );
+        }
+        StationReporter::new(counters.clone(), "ping".into()).job_completed();
+
+        // Restart with the retrieval checker added back
+        let counters = load_counters(&state_file, &["saturn", "ping", "retrieval-checker"])?;
+        let counters = counters.lock().unwrap();
+        assert_eq!(counters.total(), 4, "total");
+        assert_eq!(counters.module("saturn"), 1, "saturn");
+        assert_eq!(counters.module("ping"), 1, "ping");
+        assert_eq!(counters.module("retrieval-checker"), 2, "retrieval-checker");
         Ok(())
     }
 }
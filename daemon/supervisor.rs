@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Rules for restarting modules that exited or crashed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RestartPolicy {
+    /// How many restarts are allowed within `window` before we give up on the module.
+    pub max_restarts: u32,
+    pub window: Duration,
+    /// The delay before the first restart, it doubles with every further restart in `window`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RestartPolicy {
+    pub fn new(max_restarts: u32) -> Self {
+        Self {
+            max_restarts,
+            window: Duration::from_secs(10 * 60),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Keeps track of recent restarts of a single module.
+#[derive(Debug)]
+pub struct RestartTracker {
+    policy: RestartPolicy,
+    restarts: VecDeque<Instant>,
+}
+
+impl RestartTracker {
+    pub fn new(policy: RestartPolicy) -> Self {
+        Self {
+            policy,
+            restarts: VecDeque::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &RestartPolicy {
+        &self.policy
+    }
+
+    /// Number of restarts recorded within the current window.
+    pub fn recent_restarts(&self) -> u32 {
+        self.restarts.len() as u32
+    }
+
+    /// Record that the module stopped at `now`. Returns the delay to wait before restarting the
+    /// module, or `None` when the module was restarted too many times and we should give up.
+    pub fn next_restart(&mut self, now: Instant) -> Option<Duration> {
+        while let Some(oldest) = self.restarts.front() {
+            if now.duration_since(*oldest) < self.policy.window {
+                break;
+            }
+            self.restarts.pop_front();
+        }
+
+        let attempt = self.recent_restarts();
+        if attempt >= self.policy.max_restarts {
+            return None;
+        }
+        self.restarts.push_back(now);
+
+        let backoff = self
+            .policy
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt));
+        Some(backoff.min(self.policy.max_backoff))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn doubles_backoff_up_to_the_limit() {
+        let mut tracker = RestartTracker::new(RestartPolicy {
+            max_restarts: 10,
+            window: secs(3600),
+            initial_backoff: secs(1),
+            max_backoff: secs(5),
+        });
+        let now = Instant::now();
+        let delays: Vec<_> = (0..5).map(|_| tracker.next_restart(now)).collect();
+        assert_eq!(
+            delays,
+            [secs(1), secs(2), secs(4), secs(5), secs(5)].map(Some)
+        );
+    }
+
+    #[test]
+    fn gives_up_after_too_many_restarts() {
+        let mut tracker = RestartTracker::new(RestartPolicy {
+            max_restarts: 2,
+            ..RestartPolicy::new(0)
+        });
+        let now = Instant::now();
+        assert!(tracker.next_restart(now).is_some(), "first restart");
+        assert!(tracker.next_restart(now).is_some(), "second restart");
+        assert_eq!(tracker.next_restart(now), None, "third restart");
+    }
+
+    #[test]
+    fn forgets_restarts_outside_of_the_window() {
+        let policy = RestartPolicy {
+            max_restarts: 2,
+            ..RestartPolicy::new(0)
+        };
+        let mut tracker = RestartTracker::new(policy);
+        let start = Instant::now();
+        tracker.next_restart(start);
+        tracker.next_restart(start);
+
+        let later = start + policy.window;
+        assert_eq!(
+            tracker.next_restart(later),
+            Some(policy.initial_backoff),
+            "restart after the window elapsed"
+        );
+        assert_eq!(tracker.recent_restarts(), 1);
+    }
+
+    #[test]
+    fn never_restarts_when_disabled() {
+        let mut tracker = RestartTracker::new(RestartPolicy::new(0));
+        assert_eq!(tracker.next_restart(Instant::now()), None);
+    }
+}
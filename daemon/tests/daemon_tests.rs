@@ -1,10 +1,12 @@
 use std::fs::read_dir;
 use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
 use std::time::Duration;
 use tempfile::tempdir;
 
+use serde_json::Value;
+
 #[test]
 pub fn it_removes_lassie_temp_on_start() {
     let _ = env_logger::builder().is_test(true).try_init();
@@ -27,6 +29,7 @@ pub fn it_removes_lassie_temp_on_start() {
     cmd.env("NO_COLOR", "1")
         .env("FIL_WALLET_ADDRESS", "f1test")
         .env("STATION_ID", "a".repeat(88))
+        .env("PEER_KEY_SECRET", "test secret")
         .env("CACHE_ROOT", cache_root.display().to_string())
         .env("STATE_ROOT", state_root.display().to_string())
         .args([&mod_js.as_os_str()])
@@ -123,3 +126,313 @@ pub fn it_removes_lassie_temp_on_start() {
         "all files from the previous run should have been deleted"
     );
 }
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+/// Modules started from a JS file are named after the file path without the extension.
+fn module_name(mod_js: &Path) -> String {
+    mod_js
+        .display()
+        .to_string()
+        .trim_end_matches(".js")
+        .to_string()
+}
+
+/// Run zinniad with the given modules until it exits, keeping the cache and the state in
+/// `temp_root`. Modules are not restarted unless `extra_env` sets `MAX_RESTARTS`.
+///
+/// Returns the process output and the events printed to stdout.
+fn run_zinniad(
+    temp_root: &Path,
+    modules: &[&Path],
+    extra_env: &[(&str, &str)],
+) -> (Output, Vec<Value>) {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let bin = assert_cmd::cargo::cargo_bin("zinniad");
+    let output = Command::new(bin)
+        .env("NO_COLOR", "1")
+        .env("FIL_WALLET_ADDRESS", "f1test")
+        .env("STATION_ID", "a".repeat(88))
+        .env("PEER_KEY_SECRET", "test secret")
+        .env("CACHE_ROOT", temp_root.join("cache"))
+        .env("STATE_ROOT", temp_root.join("state"))
+        .env("MAX_RESTARTS", "0")
+        .envs(extra_env.iter().copied())
+        .args(modules)
+        .output()
+        .expect("cannot run zinniad");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    println!("[zinniad] {stdout}");
+    let events = stdout
+        .lines()
+        .map(|ln| serde_json::from_str(ln).expect("cannot parse event"))
+        .collect();
+    (output, events)
+}
+
+#[test]
+pub fn it_runs_multiple_modules() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let report_info_js = fixture("report-info.js");
+    let complete_job_js = fixture("complete-job.js");
+
+    let (output, events) = run_zinniad(temp_root.path(), &[&report_info_js, &complete_job_js], &[]);
+    assert!(output.status.success(), "zinniad should exit with success");
+
+    let report_info_name = module_name(&report_info_js);
+    assert!(
+        events.iter().any(|e| e["type"] == "activity:info"
+            && e["module"] == report_info_name.as_str()
+            && e["message"] == "hello from report-info"),
+        "activity reported by the first module should include its name"
+    );
+
+    let complete_job_name = module_name(&complete_job_js);
+    let last_jobs_completed = events
+        .iter()
+        .rev()
+        .find(|e| e["type"] == "jobs-completed")
+        .expect("zinniad should report completed jobs");
+    assert_eq!(last_jobs_completed["total"], 1);
+    assert_eq!(last_jobs_completed["modules"][&complete_job_name], 1);
+    assert_eq!(last_jobs_completed["modules"][&report_info_name], 0);
+}
+
+#[test]
+pub fn it_restarts_crashed_modules() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let crash_js = fixture("crash.js");
+
+    let (output, events) = run_zinniad(temp_root.path(), &[&crash_js], &[("MAX_RESTARTS", "1")]);
+    assert!(
+        !output.status.success(),
+        "zinniad should fail after giving up on the module"
+    );
+
+    let activities: Vec<(String, String)> = events
+        .iter()
+        .filter(|e| e["type"] == "activity:info" || e["type"] == "activity:error")
+        .map(|e| {
+            (
+                e["type"].as_str().unwrap().to_string(),
+                e["message"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+
+    let module_name = module_name(&crash_js);
+    assert_eq!(
+        activities,
+        [
+            (
+                "activity:error",
+                format!("Module {module_name} crashed: Uncaught Error: boom")
+            ),
+            (
+                "activity:info",
+                format!("Restarting module {module_name} in 1s (restart 1 of 1).")
+            ),
+            (
+                "activity:error",
+                format!("Module {module_name} crashed: Uncaught Error: boom")
+            ),
+            (
+                "activity:error",
+                format!("Module {module_name} reached the limit of 1 restarts in 600s. Giving up.")
+            ),
+        ]
+        .map(|(t, m)| (t.to_string(), m))
+    );
+}
+
+#[test]
+pub fn it_exits_with_the_module_exit_code() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let mod_js = fixture("exit-with-code.js");
+
+    let (output, events) = run_zinniad(temp_root.path(), &[&mod_js], &[]);
+    assert_eq!(output.status.code(), Some(3), "exit code");
+
+    let expected = format!("Module {} exited with code 3.", module_name(&mod_js));
+    let reported = events
+        .iter()
+        .any(|e| e["type"] == "activity:error" && e["message"] == expected.as_str());
+    assert!(reported, "zinniad should report the exit code");
+}
+
+#[test]
+pub fn it_denies_access_to_private_addresses() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let mod_js = fixture("fetch-localhost.js");
+
+    let (output, events) = run_zinniad(temp_root.path(), &[&mod_js], &[]);
+    assert!(output.status.success(), "zinniad should exit with success");
+
+    let expected = "Permission denied: fetch() cannot access 127.0.0.1:1: \
+                    access to private and loopback addresses is not allowed.";
+    let reported = events
+        .iter()
+        .any(|e| e["type"] == "activity:error" && e["message"] == expected);
+    assert!(reported, "zinniad should report the denied request");
+}
+
+#[test]
+pub fn it_denies_host_names_resolving_to_private_addresses() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let mod_js = fixture("fetch-resolved-localhost.js");
+
+    let (output, events) = run_zinniad(temp_root.path(), &[&mod_js], &[]);
+    assert!(output.status.success(), "zinniad should exit with success");
+
+    let messages = module_info_messages(&events);
+    assert_eq!(messages.len(), 2, "unexpected activities: {messages:?}");
+    assert!(
+        messages[0].starts_with("fetch failed:")
+            && messages[0].contains(
+                "fetch() cannot access localtest.me: the host name resolves to 127.0.0.1, \
+                 access to private and loopback addresses is not allowed."
+            ),
+        "unexpected fetch error: {}",
+        messages[0]
+    );
+    assert!(
+        messages[1].contains(
+            "Zinnia.requestProtocol() cannot access /dns4/localtest.me/tcp/1/p2p/\
+             12D3KooWRH71QRJe5vrMp6zZXoH4K7z5MDSWwTXXPriG9dK8HQXk: the host name resolves to \
+             /ip4/127.0.0.1/tcp/1/p2p/12D3KooWRH71QRJe5vrMp6zZXoH4K7z5MDSWwTXXPriG9dK8HQXk"
+        ),
+        "unexpected requestProtocol error: {}",
+        messages[1]
+    );
+}
+
+#[test]
+pub fn it_rejects_modules_sharing_state_directory() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let nested = temp_root.path().join("mods").join("a.js");
+    let flat = temp_root.path().join("mods_a.js");
+    std::fs::create_dir_all(nested.parent().unwrap()).unwrap();
+    std::fs::write(&nested, "").unwrap();
+    std::fs::write(&flat, "").unwrap();
+
+    let (output, _events) = run_zinniad(temp_root.path(), &[&nested, &flat], &[]);
+    assert!(!output.status.success(), "zinniad should fail");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("would share the state directory"),
+        "unexpected stderr: {stderr}"
+    );
+}
+
+#[test]
+pub fn it_runs_module_described_by_manifest() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let module_dir = fixture("manifest-module");
+
+    let (output, events) = run_zinniad(temp_root.path(), &[&module_dir], &[]);
+    assert!(output.status.success(), "zinniad should exit with success");
+
+    let first_activity = events
+        .iter()
+        .find(|e| e["type"] == "activity:info" && !e["module"].is_null())
+        .expect("the module should report an activity");
+    assert_eq!(first_activity["module"], "manifest-module");
+    assert_eq!(first_activity["message"], "manifest-module@1.2.3");
+}
+
+/// Messages of the info activities reported by the modules (excluding zinniad itself).
+fn module_info_messages(events: &[Value]) -> Vec<String> {
+    events
+        .iter()
+        .filter(|e| e["type"] == "activity:info" && !e["module"].is_null())
+        .map(|e| e["message"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+pub fn it_persists_kv_data_across_restarts() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let mod_js = fixture("kv-counter.js");
+
+    for expected in ["runs: 1", "runs: 2"] {
+        let (output, events) = run_zinniad(temp_root.path(), &[&mod_js], &[]);
+        assert!(output.status.success(), "zinniad should exit with success");
+        assert_eq!(module_info_messages(&events), [expected]);
+    }
+}
+
+#[test]
+pub fn it_keeps_peer_id_across_restarts() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let mod_js = fixture("report-peer-id.js");
+
+    let (output, events) = run_zinniad(temp_root.path(), &[&mod_js], &[]);
+    assert!(output.status.success(), "zinniad should exit with success");
+    let first = module_info_messages(&events);
+    assert!(
+        first.len() == 1 && first[0].starts_with("peer: 12D3KooW"),
+        "unexpected peer: {first:?}"
+    );
+
+    let (_, events) = run_zinniad(temp_root.path(), &[&mod_js], &[]);
+    assert_eq!(module_info_messages(&events), first);
+}
+
+#[test]
+pub fn it_requires_peer_key_secret() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let mod_js = fixture("report-peer-id.js");
+
+    let (output, _events) = run_zinniad(temp_root.path(), &[&mod_js], &[("PEER_KEY_SECRET", "")]);
+    assert!(!output.status.success(), "zinniad should fail");
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("PEER_KEY_SECRET must not be empty"),
+        "unexpected stderr: {stderr}"
+    );
+}
+
+#[test]
+pub fn it_creates_new_peer_id_when_secret_changes() {
+    let temp_root = tempdir().expect("cannot create temporary directory");
+    let mod_js = fixture("report-peer-id.js");
+
+    let (_, events) = run_zinniad(temp_root.path(), &[&mod_js], &[]);
+    let first = module_info_messages(&events);
+
+    let env = [("PEER_KEY_SECRET", "new secret")];
+    let (output, events) = run_zinniad(temp_root.path(), &[&mod_js], &env);
+    assert!(output.status.success(), "zinniad should exit with success");
+    let errors: Vec<_> = events
+        .iter()
+        .filter(|e| e["type"] == "activity:error")
+        .map(|e| e["message"].as_str().unwrap().to_string())
+        .collect();
+    assert!(
+        errors.len() == 1 && errors[0].contains("cannot load its peer identity"),
+        "unexpected errors: {errors:?}"
+    );
+    let second = module_info_messages(&events);
+    assert!(
+        second.len() == 1 && second[0].starts_with("peer: 12D3KooW"),
+        "unexpected peer: {second:?}"
+    );
+    assert_ne!(second, first, "peer ID should change");
+
+    // The new identity is persisted
+    let (_, events) = run_zinniad(temp_root.path(), &[&mod_js], &env);
+    assert!(
+        !events.iter().any(|e| e["type"] == "activity:error"),
+        "zinniad should not report errors"
+    );
+    assert_eq!(module_info_messages(&events), second);
+}
@@ -0,0 +1 @@
+Zinnia.jobCompleted();
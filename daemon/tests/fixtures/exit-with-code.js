@@ -0,0 +1,2 @@
+Zinnia.activity.info("exiting");
+Zinnia.exit(3);
@@ -0,0 +1,5 @@
+try {
+  await fetch("http://127.0.0.1:1/");
+} catch (err) {
+  Zinnia.activity.info(`fetch failed: ${err.message}`);
+}
@@ -0,0 +1,16 @@
+// localtest.me and its subdomains resolve to 127.0.0.1
+try {
+  await fetch("http://localtest.me:1/");
+} catch (err) {
+  Zinnia.activity.info(`fetch failed: ${err.message}`);
+}
+
+try {
+  await Zinnia.requestProtocol(
+    "/dns4/localtest.me/tcp/1/p2p/12D3KooWRH71QRJe5vrMp6zZXoH4K7z5MDSWwTXXPriG9dK8HQXk",
+    "/ping/1.0.0",
+    new Uint8Array(),
+  );
+} catch (err) {
+  Zinnia.activity.info(`requestProtocol failed: ${err.message}`);
+}
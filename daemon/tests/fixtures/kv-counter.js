@@ -0,0 +1,3 @@
+const runs = ((await Zinnia.kv.get("runs")) ?? 0) + 1;
+await Zinnia.kv.set("runs", runs);
+Zinnia.activity.info(`runs: ${runs}`);
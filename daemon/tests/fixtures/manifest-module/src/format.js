@@ -0,0 +1 @@
+export const formatModule = ({ name, version }) => `${name}@${version}`;
@@ -0,0 +1,3 @@
+import { formatModule } from "format";
+
+Zinnia.activity.info(formatModule(Zinnia.module));
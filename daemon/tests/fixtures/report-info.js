@@ -0,0 +1 @@
+Zinnia.activity.info("hello from report-info");
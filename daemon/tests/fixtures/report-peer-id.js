@@ -0,0 +1 @@
+Zinnia.activity.info(`peer: ${Zinnia.peerId}`);
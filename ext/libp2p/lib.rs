@@ -6,12 +6,30 @@ use deno_core::error::AnyError;
 use deno_core::{op2, JsBuffer, OpState};
 use libp2p::identity::PeerId;
 use libp2p::multiaddr::Protocol;
-use libp2p::Multiaddr;
 use peer::PeerNode;
 
-pub use peer::PeerNodeConfig;
+pub use libp2p::identity::Keypair;
+pub use libp2p::{multiaddr, Multiaddr};
+pub use peer::{PeerNodeConfig, PeerNodeMetrics};
 
 mod peer;
+mod resolve;
+
+/// Permission checks for outgoing libp2p connections, implemented by the embedder.
+pub trait Libp2pPermissions {
+    /// Check whether the module can dial the given address. The address includes the `/p2p`
+    /// component with the remote peer ID.
+    fn check_multiaddr(&mut self, addr: &Multiaddr, api_name: &str) -> Result<()>;
+
+    /// Check whether the module can dial `resolved`, the address `addr` resolved to. Called after
+    /// `check_multiaddr` allowed `addr` and only when `addr` contains DNS components.
+    fn check_resolved_multiaddr(
+        &mut self,
+        addr: &Multiaddr,
+        resolved: &Multiaddr,
+        api_name: &str,
+    ) -> Result<()>;
+}
 
 #[derive(Clone, Debug, Default)]
 pub struct Options {
@@ -24,9 +42,10 @@ struct DefaultNodeResourceId(deno_core::ResourceId);
 
 deno_core::extension!(
     zinnia_libp2p,
+    parameters = [P: Libp2pPermissions],
     ops = [
         op_p2p_get_peer_id,
-        op_p2p_request_protocol,
+        op_p2p_request_protocol<P>,
     ],
     esm = [
         dir "js",
@@ -56,16 +75,49 @@ pub fn op_p2p_get_peer_id(state: &mut OpState) -> Result<String> {
 
 #[op2(async)]
 #[buffer]
-pub async fn op_p2p_request_protocol(
+pub async fn op_p2p_request_protocol<P>(
     state: Rc<RefCell<OpState>>,
     #[string] remote_address: String,
     #[string] protocol_name: String,
     #[buffer] request_payload: JsBuffer,
-) -> Result<Vec<u8>> {
-    let mut peer_addr: Multiaddr = remote_address
+) -> Result<Vec<u8>>
+where
+    P: Libp2pPermissions + 'static,
+{
+    let api_name = "Zinnia.requestProtocol()";
+    let remote_addr: Multiaddr = remote_address
         .parse()
         .with_context(|| "invalid remote address")?;
 
+    state
+        .borrow_mut()
+        .borrow_mut::<P>()
+        .check_multiaddr(&remote_addr, api_name)?;
+
+    // Resolve the host names ourselves and dial the IP address we checked, otherwise the transport
+    // would resolve the names again and could connect to a different (e.g. private) address.
+    let mut peer_addr = remote_addr.clone();
+    let resolved_addrs = resolve::resolve_dns(&remote_addr).await?;
+    if resolved_addrs != std::slice::from_ref(&remote_addr) {
+        let mut state = state.borrow_mut();
+        let permissions = state.borrow_mut::<P>();
+        let mut first_err = None;
+        let allowed = resolved_addrs.into_iter().find(|resolved| {
+            match permissions.check_resolved_multiaddr(&remote_addr, resolved, api_name) {
+                Ok(()) => true,
+                Err(err) => {
+                    first_err.get_or_insert(err);
+                    false
+                }
+            }
+        });
+        match (allowed, first_err) {
+            (Some(resolved), _) => peer_addr = resolved,
+            (None, Some(err)) => return Err(err),
+            (None, None) => unreachable!("resolve_dns() returns at least one address"),
+        }
+    }
+
     let peer_id = match peer_addr.pop() {
         Some(Protocol::P2p(hash)) => {
             PeerId::from_multihash(hash).map_err(|_multihash| anyhow!("Invalid peer ID multihash"))
@@ -90,11 +142,16 @@ pub async fn op_p2p_request_protocol(
     Ok(response_payload)
 }
 
-pub async fn shutdown(_state: Rc<RefCell<OpState>>) -> Result<(), AnyError> {
-    // FIXME: shutdown the default PeerNode
-    // Note: the code bellow does not work because `node` is not mutable
-    // let rid = state.borrow::<DefaultNodeResourceId>().0;
-    // let node = state.resource_table.get::<PeerNode>(rid)?;
-    // node.shutdown().await?;
+/// Shut down the default PeerNode and its networking event loop.
+pub async fn shutdown(state: Rc<RefCell<OpState>>) -> Result<(), AnyError> {
+    let rid = state.borrow().borrow::<DefaultNodeResourceId>().0;
+    let node = state.borrow_mut().resource_table.take::<PeerNode>(rid)?;
+    // Pending requests may still hold a reference to the node. In that case, the event loop
+    // stops when the last reference is dropped and the command channel is closed.
+    if let Ok(mut node) = Rc::try_unwrap(node) {
+        node.shutdown()
+            .await
+            .map_err(|err| anyhow!("cannot shut down the peer node: {}", err))?;
+    }
     Ok(())
 }
@@ -35,7 +35,7 @@ use behaviour::{
     ProtocolInfo, RequestId, RequestResponse, RequestResponseEvent, RequestResponseMessage,
 };
 pub use behaviour::{RequestPayload, ResponsePayload};
-pub use config::PeerNodeConfig;
+pub use config::{PeerNodeConfig, PeerNodeMetrics};
 
 use deno_core::anyhow::Result;
 use deno_core::{AsyncResult, Resource};
@@ -43,6 +43,7 @@ use deno_core::{AsyncResult, Resource};
 use std::collections::{hash_map, HashMap};
 use std::error::Error;
 use std::rc::Rc;
+use std::sync::Arc;
 
 use either::Either;
 use tokio::sync::{mpsc, oneshot};
@@ -70,9 +71,11 @@ impl PeerNode {
     /// This will create the underlying network client and spawn a tokio task handling
     /// networking event loop. The returned [`PeerNode`] can be used to control the task.
     pub fn spawn(config: PeerNodeConfig) -> Result<PeerNode, Box<dyn Error>> {
-        // Create a new random public/private key pair
-        // Zinnia will always generate a new key pair on (re)start
-        let id_keys = Keypair::generate_ed25519();
+        // Use the configured identity, or create a new random public/private key pair
+        let id_keys = config
+            .keypair
+            .clone()
+            .unwrap_or_else(Keypair::generate_ed25519);
         let peer_id = id_keys.public().to_peer_id();
 
         let tcp_transport = create_transport(&id_keys)?;
@@ -98,7 +101,7 @@ impl PeerNode {
 
         let (command_sender, command_receiver) = mpsc::channel::<Command>(1);
 
-        let event_loop = EventLoop::new(swarm, command_receiver);
+        let event_loop = EventLoop::new(swarm, command_receiver, config.metrics);
         let event_loop_task = tokio::spawn(event_loop.run());
 
         Ok(Self {
@@ -112,7 +115,6 @@ impl PeerNode {
         self.peer_id
     }
 
-    #[allow(dead_code)]
     pub async fn shutdown(&mut self) -> Result<(), Box<dyn Error>> {
         if let Some(handle) = self.event_loop_task.take() {
             self.command_sender.send(Command::Shutdown).await?;
@@ -225,6 +227,7 @@ pub struct EventLoop {
     command_receiver: mpsc::Receiver<Command>,
     pending_dial: HashMap<PeerId, oneshot::Sender<Result<(), Box<dyn Error + Send>>>>,
     pending_requests: HashMap<RequestId, PendingRequest>,
+    metrics: Option<Arc<dyn PeerNodeMetrics>>,
 }
 
 pub struct PendingRequest {
@@ -232,12 +235,29 @@ pub struct PendingRequest {
 }
 
 impl EventLoop {
-    fn new(swarm: Swarm<NodeBehaviour>, command_receiver: mpsc::Receiver<Command>) -> Self {
+    fn new(
+        swarm: Swarm<NodeBehaviour>,
+        command_receiver: mpsc::Receiver<Command>,
+        metrics: Option<Arc<dyn PeerNodeMetrics>>,
+    ) -> Self {
         Self {
             swarm,
             command_receiver,
             pending_dial: Default::default(),
             pending_requests: Default::default(),
+            metrics,
+        }
+    }
+
+    fn record_dial(&self, succeeded: bool) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_dial(succeeded);
+        }
+    }
+
+    fn record_request(&self, succeeded: bool) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_request(succeeded);
         }
     }
 
@@ -269,6 +289,7 @@ impl EventLoop {
                     peer,
                 } => {
                     log::debug!("Cannot request {}: {}", peer, error);
+                    self.record_request(false);
                     let pending_request = self
                         .pending_requests
                         .remove(&request_id)
@@ -287,6 +308,7 @@ impl EventLoop {
                             response,
                         },
                 } => {
+                    self.record_request(true);
                     let pending_request = self
                         .pending_requests
                         .remove(&request_id)
@@ -327,6 +349,7 @@ impl EventLoop {
             } => {
                 if endpoint.is_dialer() {
                     if let Some(sender) = self.pending_dial.remove(&peer_id) {
+                        self.record_dial(true);
                         let _ = sender.send(Ok(()));
                     }
                 }
@@ -339,6 +362,7 @@ impl EventLoop {
             SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
                 if let Some(peer_id) = peer_id {
                     if let Some(sender) = self.pending_dial.remove(&peer_id) {
+                        self.record_dial(false);
                         let _ = sender.send(Err(Box::new(error)));
                     }
                 }
@@ -409,6 +433,7 @@ impl EventLoop {
                             e.insert(sender);
                         }
                         Err(err) => {
+                            self.record_dial(false);
                             let _ = sender.send(Err(Box::new(err)));
                         }
                     }
@@ -456,6 +481,8 @@ mod tests {
             connection_keep_alive: Duration::from_secs(1),
             request_timeout: Duration::from_secs(1),
             ping: Default::default(),
+            keypair: None,
+            metrics: None,
         }
     }
 
@@ -532,6 +559,22 @@ mod tests {
         peer.shutdown().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn uses_configured_keypair() {
+        init();
+        let keypair = Keypair::generate_ed25519();
+        let expected_peer_id = keypair.public().to_peer_id();
+
+        let mut peer = PeerNode::spawn(PeerNodeConfig {
+            keypair: Some(keypair),
+            ..default_test_config()
+        })
+        .unwrap();
+        assert_eq!(peer.peer_id(), expected_peer_id);
+
+        peer.shutdown().await.unwrap();
+    }
+
     #[tokio::test]
     async fn reports_dial_error() {
         init();
@@ -1,7 +1,9 @@
+use std::fmt::Debug;
+use std::sync::Arc;
 use std::time::Duration;
 
 use libp2p::identify;
-use libp2p::identity::PublicKey;
+use libp2p::identity::{Keypair, PublicKey};
 pub use libp2p::ping::Config as PingConfig;
 
 use super::behaviour::RequestResponseConfig;
@@ -19,6 +21,22 @@ pub struct PeerNodeConfig {
 
     /// Configuration for the built-in `ping` protocol
     pub ping: PingConfig,
+
+    /// The identity of the local peer. When not set, a new random keypair is generated, and
+    /// the peer ID changes on every start.
+    pub keypair: Option<Keypair>,
+
+    /// Record metrics about dials and requests, e.g. to export them to Prometheus.
+    pub metrics: Option<Arc<dyn PeerNodeMetrics>>,
+}
+
+/// Metrics about the network activity of a peer node, implemented by the embedder.
+pub trait PeerNodeMetrics: Debug + Send + Sync {
+    /// Record the outcome of dialing a remote peer.
+    fn record_dial(&self, succeeded: bool);
+
+    /// Record the outcome of a request sent to a remote peer.
+    fn record_request(&self, succeeded: bool);
 }
 
 impl Default for PeerNodeConfig {
@@ -28,6 +46,8 @@ impl Default for PeerNodeConfig {
             connection_keep_alive: Duration::from_secs(10),
             request_timeout: Duration::from_secs(10),
             ping: Default::default(),
+            keypair: None,
+            metrics: None,
         }
     }
 }
@@ -0,0 +1,116 @@
+use std::net::IpAddr;
+
+use deno_core::anyhow::{anyhow, Context, Result};
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// The maximum number of DNS lookups when resolving a single address. `/dnsaddr` TXT records
+/// can point to other `/dnsaddr` names.
+const MAX_DNS_LOOKUPS: usize = 32;
+
+/// The prefix of `/dnsaddr` TXT records, see https://github.com/multiformats/multiaddr/blob/master/protocols/DNSADDR.md
+const DNSADDR_PREFIX: &str = "_dnsaddr.";
+
+/// Resolve all `/dns`, `/dns4`, `/dns6` and `/dnsaddr` components of the address. The returned
+/// addresses contain IP addresses only, so that the embedder can check them before we dial the
+/// peer, and the transport dials them without another DNS lookup.
+///
+/// Addresses without DNS components are returned unchanged.
+pub async fn resolve_dns(addr: &Multiaddr) -> Result<Vec<Multiaddr>> {
+    if find_dns_component(addr).is_none() {
+        return Ok(vec![addr.clone()]);
+    }
+
+    let resolver =
+        TokioAsyncResolver::tokio_from_system_conf().context("cannot create the DNS resolver")?;
+    let mut resolved = Vec::new();
+    let mut unresolved = vec![addr.clone()];
+    let mut dns_lookups = 0;
+    let mut last_err = None;
+
+    while let Some(addr) = unresolved.pop() {
+        let Some((index, name)) = find_dns_component(&addr) else {
+            resolved.push(addr);
+            continue;
+        };
+        if dns_lookups == MAX_DNS_LOOKUPS {
+            log::debug!("Too many DNS lookups. Dropping unresolved {addr}.");
+            continue;
+        }
+        dns_lookups += 1;
+
+        match &name {
+            Protocol::Dns(host) | Protocol::Dns4(host) | Protocol::Dns6(host) => {
+                let ips = match resolver.lookup_ip(&**host).await {
+                    Ok(ips) => ips,
+                    Err(err) => {
+                        last_err = Some(anyhow!("cannot resolve {host}: {err}"));
+                        continue;
+                    }
+                };
+                for ip in ips.iter() {
+                    let component = match (&name, ip) {
+                        (Protocol::Dns4(_), IpAddr::V6(_)) | (Protocol::Dns6(_), IpAddr::V4(_)) => {
+                            continue
+                        }
+                        (_, IpAddr::V4(ip)) => Protocol::Ip4(ip),
+                        (_, IpAddr::V6(ip)) => Protocol::Ip6(ip),
+                    };
+                    let addr = addr
+                        .replace(index, |_| Some(component))
+                        .expect("`index` is a valid index");
+                    unresolved.push(addr);
+                }
+            }
+            Protocol::Dnsaddr(host) => {
+                let records = match resolver.txt_lookup(format!("{DNSADDR_PREFIX}{host}")).await {
+                    Ok(records) => records,
+                    Err(err) => {
+                        last_err = Some(anyhow!("cannot resolve {DNSADDR_PREFIX}{host}: {err}"));
+                        continue;
+                    }
+                };
+                // The resolved addresses must end with the components following `/dnsaddr`,
+                // typically the `/p2p` component with the peer ID
+                let prefix = addr.iter().take(index).collect::<Multiaddr>();
+                let suffix = addr.iter().skip(index + 1).collect::<Multiaddr>();
+                for record in records.iter() {
+                    for chars in record.txt_data() {
+                        let Some(value) = std::str::from_utf8(chars)
+                            .ok()
+                            .and_then(|s| s.strip_prefix("dnsaddr="))
+                        else {
+                            continue;
+                        };
+                        match value.parse::<Multiaddr>() {
+                            Ok(a) if a.ends_with(&suffix) => {
+                                unresolved.push(prefix.iter().chain(a.iter()).collect());
+                            }
+                            Ok(_) => {}
+                            Err(err) => log::debug!("Invalid dnsaddr record {value:?}: {err}"),
+                        }
+                    }
+                }
+            }
+            _ => unreachable!("find_dns_component() returns DNS components only"),
+        }
+    }
+
+    if resolved.is_empty() {
+        return Err(last_err
+            .unwrap_or_else(|| anyhow!("cannot resolve {addr}: no matching addresses found")));
+    }
+    // Dial the addresses in the order returned by the DNS server
+    resolved.reverse();
+    Ok(resolved)
+}
+
+fn find_dns_component(addr: &Multiaddr) -> Option<(usize, Protocol<'_>)> {
+    addr.iter().enumerate().find(|(_, p)| {
+        matches!(
+            p,
+            Protocol::Dns(_) | Protocol::Dns4(_) | Protocol::Dns6(_) | Protocol::Dnsaddr(_)
+        )
+    })
+}
@@ -0,0 +1,356 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+use deno_ast::dep::{DependencyDescriptor, DependencyKind, DynamicArgument};
+use deno_ast::swc::ast;
+use deno_ast::swc::atoms::JsWord;
+use deno_ast::swc::bundler::{Bundler, Hook, Load, ModuleData, ModuleRecord, Resolve};
+use deno_ast::swc::codegen::text_writer::JsWriter;
+use deno_ast::swc::codegen::{Config as CodegenConfig, Emitter};
+use deno_ast::swc::common::comments::SingleThreadedComments;
+use deno_ast::swc::common::{FileName, FilePathMapping, Globals, Mark, SourceMap, Span, GLOBALS};
+use deno_ast::swc::parser::lexer::Lexer;
+use deno_ast::swc::parser::{Parser, StringInput};
+use deno_ast::{
+    fold_program, get_syntax, Diagnostic, EmitOptions, MediaType, ParseParams, SourceMapConfig,
+    SourceTextInfo, ES_VERSION,
+};
+use deno_core::anyhow::{anyhow, Context, Result};
+use deno_core::{serde_json, ModuleLoader, ModuleSpecifier, ResolutionKind};
+use import_map::ImportMap;
+
+use crate::module_loader::{get_module_root, ZinniaModuleLoader};
+use crate::remote_modules::{RemoteModules, RemoteModulesConfig};
+
+/// Built-in modules provided by the runtime, we keep them as external imports in the bundle.
+const BUILTIN_MODULES: &[&str] = &["zinnia:test", "zinnia:assert"];
+
+pub struct BundleOptions {
+    /// The user agent to use when downloading remote modules.
+    pub agent_version: String,
+
+    /// Import map used to resolve bare specifiers, see `read_import_map()`.
+    pub import_map: Option<ImportMap>,
+
+    /// Allow `https:` imports, see `RemoteModulesConfig`.
+    pub remote_modules: Option<RemoteModulesConfig>,
+
+    /// Append the source map to the bundle as a `data:` URL.
+    pub inline_source_map: bool,
+}
+
+/// Bundle the module `entry` with all its dependencies into a single ES module.
+///
+/// Modules are resolved and loaded using the same rules as `zinniad` applies at runtime:
+/// only modules inside the directory of the entry module can be imported. Imports of
+/// built-in modules like `zinnia:test` are preserved.
+pub async fn bundle_module(entry: &ModuleSpecifier, options: BundleOptions) -> Result<String> {
+    let remote_modules = options
+        .remote_modules
+        .map(|config| RemoteModules::new(config, &options.agent_version))
+        .transpose()?;
+    let loader = ZinniaModuleLoader::build(
+        Some(get_module_root(entry)?),
+        options.import_map,
+        remote_modules,
+    )?;
+    let graph = ModuleGraph::build(&loader, entry).await?;
+
+    let globals = Globals::new();
+    GLOBALS.set(&globals, || {
+        emit_bundle(&globals, &graph, options.inline_source_map)
+    })
+}
+
+/// Sources of all modules reachable from the entry module, together with resolved imports.
+struct ModuleGraph {
+    entry: ModuleSpecifier,
+    sources: HashMap<ModuleSpecifier, String>,
+    /// Maps `(referrer, specifier)` to the resolved module URL.
+    resolutions: HashMap<(ModuleSpecifier, String), ModuleSpecifier>,
+}
+
+impl ModuleGraph {
+    async fn build(loader: &ZinniaModuleLoader, entry: &ModuleSpecifier) -> Result<Self> {
+        let mut graph = ModuleGraph {
+            entry: entry.clone(),
+            sources: HashMap::new(),
+            resolutions: HashMap::new(),
+        };
+
+        let mut visited = HashSet::from([entry.clone()]);
+        let mut pending = VecDeque::from([(entry.clone(), None)]);
+        while let Some((specifier, referrer)) = pending.pop_front() {
+            let code = loader
+                .load_source(&specifier, referrer.as_ref())
+                .await
+                .with_context(|| format!("Cannot bundle {specifier}"))?;
+
+            for (import, is_dynamic) in find_imports(&specifier, &code)? {
+                if BUILTIN_MODULES.contains(&import.as_str()) {
+                    continue;
+                }
+                // The bundler inlines only static imports, dynamically imported modules would be
+                // missing in the bundle
+                if is_dynamic {
+                    return Err(anyhow!(
+                        "Cannot bundle the dynamic import of {import:?} in {specifier}. \
+                         Use a static import instead."
+                    ));
+                }
+                let resolved = loader
+                    .resolve(&import, specifier.as_str(), ResolutionKind::Import)
+                    .with_context(|| format!("Cannot resolve {import:?} from {specifier}"))?;
+                graph
+                    .resolutions
+                    .insert((specifier.clone(), import), resolved.clone());
+                if visited.insert(resolved.clone()) {
+                    pending.push_back((resolved, Some(specifier.clone())));
+                }
+            }
+
+            graph.sources.insert(specifier, code);
+        }
+
+        Ok(graph)
+    }
+}
+
+/// Find specifiers of all modules imported by the given module, excluding type-only imports and
+/// dynamic imports with computed specifiers. Returns the specifiers together with a flag
+/// indicating whether the import is dynamic.
+fn find_imports(specifier: &ModuleSpecifier, code: &str) -> Result<Vec<(String, bool)>> {
+    let media_type = MediaType::from_specifier(specifier);
+    if media_type == MediaType::Json {
+        return Ok(vec![]);
+    }
+    if matches!(media_type, MediaType::Jsx | MediaType::Tsx) {
+        return Err(anyhow!(
+            "JSX is not supported. Please convert the module {specifier} to a .js or .ts file."
+        ));
+    }
+
+    let parsed = deno_ast::parse_module(ParseParams {
+        specifier: specifier.to_string(),
+        text_info: SourceTextInfo::from_string(code.to_string()),
+        media_type,
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax: None,
+    })?;
+
+    let imports = parsed
+        .analyze_dependencies()
+        .into_iter()
+        .filter_map(|dep| match dep {
+            DependencyDescriptor::Static(dep) => match dep.kind {
+                DependencyKind::ImportType | DependencyKind::ExportType => None,
+                _ => Some((dep.specifier.to_string(), false)),
+            },
+            DependencyDescriptor::Dynamic(dep) => match dep.argument {
+                DynamicArgument::String(specifier) => Some((specifier.to_string(), true)),
+                _ => None,
+            },
+        })
+        .collect();
+    Ok(imports)
+}
+
+fn emit_bundle(globals: &Globals, graph: &ModuleGraph, inline_source_map: bool) -> Result<String> {
+    let cm = Rc::new(SourceMap::new(FilePathMapping::empty()));
+    let loader = BundleLoader {
+        graph,
+        cm: cm.clone(),
+    };
+    let resolver = BundleResolver { graph };
+    let config = deno_ast::swc::bundler::Config {
+        module: deno_ast::swc::bundler::ModuleType::Es,
+        external_modules: BUILTIN_MODULES.iter().map(|m| JsWord::from(*m)).collect(),
+        ..Default::default()
+    };
+    let mut bundler = Bundler::new(
+        globals,
+        cm.clone(),
+        loader,
+        resolver,
+        config,
+        Box::new(BundleHook),
+    );
+
+    let entries = HashMap::from([("bundle".to_string(), FileName::Url(graph.entry.clone()))]);
+    let output = bundler
+        .bundle(entries)
+        .context("Cannot bundle the module")?;
+
+    let mut buf = Vec::new();
+    let mut srcmap = Vec::new();
+    {
+        // We cannot use a struct expression because the Config is #[non_exhaustive]
+        let mut cfg = CodegenConfig::default();
+        cfg.target = ES_VERSION;
+        cfg.emit_assert_for_import_attributes = false;
+        let mut emitter = Emitter {
+            cfg,
+            cm: cm.clone(),
+            comments: None,
+            wr: Box::new(JsWriter::new(cm.clone(), "\n", &mut buf, Some(&mut srcmap))),
+        };
+        emitter
+            .emit_module(&output[0].module)
+            .context("Cannot emit the bundle")?;
+    }
+    let mut code = String::from_utf8(buf).context("The bundle is not valid UTF-8")?;
+
+    if inline_source_map {
+        let mut map = Vec::new();
+        cm.build_source_map_with_config(
+            &srcmap,
+            None,
+            SourceMapConfig {
+                inline_sources: true,
+            },
+        )
+        .to_writer(&mut map)?;
+        code.push_str(&format!(
+            "//# sourceMappingURL=data:application/json;base64,{}\n",
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, map)
+        ));
+    }
+
+    Ok(code)
+}
+
+struct BundleLoader<'a> {
+    graph: &'a ModuleGraph,
+    cm: Rc<SourceMap>,
+}
+
+impl Load for BundleLoader<'_> {
+    fn load(&self, file_name: &FileName) -> Result<ModuleData> {
+        let FileName::Url(specifier) = file_name else {
+            return Err(anyhow!("Unsupported module {file_name}"));
+        };
+        let source = self
+            .graph
+            .sources
+            .get(specifier)
+            .ok_or_else(|| anyhow!("Module {specifier} is missing in the module graph"))?;
+
+        let media_type = MediaType::from_specifier(specifier);
+        let (source, syntax) = if media_type == MediaType::Json {
+            // JSON modules are converted to JS modules with a default export
+            let code = format!(
+                "export default JSON.parse({});",
+                serde_json::to_string(source.trim_end())?
+            );
+            (code, get_syntax(MediaType::JavaScript))
+        } else {
+            (source.clone(), get_syntax(media_type))
+        };
+
+        let fm = self.cm.new_source_file(file_name.clone(), source);
+        let comments = SingleThreadedComments::default();
+        let lexer = Lexer::new(syntax, ES_VERSION, StringInput::from(&*fm), Some(&comments));
+        let mut parser = Parser::new_from(lexer);
+        let module = parser.parse_module().map_err(|err| {
+            Diagnostic::from_swc_error(
+                err,
+                specifier.as_str(),
+                SourceTextInfo::from_string(fm.src.to_string()),
+            )
+        })?;
+        let text_info = SourceTextInfo::from_string(fm.src.to_string());
+        let diagnostics = parser
+            .take_errors()
+            .into_iter()
+            .map(|err| Diagnostic::from_swc_error(err, specifier.as_str(), text_info.clone()))
+            .collect::<Vec<_>>();
+
+        // Strip TypeScript types
+        let program = fold_program(
+            ast::Program::Module(module),
+            &EmitOptions::default(),
+            self.cm.clone(),
+            &comments,
+            Mark::fresh(Mark::root()),
+            &diagnostics,
+        )?;
+        let ast::Program::Module(module) = program else {
+            unreachable!("fold_program() must preserve the program kind");
+        };
+
+        Ok(ModuleData {
+            fm,
+            module,
+            helpers: Default::default(),
+        })
+    }
+}
+
+struct BundleResolver<'a> {
+    graph: &'a ModuleGraph,
+}
+
+impl Resolve for BundleResolver<'_> {
+    fn resolve(&self, referrer: &FileName, specifier: &str) -> Result<FileName> {
+        let FileName::Url(referrer) = referrer else {
+            return Err(anyhow!("Unsupported referrer {referrer}"));
+        };
+        self.graph
+            .resolutions
+            .get(&(referrer.clone(), specifier.to_string()))
+            .map(|resolved| FileName::Url(resolved.clone()))
+            .ok_or_else(|| anyhow!("Cannot resolve {specifier:?} from {referrer}"))
+    }
+}
+
+/// Rewrite `import.meta.url` of bundled modules to preserve the behaviour of the original code.
+struct BundleHook;
+
+impl Hook for BundleHook {
+    fn get_import_meta_props(
+        &self,
+        span: Span,
+        module_record: &ModuleRecord,
+    ) -> Result<Vec<ast::KeyValueProp>> {
+        Ok(vec![ast::KeyValueProp {
+            key: ast::PropName::Ident(ast::Ident::new("url".into(), span)),
+            value: Box::new(ast::Expr::Lit(ast::Lit::Str(ast::Str {
+                span,
+                value: module_record.file_name.to_string().into(),
+                raw: None,
+            }))),
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn finds_static_and_dynamic_imports() {
+        let specifier = ModuleSpecifier::parse("file:///project/main.ts").unwrap();
+        let imports = find_imports(
+            &specifier,
+            r#"
+import { test } from "zinnia:test";
+import type { Options } from "./types.ts";
+export { greet } from "./lib.ts";
+const mod = await import("./dyn.js");
+const other = await import(`./${mod.name}.js`);
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            imports,
+            vec![
+                ("zinnia:test".to_string(), false),
+                ("./lib.ts".to_string(), false),
+                ("./dyn.js".to_string(), true),
+            ]
+        );
+    }
+}
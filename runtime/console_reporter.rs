@@ -6,7 +6,7 @@ use termcolor::{Ansi, Color, ColorSpec, WriteColor};
 
 use crate::anyhow::Result;
 use crate::colors::use_color;
-use crate::{LogLevel, Reporter};
+use crate::{Activity, ActivityLevel, LogLevel, Reporter};
 
 #[derive(Debug)]
 pub struct JobCompletionTracker {
@@ -132,12 +132,13 @@ impl Reporter for ConsoleReporter {
         }
     }
 
-    fn info_activity(&self, msg: &str) {
-        self.report("INFO", msg, Color::Green);
-    }
-
-    fn error_activity(&self, msg: &str) {
-        self.report("ERROR", msg, Color::Red);
+    fn activity(&self, activity: &Activity) {
+        let (scope, color) = match activity.level {
+            ActivityLevel::Info => ("INFO", Color::Green),
+            ActivityLevel::Warn => ("WARN", Color::Yellow),
+            ActivityLevel::Error => ("ERROR", Color::Red),
+        };
+        self.report(scope, &activity.to_string(), color);
     }
 
     fn job_completed(&self) {
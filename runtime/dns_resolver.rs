@@ -0,0 +1,70 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use deno_core::anyhow::{Error, Result};
+use deno_fetch::reqwest;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::header::{HeaderMap, USER_AGENT};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::permissions::{NetDestination, NetPermissions};
+
+/// Resolves host names for `fetch()` and checks the resolved addresses against the network
+/// policy, e.g. to deny host names pointing to private addresses. The check runs right before the
+/// client connects, so the host name cannot resolve to a different address after the check.
+///
+/// The resolver runs outside of the module's thread, it sends the denials to `denials` for the
+/// module to report them, see `ZinniaPermissions::report_resolver_denials()`.
+struct PolicyResolver {
+    permissions: Arc<NetPermissions>,
+    denials: UnboundedSender<Error>,
+}
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let permissions = Arc::clone(&self.permissions);
+        let denials = self.denials.clone();
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+            let dest = NetDestination::from_domain(host);
+            for addr in &addrs {
+                let resolved = NetDestination::from_ip(addr.ip());
+                if let Err(err) = permissions.check_resolved(&dest, &resolved, "fetch()") {
+                    let message = err.to_string();
+                    // The module is gone when the receiver was dropped, there is nobody to tell
+                    let _ = denials.send(err);
+                    return Err(message.into());
+                }
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Create the HTTP client for `fetch()`. The client is configured like the default client created
+/// by `deno_fetch`, except that it resolves host names using `PolicyResolver`.
+pub fn create_http_client(
+    user_agent: &str,
+    permissions: NetPermissions,
+    denials: UnboundedSender<Error>,
+) -> Result<reqwest::Client> {
+    let mut tls_config =
+        deno_tls::create_client_config(None, vec![], None, None, deno_tls::SocketUse::Http)?;
+    tls_config.alpn_protocols = vec!["h2".into(), "http/1.1".into()];
+
+    let mut headers = HeaderMap::new();
+    headers.insert(USER_AGENT, user_agent.parse()?);
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .default_headers(headers)
+        .use_preconfigured_tls(tls_config)
+        .dns_resolver(Arc::new(PolicyResolver {
+            permissions: Arc::new(permissions),
+            denials,
+        }))
+        .build()?;
+    Ok(client)
+}
@@ -1,17 +1,56 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-use deno_core::anyhow::Result;
+use deno_core::anyhow::{anyhow, Result};
 use deno_core::error::JsError;
 use deno_core::url::Url;
-use deno_core::{op2, OpState};
+use deno_core::{op2, v8, OpState};
 use deno_fetch::FetchPermissions;
 use deno_web::TimersPermission;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::Notify;
+use zinnia_libp2p::{Libp2pPermissions, Multiaddr};
 
-use crate::Reporter;
+use crate::kv::{op_kv_commit, op_kv_get, op_kv_list, KvState, KvStoreConfig};
+use crate::metrics::ModuleMetrics;
+use crate::permissions::{NetDestination, NetPermissions};
+use crate::{Activity, Reporter, TestResults};
 
-/// Hard-coded permissions
-pub struct ZinniaPermissions;
+/// Permissions of the module. Denied requests are reported as error activities.
+pub struct ZinniaPermissions {
+    pub net: NetPermissions,
+    /// Requests to the IPFS retrieval client (Lassie) made on behalf of `fetch("ipfs://...")`
+    /// are always allowed, even when the policy denies loopback addresses.
+    pub lassie_url: Url,
+    /// The canonical path of the module root directory. When set, `fetch("file://...")` can read
+    /// files inside this directory only, the same sandbox applies to imports.
+    pub module_root: Option<PathBuf>,
+    pub reporter: Rc<dyn Reporter>,
+    /// Requests denied by the DNS resolver of `fetch()` after the host name was resolved.
+    pub resolver_denials: UnboundedReceiver<deno_core::anyhow::Error>,
+}
+
+impl ZinniaPermissions {
+    fn check_net(&self, dest: &NetDestination, api_name: &str) -> Result<()> {
+        self.net
+            .check(dest, api_name)
+            .map_err(|err| self.report_denied(err))
+    }
+
+    fn report_denied(&self, err: deno_core::anyhow::Error) -> deno_core::anyhow::Error {
+        self.reporter
+            .error_activity(&format!("Permission denied: {err}"));
+        err
+    }
+
+    /// Report the requests denied by the DNS resolver since the last call. The resolver runs on
+    /// a different thread, the module reports the denials when the request fails.
+    fn report_resolver_denials(&mut self) {
+        while let Ok(err) = self.resolver_denials.try_recv() {
+            self.report_denied(err);
+        }
+    }
+}
 
 impl TimersPermission for ZinniaPermissions {
     fn allow_hrtime(&mut self) -> bool {
@@ -27,22 +66,71 @@ impl TimersPermission for ZinniaPermissions {
 }
 
 impl FetchPermissions for ZinniaPermissions {
-    fn check_net_url(&mut self, _url: &Url, _api_name: &str) -> Result<()> {
-        Ok(())
+    fn check_net_url(&mut self, url: &Url, api_name: &str) -> Result<()> {
+        if url.origin() == self.lassie_url.origin() {
+            return Ok(());
+        }
+        self.check_net(&NetDestination::from_url(url)?, api_name)
     }
-    fn check_read(&mut self, _p: &Path, _api_name: &str) -> Result<()> {
+    fn check_read(&mut self, path: &Path, api_name: &str) -> Result<()> {
+        let Some(module_root) = &self.module_root else {
+            return Ok(());
+        };
+
+        // Resolve any symlinks inside the path to prevent modules from escaping our sandbox
+        let canonical_path = path.canonicalize().map_err(|err| {
+            self.report_denied(anyhow!("{api_name} cannot read {}: {err}.", path.display()))
+        })?;
+        if !canonical_path.starts_with(module_root) {
+            return Err(self.report_denied(anyhow!(
+                "{api_name} cannot read files outside of the module root directory.\n\
+                 Root directory (canonical): {}\n\
+                 File path (canonical): {}",
+                module_root.display(),
+                canonical_path.display(),
+            )));
+        }
         Ok(())
     }
 }
 
+impl Libp2pPermissions for ZinniaPermissions {
+    fn check_multiaddr(&mut self, addr: &Multiaddr, api_name: &str) -> Result<()> {
+        self.check_net(&NetDestination::from_multiaddr(addr), api_name)
+    }
+
+    fn check_resolved_multiaddr(
+        &mut self,
+        addr: &Multiaddr,
+        resolved: &Multiaddr,
+        api_name: &str,
+    ) -> Result<()> {
+        self.net
+            .check_resolved(
+                &NetDestination::from_multiaddr(addr),
+                &NetDestination::from_multiaddr(resolved),
+                api_name,
+            )
+            .map_err(|err| self.report_denied(err))
+    }
+}
+
 deno_core::extension!(
     zinnia_runtime,
     ops = [
         op_job_completed,
-        op_info_activity,
-        op_error_activity,
+        op_activity,
         op_zinnia_log,
-        op_format_test_error
+        op_format_test_error,
+        op_report_test_results,
+        op_set_exit_code,
+        op_exit,
+        op_check_net_url,
+        op_fetch_completed,
+        op_report_resolver_denials,
+        op_kv_get,
+        op_kv_list,
+        op_kv_commit,
     ],
     esm_entry_point = "ext:zinnia_runtime/99_main.js",
     esm = [
@@ -51,38 +139,78 @@ deno_core::extension!(
       "90_zinnia_apis.js",
       "98_global_scope.js",
       "internals.js",
+      "kv.js",
       "fetch.js",
       "test.js",
       "vendored/asserts.bundle.js",
       "99_main.js",
     ],
     options = {
-        reporter: Rc<dyn Reporter>,
+        permissions: ZinniaPermissions,
+        kv_store: Option<KvStoreConfig>,
+        metrics: Option<ModuleMetrics>,
     },
     state = |state, options| {
-        state.put(ZinniaPermissions {});
-        state.put(Rc::clone(&options.reporter));
+        state.put::<StoredReporter>(Rc::clone(&options.permissions.reporter));
+        state.put(options.permissions);
+        state.put(KvState {
+            config: options.kv_store,
+            store: None,
+        });
+        if let Some(metrics) = options.metrics {
+            state.put(metrics);
+        }
     }
 );
 
 type StoredReporter = Rc<dyn Reporter>;
 
+/// Check the network permissions before starting a background operation like `EventSource`.
 #[op2(fast)]
-fn op_job_completed(state: &mut OpState) {
-    let reporter = state.borrow::<StoredReporter>();
-    reporter.job_completed();
+fn op_check_net_url(
+    state: &mut OpState,
+    #[string] url: &str,
+    #[string] api_name: &str,
+) -> Result<()> {
+    let url = Url::parse(url)?;
+    state
+        .borrow_mut::<ZinniaPermissions>()
+        .check_net_url(&url, api_name)
 }
 
+/// Report network requests denied after resolving the host name, called when a request fails.
 #[op2(fast)]
-fn op_info_activity(state: &mut OpState, #[string] msg: &str) {
-    let reporter = state.borrow::<StoredReporter>();
-    reporter.info_activity(msg);
+fn op_report_resolver_denials(state: &mut OpState) {
+    state
+        .borrow_mut::<ZinniaPermissions>()
+        .report_resolver_denials();
 }
 
+/// Record a completed `fetch()` request in the metrics. The status is 0 when the request failed
+/// without a response.
 #[op2(fast)]
-fn op_error_activity(state: &mut OpState, #[string] msg: &str) {
+fn op_fetch_completed(state: &mut OpState, ipfs: bool, status: u32) {
+    let Some(metrics) = state.try_borrow::<ModuleMetrics>() else {
+        return;
+    };
+    let status = u16::try_from(status).ok().filter(|status| *status != 0);
+    if ipfs {
+        metrics.lassie_retrieval(status);
+    } else {
+        metrics.fetch_request(status);
+    }
+}
+
+#[op2(fast)]
+fn op_job_completed(state: &mut OpState) {
+    let reporter = state.borrow::<StoredReporter>();
+    reporter.job_completed();
+}
+
+#[op2]
+fn op_activity(state: &mut OpState, #[serde] activity: Activity) {
     let reporter = state.borrow::<StoredReporter>();
-    reporter.error_activity(msg);
+    reporter.activity(&activity);
 }
 
 #[op2(fast)]
@@ -96,3 +224,36 @@ fn op_zinnia_log(state: &mut OpState, #[string] msg: &str, #[smi] level: i32) {
 fn op_format_test_error(#[serde] error: JsError) -> String {
     crate::vendored::cli_tools::format_test_error(&error)
 }
+
+#[op2]
+fn op_report_test_results(state: &mut OpState, #[serde] results: TestResults) {
+    state.put(results);
+}
+
+/// The exit code set by the module via `Zinnia.exitCode` or `Zinnia.exit()`.
+#[derive(Debug, Clone, Copy)]
+pub struct ExitCode(pub i32);
+
+/// Allows `Zinnia.exit()` to stop the module.
+pub struct ExitSignal {
+    /// Used to stop the execution of JavaScript code immediately.
+    pub isolate: v8::IsolateHandle,
+    /// Wakes up the event loop, which may be waiting for pending timers or network requests.
+    pub notify: Rc<Notify>,
+    /// Set to `true` when the module called `Zinnia.exit()`.
+    pub requested: bool,
+}
+
+#[op2(fast)]
+fn op_set_exit_code(state: &mut OpState, #[smi] code: i32) {
+    state.put(ExitCode(code));
+}
+
+#[op2(fast)]
+fn op_exit(state: &mut OpState, #[smi] code: i32) {
+    state.put(ExitCode(code));
+    let signal = state.borrow_mut::<ExitSignal>();
+    signal.requested = true;
+    signal.isolate.terminate_execution();
+    signal.notify.notify_one();
+}
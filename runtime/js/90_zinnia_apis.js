@@ -1,11 +1,21 @@
 const primordials = globalThis.__bootstrap.primordials;
-const { ObjectDefineProperties, ObjectCreate, ObjectFreeze } = primordials;
+const {
+  JSONParse,
+  JSONStringify,
+  NumberIsInteger,
+  ObjectDefineProperties,
+  ObjectCreate,
+  ObjectFreeze,
+  ObjectGetPrototypeOf,
+  TypeError,
+} = primordials;
 
 const { ops } = globalThis.Deno.core;
 
 import { readOnly } from "ext:zinnia_runtime/06_util.js";
 import * as libp2p from "ext:zinnia_libp2p/01_peer.js";
 import { inspect } from "ext:deno_console/01_console.js";
+import { kvApi } from "ext:zinnia_runtime/kv.js";
 
 const versions = {
   zinnia: "",
@@ -24,31 +34,85 @@ ObjectDefineProperties(zinniaNs, libp2p.defaultPeerProps);
 
 const activityApi = ObjectCreate(null);
 ObjectDefineProperties(activityApi, {
-  info: readOnly(reportInfoActivity),
-  error: readOnly(reportErrorActivity),
+  info: readOnly((activity) => reportActivity("info", activity)),
+  warn: readOnly((activity) => reportActivity("warn", activity)),
+  error: readOnly((activity) => reportActivity("error", activity)),
 });
 
 ObjectDefineProperties(zinniaNs, {
   activity: readOnly(activityApi),
+  kv: readOnly(kvApi),
   jobCompleted: readOnly(reportJobCompleted),
   versions: readOnly(versions),
   inspect: readOnly(inspect),
+  exitCode: {
+    get: getExitCode,
+    set: setExitCode,
+    enumerable: true,
+    configurable: false,
+  },
+  exit: readOnly(exit),
 });
 
-function reportInfoActivity(msg) {
-  if (typeof msg !== "string") msg = "" + msg;
-  ops.op_info_activity(msg);
+// The activity is either a message or a plain object `{ message, code, details }`. Other values,
+// including Error instances, are converted to a string message.
+function reportActivity(level, activity) {
+  if (!isPlainObject(activity)) {
+    activity = { message: "" + activity };
+  }
+  let { message, code, details } = activity;
+  if (typeof message !== "string") message = "" + message;
+  if (code === undefined || code === null) {
+    code = null;
+  } else if (typeof code !== "string") {
+    throw new TypeError(`Activity code must be a string (found: ${typeof code})`);
+  }
+  if (details === undefined || details === null) {
+    details = null;
+  } else {
+    const encoded = JSONStringify(details);
+    if (encoded === undefined) {
+      throw new TypeError(
+        `Activity details must be serializable to JSON (found: ${typeof details})`,
+      );
+    }
+    details = JSONParse(encoded);
+  }
+  ops.op_activity({ level, message, code, details });
 }
 
-function reportErrorActivity(msg) {
-  if (typeof msg !== "string") msg = "" + msg;
-  ops.op_error_activity(msg);
+function isPlainObject(value) {
+  if (value === null || typeof value !== "object") return false;
+  // Plain objects inherit from Object.prototype (or nothing), whose prototype is null
+  const proto = ObjectGetPrototypeOf(value);
+  return proto === null || ObjectGetPrototypeOf(proto) === null;
 }
 
 function reportJobCompleted() {
   ops.op_job_completed();
 }
 
+let exitCode = 0;
+
+function getExitCode() {
+  return exitCode;
+}
+
+function setExitCode(code) {
+  if (!NumberIsInteger(code) || code < 0 || code > 255) {
+    throw new TypeError(`Exit code must be an integer between 0 and 255, was: ${code}`);
+  }
+  exitCode = code;
+  ops.op_set_exit_code(code);
+}
+
+// Stop the module immediately. Pending timers, network requests and other async operations are
+// abandoned. When `code` is not provided, the module exits with `Zinnia.exitCode`.
+function exit(code) {
+  if (code !== undefined) setExitCode(code);
+  ops.op_exit(exitCode);
+}
+
 function log(msg, level) {
   if (typeof msg !== "string") msg = "" + msg;
   ops.op_zinnia_log(msg, level);
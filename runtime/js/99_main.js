@@ -17,6 +17,7 @@ const {
   Error,
   ErrorPrototype,
   ObjectDefineProperties,
+  ObjectFreeze,
   ObjectPrototypeIsPrototypeOf,
   ObjectSetPrototypeOf,
 } = primordials;
@@ -36,8 +37,9 @@ import {
   mainRuntimeGlobalProperties,
   windowOrWorkerGlobalScope,
 } from "ext:zinnia_runtime/98_global_scope.js";
-import { setLassieConfig } from "ext:zinnia_runtime/fetch.js";
+import { handleWasmStreaming, setLassieConfig } from "ext:zinnia_runtime/fetch.js";
 import { setVersions } from "ext:zinnia_runtime/90_zinnia_apis.js";
+import { setTestFilter } from "ext:zinnia_runtime/internals.js";
 
 function formatException(error) {
   if (ObjectPrototypeIsPrototypeOf(ErrorPrototype, error)) {
@@ -54,7 +56,7 @@ function formatException(error) {
 function runtimeStart(runtimeOptions) {
   core.setMacrotaskCallback(timers.handleTimerMacrotask);
   // core.setMacrotaskCallback(promiseRejectMacrotaskCallback);
-  // core.setWasmStreamingCallback(fetch.handleWasmStreaming);
+  core.setWasmStreamingCallback(handleWasmStreaming);
   // core.setReportExceptionCallback(event.reportException);
   ops.op_set_format_exception_callback(formatException);
   // version.setVersions(
@@ -70,6 +72,7 @@ function runtimeStart(runtimeOptions) {
 
   setLassieConfig(runtimeOptions.lassieUrl, runtimeOptions.lassieAuth);
   setVersions(runtimeOptions.zinniaVersion, runtimeOptions.v8Version);
+  setTestFilter(runtimeOptions.testFilter);
 }
 
 let hasBootstrapped = false;
@@ -105,6 +108,12 @@ function bootstrapMainRuntime(runtimeOptions) {
   ObjectDefineProperties(globalThis.Zinnia, {
     walletAddress: util.readOnly(runtimeOptions.walletAddress),
     stationId: util.readOnly(runtimeOptions.stationId),
+    module: util.readOnly(
+      ObjectFreeze({
+        name: runtimeOptions.moduleName,
+        version: runtimeOptions.moduleVersion,
+      }),
+    ),
   });
 
   // delete `Deno` global
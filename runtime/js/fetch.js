@@ -1,8 +1,17 @@
-import { fetch as fetchImpl } from "ext:deno_fetch/26_fetch.js";
+import {
+  fetch as fetchImpl,
+  handleWasmStreaming as handleWasmStreamingImpl,
+} from "ext:deno_fetch/26_fetch.js";
 import { fromInnerResponse, toInnerResponse } from "ext:deno_fetch/23_response.js";
 import { toInnerRequest, fromInnerRequest, Request } from "ext:deno_fetch/23_request.js";
+import { EventSource as EventSourceImpl } from "ext:deno_fetch/27_eventsource.js";
 import { guardFromHeaders } from "ext:deno_fetch/20_headers.js";
 import { byteLowerCase } from "ext:deno_web/00_infra.js";
+import { URL } from "ext:deno_url/00_url.js";
+import * as webidl from "ext:deno_webidl/00_webidl.js";
+
+const core = globalThis.Deno.core;
+const ops = core.ops;
 
 const ipfsScheme = "ipfs://";
 let ipfsBaseUrl = undefined;
@@ -22,11 +31,24 @@ export function fetch(resource, options) {
   // See https://developer.mozilla.org/en-US/docs/Web/API/fetch#parameters
   // Fortunately, Request's constructor handles the conversions, and Request#url is always a string.
   // See https://developer.mozilla.org/en-US/docs/Web/API/Request/url
-  if (request.url.startsWith(ipfsScheme)) {
-    return fetchFromIpfs(request);
-  } else {
-    return fetchImpl(request);
-  }
+  const ipfs = request.url.startsWith(ipfsScheme);
+  const response = ipfs ? fetchFromIpfs(request) : fetchImpl(request);
+  return recordFetchMetrics(response, ipfs);
+}
+
+function recordFetchMetrics(response, ipfs) {
+  return response.then(
+    (res) => {
+      ops.op_fetch_completed(ipfs, res.status);
+      return res;
+    },
+    (err) => {
+      // Status 0 means the request failed without a response, e.g. because of a network error
+      ops.op_fetch_completed(ipfs, 0);
+      ops.op_report_resolver_denials();
+      throw err;
+    },
+  );
 }
 
 async function fetchFromIpfs(request) {
@@ -79,3 +101,426 @@ function patchIpfsResponse(response) {
 
   return fromInnerResponse(inner, guardFromHeaders(response.headers));
 }
+
+// Deno's EventSource connects from a background loop, where a permission error would become
+// an unhandled promise rejection crashing the module. We check the permissions upfront instead.
+export class EventSource extends EventSourceImpl {
+  constructor(url, eventSourceInitDict = undefined) {
+    let parsedUrl;
+    try {
+      parsedUrl = new URL(url);
+    } catch {
+      // Let Deno's implementation report the invalid URL
+    }
+    if (parsedUrl) {
+      ops.op_check_net_url(parsedUrl.href, "EventSource");
+    }
+    super(url, eventSourceInitDict);
+    // The host name may resolve to an address the module cannot access
+    this.addEventListener("error", () => ops.op_report_resolver_denials());
+  }
+}
+
+// Compile WebAssembly modules from `fetch()` responses, this powers
+// `WebAssembly.compileStreaming()` and `WebAssembly.instantiateStreaming()`.
+export function handleWasmStreaming(source, rid) {
+  let response;
+  try {
+    response = webidl.converters["Response"](
+      source,
+      "Failed to call 'WebAssembly.compileStreaming'",
+      "Argument 1",
+    );
+  } catch (err) {
+    core.abortWasmStreaming(rid, err);
+    return;
+  }
+
+  if (!response.url.startsWith(ipfsScheme)) {
+    handleWasmStreamingImpl(response, rid);
+    return;
+  }
+
+
+  // IPFS responses contain the content in the CAR format, we must extract the WebAssembly bytes
+  // from the CAR data before we can compile them. We feed the module to the compiler block by
+  // block as we walk the DAG.
+  (async () => {
+    if (!response.ok) {
+      throw new TypeError(`HTTP status code ${response.status}`);
+    }
+    const blocks = new CarBlocks(new Uint8Array(await response.arrayBuffer()));
+    const { cid, segments } = parseIpfsPath(response.url.slice(ipfsScheme.length));
+    const fileCid = await resolveUnixFsPath(blocks, cid, segments);
+    ops.op_wasm_streaming_set_url(rid, response.url);
+    for await (const chunk of readUnixFsFile(blocks, fileCid)) {
+      ops.op_wasm_streaming_feed(rid, chunk);
+    }
+  })().then(
+    () => core.close(rid),
+    (err) => core.abortWasmStreaming(rid, err),
+  );
+}
+
+const CID_VERSION_1 = 1;
+const CODEC_RAW = 0x55;
+const CODEC_DAG_PB = 0x70;
+const MULTIHASH_IDENTITY = 0x00;
+const MULTIHASH_SHA2_256 = 0x12;
+const SHA2_256_LENGTH = 32;
+
+// UnixFS node types, see https://github.com/ipfs/specs/blob/main/UNIXFS.md
+const UNIXFS_RAW = 0;
+const UNIXFS_DIRECTORY = 1;
+const UNIXFS_FILE = 2;
+const UNIXFS_HAMT_SHARD = 5;
+
+/**
+ * @typedef {{ codec: number, multihash: Uint8Array }} Cid
+ */
+
+// The blocks of the CAR returned by Lassie, indexed by their multihash. Every block is verified
+// against its CID when it's read.
+class CarBlocks {
+  #blocks = new Map();
+
+  constructor(/** @type {Uint8Array} */ car) {
+    const reader = new ByteReader(car);
+
+    // Skip the CAR header (a DAG-CBOR map with the roots and the version).
+    reader.skip(reader.readVarint());
+
+    while (reader.offset < car.length) {
+      const sectionLength = reader.readVarint();
+      const sectionEnd = reader.offset + sectionLength;
+      const { multihash } = readCid(reader);
+      this.#blocks.set(bytesToHex(multihash), reader.readBytes(sectionEnd - reader.offset));
+    }
+  }
+
+  async get(/** @type {Cid} */ cid) {
+    const hashReader = new ByteReader(cid.multihash);
+    const hashCode = hashReader.readVarint();
+    const digest = hashReader.readBytes(hashReader.readVarint());
+
+    // The identity multihash contains the data itself, such blocks are not stored in CARs.
+    if (hashCode === MULTIHASH_IDENTITY) return digest;
+    if (hashCode !== MULTIHASH_SHA2_256) {
+      throw new TypeError(`Unsupported multihash code 0x${hashCode.toString(16)}.`);
+    }
+
+    const data = this.#blocks.get(bytesToHex(cid.multihash));
+    if (data === undefined) {
+      throw new TypeError(`The retrieved CAR data does not contain the block ${formatCid(cid)}.`);
+    }
+    const actualDigest = new Uint8Array(await crypto.subtle.digest("SHA-256", data));
+    if (!bytesEqual(actualDigest, digest)) {
+      throw new TypeError(`The retrieved block does not match its CID ${formatCid(cid)}.`);
+    }
+    return data;
+  }
+}
+
+// Parse the part of an `ipfs://` URL after the scheme, e.g. `bafy.../dir/module.wasm`.
+function parseIpfsPath(/** @type {string} */ path) {
+  const [cidString, ...segments] = path.replace(/[?#].*$/, "").split("/");
+  return {
+    cid: parseCid(cidString),
+    segments: segments.filter((segment) => segment !== "").map(decodeURIComponent),
+  };
+}
+
+/** @returns {Cid} */
+function parseCid(/** @type {string} */ cidString) {
+  // CIDv0 is a base58btc-encoded sha2-256 multihash of a dag-pb block.
+  if (cidString.length === 46 && cidString.startsWith("Qm")) {
+    return { codec: CODEC_DAG_PB, multihash: decodeBase58(cidString) };
+  }
+
+  let bytes;
+  if (cidString.startsWith("b")) {
+    bytes = decodeBase32(cidString.slice(1));
+  } else if (cidString.startsWith("z")) {
+    bytes = decodeBase58(cidString.slice(1));
+  } else {
+    throw new TypeError(
+      `Invalid CID ${cidString}: only CIDv0 and CIDv1 encoded in base32 or base58btc ` +
+        "are supported.",
+    );
+  }
+  const reader = new ByteReader(bytes);
+  const cid = readCid(reader);
+  if (reader.offset !== bytes.length) {
+    throw new TypeError(`Invalid CID ${cidString}.`);
+  }
+  return cid;
+}
+
+/** @returns {Cid} */
+function readCid(/** @type {ByteReader} */ reader) {
+  const start = reader.offset;
+  // CIDv0 is a bare sha2-256 multihash.
+  if (reader.peekByte() === MULTIHASH_SHA2_256) {
+    reader.skip(2 + SHA2_256_LENGTH);
+    return { codec: CODEC_DAG_PB, multihash: reader.bytesFrom(start) };
+  }
+
+  const version = reader.readVarint();
+  if (version !== CID_VERSION_1) {
+    throw new TypeError(`Unsupported CID version ${version}.`);
+  }
+  const codec = reader.readVarint();
+  const hashStart = reader.offset;
+  reader.readVarint();
+  reader.skip(reader.readVarint());
+  return { codec, multihash: reader.bytesFrom(hashStart) };
+}
+
+// Follow the path segments through UnixFS directories, returns the CID of the last entry.
+async function resolveUnixFsPath(
+  /** @type {CarBlocks} */ blocks,
+  /** @type {Cid} */ cid,
+  /** @type {string[]} */ segments,
+) {
+  for (const name of segments) {
+    if (cid.codec !== CODEC_DAG_PB) {
+      throw new TypeError(`Cannot resolve '${name}': ${formatCid(cid)} is not a directory.`);
+    }
+    const node = decodeDagPb(await blocks.get(cid));
+    const { type } = decodeUnixFs(node.data);
+    if (type === UNIXFS_HAMT_SHARD) {
+      throw new TypeError(
+        `Cannot resolve '${name}': HAMT-sharded directories like ${formatCid(cid)} ` +
+          "are not supported.",
+      );
+    }
+    if (type !== UNIXFS_DIRECTORY) {
+      throw new TypeError(`Cannot resolve '${name}': ${formatCid(cid)} is not a directory.`);
+    }
+    const link = node.links.find((link) => link.name === name);
+    if (!link) {
+      throw new TypeError(`Cannot resolve '${name}': no such entry in ${formatCid(cid)}.`);
+    }
+    cid = link.cid;
+  }
+  return cid;
+}
+
+// Yield the content of the UnixFS file in order, a raw block or a tree of dag-pb nodes with the
+// data in their leaves.
+async function* readUnixFsFile(/** @type {CarBlocks} */ blocks, /** @type {Cid} */ cid) {
+  const block = await blocks.get(cid);
+  if (cid.codec === CODEC_RAW) {
+    yield block;
+    return;
+  }
+  if (cid.codec !== CODEC_DAG_PB) {
+    throw new TypeError(
+      `Unsupported codec 0x${cid.codec.toString(16)} of ${formatCid(cid)}, ` +
+        "WebAssembly modules retrieved from IPFS must be stored as UnixFS files.",
+    );
+  }
+
+  const node = decodeDagPb(block);
+  const { type, data } = decodeUnixFs(node.data);
+  if (type !== UNIXFS_FILE && type !== UNIXFS_RAW) {
+    throw new TypeError(
+      `${formatCid(cid)} is not a file. WebAssembly modules retrieved from IPFS must be ` +
+        "stored as UnixFS files.",
+    );
+  }
+  if (data.length > 0) yield data;
+  for (const link of node.links) {
+    yield* readUnixFsFile(blocks, link.cid);
+  }
+}
+
+// Decode a dag-pb block, see https://ipld.io/specs/codecs/dag-pb/spec/
+function decodeDagPb(/** @type {Uint8Array} */ bytes) {
+  let data = new Uint8Array();
+  const links = [];
+  for (const { field, value } of readProtobufFields(bytes)) {
+    if (field === 1) {
+      data = value;
+    } else if (field === 2) {
+      let cid;
+      let name = "";
+      for (const link of readProtobufFields(value)) {
+        if (link.field === 1) cid = readCid(new ByteReader(link.value));
+        if (link.field === 2) name = core.decode(link.value);
+      }
+      if (!cid) throw new TypeError("Invalid dag-pb data: link without a hash.");
+      links.push({ cid, name });
+    }
+  }
+  return { data, links };
+}
+
+// Decode the UnixFS metadata stored in the `Data` field of dag-pb nodes.
+function decodeUnixFs(/** @type {Uint8Array} */ bytes) {
+  let type = undefined;
+  let data = new Uint8Array();
+  for (const { field, value } of readProtobufFields(bytes)) {
+    if (field === 1) type = value;
+    if (field === 2) data = value;
+  }
+  if (type === undefined) throw new TypeError("Invalid UnixFS data: missing node type.");
+  return { type, data };
+}
+
+// Read the fields of a protobuf message. Varints are returned as numbers, length-delimited
+// fields as bytes and fixed-size fields are skipped.
+function* readProtobufFields(/** @type {Uint8Array} */ bytes) {
+  const reader = new ByteReader(bytes);
+  while (reader.offset < bytes.length) {
+    const key = reader.readVarint();
+    const field = Math.floor(key / 8);
+    switch (key % 8) {
+      case 0:
+        yield { field, value: reader.readVarint() };
+        break;
+      case 1:
+        reader.skip(8);
+        break;
+      case 2:
+        yield { field, value: reader.readBytes(reader.readVarint()) };
+        break;
+      case 5:
+        reader.skip(4);
+        break;
+      default:
+        throw new TypeError(`Invalid protobuf data: unsupported wire type ${key % 8}.`);
+    }
+  }
+}
+
+class ByteReader {
+  #bytes;
+  offset = 0;
+
+  constructor(/** @type {Uint8Array} */ bytes) {
+    this.#bytes = bytes;
+  }
+
+  // Unsigned LEB128 varint as used by multiformats.
+  readVarint() {
+    let value = 0;
+    let shift = 0;
+    while (true) {
+      const byte = this.readBytes(1)[0];
+      value += (byte & 0x7f) * 2 ** shift;
+      if (byte < 0x80) return value;
+      shift += 7;
+      if (shift > 49) throw new TypeError("Invalid IPFS data: varint is too long.");
+    }
+  }
+
+  readBytes(/** @type {number} */ length) {
+    const end = this.offset + length;
+    if (end > this.#bytes.length) {
+      throw new TypeError("Invalid IPFS data: unexpected end of data.");
+    }
+    const result = this.#bytes.subarray(this.offset, end);
+    this.offset = end;
+    return result;
+  }
+
+  peekByte() {
+    if (this.offset >= this.#bytes.length) {
+      throw new TypeError("Invalid IPFS data: unexpected end of data.");
+    }
+    return this.#bytes[this.offset];
+  }
+
+  bytesFrom(/** @type {number} */ start) {
+    return this.#bytes.subarray(start, this.offset);
+  }
+
+  skip(/** @type {number} */ length) {
+    this.readBytes(length);
+  }
+}
+
+const BASE32_ALPHABET = "abcdefghijklmnopqrstuvwxyz234567";
+const BASE58_ALPHABET = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+// Format the CID as CIDv1 using the multibase `base32` (the default encoding of CIDv1).
+function formatCid(/** @type {Cid} */ cid) {
+  const bytes = [CID_VERSION_1, ...encodeVarint(cid.codec), ...cid.multihash];
+  let output = "b";
+  let value = 0;
+  let bits = 0;
+  for (const byte of bytes) {
+    value = (value << 8) | byte;
+    bits += 8;
+    while (bits >= 5) {
+      output += BASE32_ALPHABET[(value >>> (bits - 5)) & 31];
+      bits -= 5;
+    }
+    value &= (1 << bits) - 1;
+  }
+  if (bits > 0) {
+    output += BASE32_ALPHABET[(value << (5 - bits)) & 31];
+  }
+  return output;
+}
+
+function decodeBase32(/** @type {string} */ encoded) {
+  const bytes = [];
+  let value = 0;
+  let bits = 0;
+  for (const char of encoded) {
+    const index = BASE32_ALPHABET.indexOf(char);
+    if (index < 0) throw new TypeError(`Invalid base32 character '${char}'.`);
+    value = (value << 5) | index;
+    bits += 5;
+    if (bits >= 8) {
+      bytes.push((value >>> (bits - 8)) & 0xff);
+      bits -= 8;
+    }
+    value &= (1 << bits) - 1;
+  }
+  return Uint8Array.from(bytes);
+}
+
+function decodeBase58(/** @type {string} */ encoded) {
+  // Little-endian digits of the decoded number in base 256
+  const bytes = [];
+  for (const char of encoded) {
+    let carry = BASE58_ALPHABET.indexOf(char);
+    if (carry < 0) throw new TypeError(`Invalid base58 character '${char}'.`);
+    for (let i = 0; i < bytes.length; i++) {
+      carry += bytes[i] * 58;
+      bytes[i] = carry & 0xff;
+      carry >>= 8;
+    }
+    while (carry > 0) {
+      bytes.push(carry & 0xff);
+      carry >>= 8;
+    }
+  }
+  // Each leading '1' encodes a leading zero byte
+  for (const char of encoded) {
+    if (char !== "1") break;
+    bytes.push(0);
+  }
+  return Uint8Array.from(bytes.reverse());
+}
+
+function encodeVarint(/** @type {number} */ value) {
+  const bytes = [];
+  while (value >= 0x80) {
+    bytes.push((value % 0x80) | 0x80);
+    value = Math.floor(value / 0x80);
+  }
+  bytes.push(value);
+  return bytes;
+}
+
+function bytesToHex(/** @type {Uint8Array} */ bytes) {
+  return Array.from(bytes, (byte) => byte.toString(16).padStart(2, "0")).join("");
+}
+
+function bytesEqual(/** @type {Uint8Array} */ a, /** @type {Uint8Array} */ b) {
+  return a.length === b.length && a.every((value, index) => value === b[index]);
+}
@@ -9,3 +9,14 @@ export function format_test_error(error) {
     ? DenoCore.ops.op_format_test_error(DenoCore.destructureError(error))
     : error;
 }
+
+// Filter selecting tests to run, configured by the host (e.g. `zinnia test --filter`)
+let testFilter = null;
+
+export function setTestFilter(filter) {
+  testFilter = filter;
+}
+
+export function getTestFilter() {
+  return testFilter;
+}
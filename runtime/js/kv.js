@@ -0,0 +1,101 @@
+const primordials = globalThis.__bootstrap.primordials;
+const {
+  ArrayPrototypeMap,
+  ArrayPrototypePush,
+  JSONParse,
+  JSONStringify,
+  NumberIsInteger,
+  ObjectCreate,
+  ObjectDefineProperties,
+  TypeError,
+} = primordials;
+
+const core = globalThis.Deno.core;
+const { opAsync } = core;
+
+import { readOnly } from "ext:zinnia_runtime/06_util.js";
+
+function validateKey(key) {
+  if (typeof key !== "string") {
+    throw new TypeError(`Key must be a string (found: ${typeof key})`);
+  }
+}
+
+// Values are stored as JSON
+function encodeValue(value) {
+  const encoded = JSONStringify(value);
+  if (encoded === undefined) {
+    throw new TypeError(`Value must be serializable to JSON (found: ${typeof value})`);
+  }
+  return encoded;
+}
+
+function decodeValue(encoded) {
+  return JSONParse(encoded);
+}
+
+async function get(key) {
+  validateKey(key);
+  const encoded = await opAsync("op_kv_get", key);
+  return encoded === null || encoded === undefined ? undefined : decodeValue(encoded);
+}
+
+async function set(key, value) {
+  await atomic().set(key, value).commit();
+}
+
+async function del(key) {
+  await atomic().delete(key).commit();
+}
+
+async function list({ prefix = "", limit } = {}) {
+  if (typeof prefix !== "string") {
+    throw new TypeError(`Prefix must be a string (found: ${typeof prefix})`);
+  }
+  if (limit !== undefined && (!NumberIsInteger(limit) || limit < 0)) {
+    throw new TypeError(`Limit must be a non-negative integer (found: ${limit})`);
+  }
+  const entries = await opAsync("op_kv_list", prefix, limit ?? null);
+  return ArrayPrototypeMap(entries, ([key, value]) => ({ key, value: decodeValue(value) }));
+}
+
+// Collects changes to commit atomically: either all of them are applied, or none.
+class AtomicOperation {
+  #mutations = [];
+  #committed = false;
+
+  set(key, value) {
+    validateKey(key);
+    ArrayPrototypePush(this.#mutations, { type: "set", key, value: encodeValue(value) });
+    return this;
+  }
+
+  delete(key) {
+    validateKey(key);
+    ArrayPrototypePush(this.#mutations, { type: "delete", key });
+    return this;
+  }
+
+  async commit() {
+    if (this.#committed) {
+      throw new TypeError("The atomic operation was already committed");
+    }
+    this.#committed = true;
+    await opAsync("op_kv_commit", this.#mutations);
+  }
+}
+
+function atomic() {
+  return new AtomicOperation();
+}
+
+const kvApi = ObjectCreate(null);
+ObjectDefineProperties(kvApi, {
+  get: readOnly(get),
+  set: readOnly(set),
+  delete: readOnly(del),
+  list: readOnly(list),
+  atomic: readOnly(atomic),
+});
+
+export { kvApi };
@@ -1,31 +1,73 @@
 // A minimalistic test framework for testing Zinnia modules
 // Inspired by `node:test`, `Deno.test`, `mocha` and others
 
-import { DenoCore, format_test_error } from "ext:zinnia_runtime/internals.js";
+import { DenoCore, format_test_error, getTestFilter } from "ext:zinnia_runtime/internals.js";
+
+// How long a test or a hook can run before we report it as failed.
+// Tests can configure a different value via the `timeout` option.
+const DEFAULT_TIMEOUT_MS = 30_000;
 
 /** @type {{
-   pendingTests: TestCase[];
+   rootSuite: TestSuite;
+   currentSuite: TestSuite;
+   onlyMode: boolean;
+   filter: ((fullName: string) => boolean) | undefined;
+   running: boolean;
    result: {
     passed: number;
+    skipped: number;
     failed: TestFailure[];
+    tests: TestOutcome[];
    };
    started: Date;
-   lastReportedTest: TestCase | undefined;
+   fileName: string | undefined;
  }} */
 let globalRoot;
 
+/**
+ * Build the function selecting tests to run from the test filter configured by the host (e.g.
+ * `zinnia test --filter`). Tests whose full name (including the names of parent suites) does not
+ * match the filter are ignored.
+ *
+ * The filter is either a substring or a regular expression enclosed in slashes, e.g. `/^fetch/`.
+ *
+ * @returns {((fullName: string) => boolean) | undefined}
+ */
+function buildTestFilter() {
+  const filter = getTestFilter();
+  if (!filter) {
+    return undefined;
+  } else if (filter.length > 2 && filter.startsWith("/") && filter.endsWith("/")) {
+    const pattern = new RegExp(filter.slice(1, -1));
+    return (fullName) => pattern.test(fullName);
+  } else {
+    return (fullName) => fullName.includes(filter);
+  }
+}
+
 function getGlobalRoot() {
   if (!globalRoot) {
+    const rootSuite = new TestSuite({ name: undefined, options: {}, parent: undefined });
     globalRoot = {
       started: Date.now(),
-      pendingTests: [],
+      rootSuite,
+      currentSuite: rootSuite,
+      onlyMode: false,
+      filter: undefined,
+      running: false,
       result: {
         passed: 0,
+        skipped: 0,
         failed: [],
+        tests: [],
       },
-      lastReportedTest: undefined,
+      fileName: undefined,
     };
-    setTimeout(runNextTest);
+    // Wait until the test file registers all tests before running them
+    setTimeout(runAllTests);
+  }
+  if (globalRoot.running) {
+    throw new Error("Tests, suites and hooks cannot be registered after the test run started.");
   }
   return globalRoot;
 }
@@ -36,53 +78,155 @@ function getGlobalRoot() {
   columnNumber: number;
 }} TestLocation */
 
+/** Outcome of a single test (or a failed hook), reported to the host for machine-readable reports.
+ * @typedef {{
+  name: string;
+  status: "passed" | "failed" | "skipped";
+  durationMs: number;
+  location: TestLocation;
+  error?: string;
+}} TestOutcome */
+
+/** @typedef {{
+  skip?: boolean;
+  only?: boolean;
+  timeout?: number;
+}} TestOptions */
+
+class TestSuite {
+  /**
+   * @param {Object} args
+   * @param {string | undefined} args.name
+   * @param {TestOptions} args.options
+   * @param {TestSuite | undefined} args.parent
+   */
+  constructor({ name, options, parent }) {
+    this.name = name;
+    this.parent = parent;
+    this.depth = parent ? parent.depth + 1 : 0;
+    this.skip = !!(options.skip || parent?.skip);
+    this.only = !!options.only;
+    this.timeout = options.timeout ?? parent?.timeout ?? DEFAULT_TIMEOUT_MS;
+    /** @type {(TestSuite | TestCase)[]} */
+    this.children = [];
+    /** @type {Record<"before" | "after" | "beforeEach" | "afterEach", TestHook[]>} */
+    this.hooks = { before: [], after: [], beforeEach: [], afterEach: [] };
+  }
+
+  get fullName() {
+    return this.parent?.fullName ? `${this.parent.fullName} > ${this.name}` : this.name;
+  }
+
+  /** The suite and all its parents, starting with the root suite. */
+  get path() {
+    return this.parent ? [...this.parent.path, this] : [this];
+  }
+
+  /** @returns {TestCase[]} */
+  selectedTests() {
+    return this.children.flatMap((child) =>
+      child instanceof TestSuite ? child.selectedTests() : isSelected(child) ? [child] : [],
+    );
+  }
+
+  containsOnly() {
+    return this.children.some((child) =>
+      child instanceof TestSuite ? child.only || child.containsOnly() : child.only,
+    );
+  }
+
+  isInsideOnly() {
+    return this.only || !!this.parent?.isInsideOnly();
+  }
+}
+
 class TestCase {
   /**
    * @param {Object} args
    * @param {string} args.name
    * @param {() => void | () => Promise<void>} args.fn
+   * @param {TestOptions} args.options
+   * @param {TestSuite} args.suite
    * @param {TestLocation} args.location
    */
-  constructor({ name, fn, location }) {
+  constructor({ name, fn, options, suite, location }) {
     this.name = name;
     this.fn = fn;
+    this.suite = suite;
     this.location = location;
+    this.skip = !!(options.skip || suite.skip);
+    this.only = !!options.only;
+    this.timeout = options.timeout ?? suite.timeout;
     this.started = undefined;
   }
 
+  get fullName() {
+    return this.suite.fullName ? `${this.suite.fullName} > ${this.name}` : this.name;
+  }
+
   async execute() {
-    this.started = Date.now();
-    let maybePromise;
-    try {
-      maybePromise = this.fn();
-    } catch (error) {
-      this.#recordFailure(error);
+    if (this.skip) {
+      globalRoot.result.skipped++;
+      recordOutcome({
+        name: this.fullName,
+        status: "skipped",
+        durationMs: 0,
+        location: this.location,
+      });
+      logLine(this.suite.depth + 1, "%s %s %s", grey("-"), this.name, grey("(skipped)"));
       return;
     }
 
-    if (typeof maybePromise?.then !== "function") {
-      // The test fn was synchronous, we are done.
-      this.#recordSuccess();
-      return;
+    this.started = Date.now();
+    const suites = this.suite.path;
+    let failed = false;
+    let error;
+
+    try {
+      for (const suite of suites) {
+        for (const hook of suite.hooks.beforeEach) {
+          await hook.execute();
+        }
+      }
+      await callWithTimeout(() => this.fn(), this.timeout);
+    } catch (err) {
+      failed = true;
+      error = err;
     }
 
-    // The test fn returned a promise, we need to wait for completion
-    return maybePromise.then(
-      () => this.#recordSuccess(),
-      (error) => this.#recordFailure(error),
-    );
-  }
+    for (const suite of suites.reverse()) {
+      for (const hook of suite.hooks.afterEach) {
+        try {
+          await hook.execute();
+        } catch (err) {
+          // Report the first error, it's usually the root cause of subsequent errors
+          if (!failed) {
+            failed = true;
+            error = err;
+          }
+        }
+      }
+    }
 
-  #log(passed) {
-    if (globalRoot.lastReportedTest?.location.fileName !== this.location.fileName) {
-      // TODO(bajtos) Ideally, we should report paths relative to the project root
-      console.log("\n%s", this.location.fileName.split(/[\\\/]+/g).slice(-1)[0]);
-      globalRoot.lastReportedTest = this;
+    if (failed) {
+      this.#recordFailure(error);
+    } else {
+      this.#recordSuccess();
     }
+  }
 
+  #log(passed, failure) {
     let duration = Date.now() - this.started;
-    console.log(
-      "  %s %s %s",
+    recordOutcome({
+      name: this.fullName,
+      status: passed ? "passed" : "failed",
+      durationMs: duration,
+      location: this.location,
+      error: failure?.reason,
+    });
+    logLine(
+      this.suite.depth + 1,
+      "%s %s %s",
       passed ? green("\u2714" /* check mark */) : red("\u2716" /* heavy multiplication */),
       this.name,
       grey(`(${duration}ms)`),
@@ -90,8 +234,9 @@ class TestCase {
   }
 
   #recordFailure(error) {
-    globalRoot.result.failed.push(new TestFailure({ testCase: this, error }));
-    this.#log(false);
+    const failure = new TestFailure({ name: this.fullName, location: this.location, error });
+    globalRoot.result.failed.push(failure);
+    this.#log(false, failure);
   }
 
   #recordSuccess() {
@@ -100,6 +245,59 @@ class TestCase {
   }
 }
 
+class TestHook {
+  /**
+   * @param {Object} args
+   * @param {"before" | "after" | "beforeEach" | "afterEach"} args.kind
+   * @param {() => void | () => Promise<void>} args.fn
+   * @param {TestSuite} args.suite
+   * @param {TestLocation} args.location
+   */
+  constructor({ kind, fn, suite, location }) {
+    this.kind = kind;
+    this.fn = fn;
+    this.suite = suite;
+    this.location = location;
+  }
+
+  get description() {
+    return this.suite.fullName
+      ? `"${this.kind}" hook in "${this.suite.fullName}"`
+      : `"${this.kind}" hook`;
+  }
+
+  execute() {
+    return callWithTimeout(() => this.fn(), this.suite.timeout);
+  }
+}
+
+class TimeoutError extends Error {
+  constructor(timeout) {
+    super(`Timed out after ${timeout}ms.`);
+    this.name = "TimeoutError";
+  }
+}
+
+/**
+ * @param {() => void | () => Promise<void>} fn
+ * @param {number} timeout
+ */
+async function callWithTimeout(fn, timeout) {
+  if (timeout === Infinity) {
+    return await fn();
+  }
+
+  let timer;
+  const timedOut = new Promise((_resolve, reject) => {
+    timer = setTimeout(() => reject(new TimeoutError(timeout)), timeout);
+  });
+  try {
+    return await Promise.race([fn(), timedOut]);
+  } finally {
+    clearTimeout(timer);
+  }
+}
+
 /**
  * @param {TestLocation} location
  * @returns {string}
@@ -111,36 +309,121 @@ function displayTestLocation({ fileName, lineNumber, columnNumber }) {
 class TestFailure {
   /**
    * @param {Object} args
-   * @param {TestCase} args.testCase
+   * @param {string} args.name
+   * @param {TestLocation} args.location
    * @param {Error} args.error
    */
-  constructor({ testCase, error }) {
-    this.testCase = testCase;
+  constructor({ name, location, error }) {
+    this.name = name;
+    this.location = location;
     this.error = error;
   }
 
+  get reason() {
+    // The stack trace of timeout errors points to the test runner internals, it's not helpful
+    return this.error instanceof TimeoutError
+      ? this.error.message
+      : String(format_test_error(this.error));
+  }
+
   render() {
-    let location = grey(`=> ${displayTestLocation(this.testCase.location)}`);
-    let reason = format_test_error(this.error);
-    return `${this.testCase.name} ${location}\n${boldRed("error:")} ${reason}`;
+    let location = grey(`=> ${displayTestLocation(this.location)}`);
+    return `${this.name} ${location}\n${boldRed("error:")} ${this.reason}`;
   }
 }
 
-function runNextTest() {
-  const nextTest = globalRoot.pendingTests.shift();
+/** @param {TestOutcome} outcome */
+function recordOutcome(outcome) {
+  globalRoot.result.tests.push(outcome);
+}
 
-  if (nextTest) {
-    nextTest.execute(nextTest).then(runNextTest);
-    return;
+/** @param {TestCase} test */
+function isSelected(test) {
+  if (globalRoot.onlyMode && !test.only && !test.suite.isInsideOnly()) return false;
+  return !globalRoot.filter || globalRoot.filter(test.fullName);
+}
+
+/** Print a line of the test report, indented by the given nesting level. */
+function logLine(depth, format, ...args) {
+  console.log("  ".repeat(depth) + format, ...args);
+}
+
+async function runAllTests() {
+  globalRoot.running = true;
+  globalRoot.onlyMode = globalRoot.rootSuite.containsOnly();
+  globalRoot.filter = buildTestFilter();
+
+  if (globalRoot.rootSuite.selectedTests().length) {
+    // TODO(bajtos) Ideally, we should report paths relative to the project root
+    console.log("\n%s", globalRoot.fileName.split(/[\\\/]+/g).slice(-1)[0]);
   }
 
+  await runSuite(globalRoot.rootSuite);
   reportTestResults();
 }
 
+/** @param {TestSuite} suite */
+async function runSuite(suite) {
+  const tests = suite.selectedTests();
+  if (!tests.length) return;
+
+  if (suite.name !== undefined) {
+    logLine(suite.depth, "%s", suite.name);
+  }
+
+  // Don't run `before` and `after` hooks when all tests in the suite are skipped
+  const runHooks = tests.some((t) => !t.skip);
+
+  if (runHooks && !(await runHooksOnce(suite.hooks.before))) {
+    // The suite was not set up correctly, running its tests would produce confusing errors
+    await runHooksOnce(suite.hooks.after);
+    return;
+  }
+
+  for (const child of suite.children) {
+    if (child instanceof TestSuite) {
+      await runSuite(child);
+    } else if (isSelected(child)) {
+      await child.execute();
+    }
+  }
+
+  if (runHooks) {
+    await runHooksOnce(suite.hooks.after);
+  }
+}
+
+/**
+ * Run `before` or `after` hooks, record failures. Returns `true` when all hooks passed.
+ *
+ * @param {TestHook[]} hooks
+ */
+async function runHooksOnce(hooks) {
+  for (const hook of hooks) {
+    try {
+      await hook.execute();
+    } catch (error) {
+      const failure = new TestFailure({ name: hook.description, location: hook.location, error });
+      globalRoot.result.failed.push(failure);
+      recordOutcome({
+        name: hook.description,
+        status: "failed",
+        durationMs: 0,
+        location: hook.location,
+        error: failure.reason,
+      });
+      logLine(hook.suite.depth + 1, "%s %s", red("\u2716"), hook.description);
+      return false;
+    }
+  }
+  return true;
+}
+
 function reportTestResults() {
   let duration = Date.now() - globalRoot.started;
   let failed = globalRoot.result.failed.length;
   let passed = globalRoot.result.passed;
+  let skipped = globalRoot.result.skipped;
 
   if (failed) {
     // extra spaces are intentional to show red background
@@ -151,38 +434,145 @@ function reportTestResults() {
   }
 
   console.log(
-    "\n%s | %s passed | %s failed %s%s",
+    "\n%s | %s passed | %s failed%s %s\n",
     failed ? red("FAIL") : green("ok"),
     passed,
     failed,
+    skipped ? ` | ${skipped} skipped` : "",
     grey(`(${duration}ms)`),
-    // Add another empty line when all tests passed, for consistency with the failure output
-    failed ? "" : "\n",
   );
-  // Signal test failure by creating an unhandled error,
-  // so that `zinnia` CLI returns a non-zero exit code.
-  // TODO(bajtos) Find a more elegant solution, e.g. add an API for setting the exit code
-  if (failed) {
-    setTimeout(() => {
-      const err = new Error();
-      // Hack: After the error message, send ASCII sequence to clear the line and
-      // move the cursor to column 0. This hides the error message when running in TTY terminals
-      // and leaves only the test summary followed by an empty line.
-      err.name = `[some tests failed]\u001b[2K\x0D`;
-      err.message = undefined;
-      err.stack = undefined;
-      throw err;
-    });
-  }
+
+  // Let the host (e.g. `zinnia` CLI) know about the results, so that it can report
+  // the failure via the exit code.
+  DenoCore.ops.op_report_test_results({
+    passed,
+    failed,
+    skipped,
+    durationMs: duration,
+    tests: globalRoot.result.tests,
+  });
 }
 
-export function test(name, fn) {
+/**
+ * Find the location of the user code calling our public API function.
+ * Must be called directly from the API function.
+ *
+ * @returns {TestLocation}
+ */
+function callerLocation() {
+  // frames[0] is this function, frames[1] is the API function
+  return DenoCore.destructureError(new Error()).frames[2];
+}
+
+/**
+ * Parse arguments of `test(name, [options], fn)` and `describe(name, [options], fn)`.
+ *
+ * @returns {{ name: string, options: TestOptions, fn: Function }}
+ */
+function parseArgs(name, optionsOrFn, maybeFn) {
+  let options = {};
+  let fn = optionsOrFn;
+  if (typeof optionsOrFn === "object" && optionsOrFn !== null) {
+    options = optionsOrFn;
+    fn = maybeFn;
+  }
+
   if (typeof name !== "string") throw new TypeError(`"name" must be a string, was: ${typeof name}`);
   if (typeof fn !== "function") throw new TypeError(`"fn" must be a function, was: ${typeof fn}`);
 
-  let location = DenoCore.destructureError(new Error()).frames[1];
+  const { timeout } = options;
+  if (timeout !== undefined && !(typeof timeout === "number" && timeout > 0)) {
+    throw new TypeError(`"timeout" must be a positive number or Infinity, was: ${timeout}`);
+  }
+
+  return { name, options, fn };
+}
+
+function registerTest(location, args, overrides = {}) {
+  const { name, options, fn } = parseArgs(...args);
+  const root = getGlobalRoot();
+  root.fileName ??= location.fileName;
+  const suite = root.currentSuite;
+  suite.children.push(
+    new TestCase({ name, fn, options: { ...options, ...overrides }, suite, location }),
+  );
+}
+
+function registerSuite(location, args, overrides = {}) {
+  const { name, options, fn } = parseArgs(...args);
+  const root = getGlobalRoot();
+  root.fileName ??= location.fileName;
+  const parent = root.currentSuite;
+  const suite = new TestSuite({ name, options: { ...options, ...overrides }, parent });
+  parent.children.push(suite);
+
+  // Tests, suites and hooks registered by `fn` belong to the new suite
+  root.currentSuite = suite;
+  try {
+    const maybePromise = fn();
+    if (typeof maybePromise?.then === "function") {
+      throw new TypeError(`The callback of describe("${name}") must be synchronous.`);
+    }
+  } finally {
+    root.currentSuite = parent;
+  }
+}
+
+function registerHook(kind, location, fn) {
+  if (typeof fn !== "function") throw new TypeError(`"fn" must be a function, was: ${typeof fn}`);
+  const suite = getGlobalRoot().currentSuite;
+  suite.hooks[kind].push(new TestHook({ kind, fn, suite, location }));
+}
+
+/**
+ * Register a test: `test(name, fn)` or `test(name, { skip, only, timeout }, fn)`.
+ */
+export function test(name, optionsOrFn, fn) {
+  registerTest(callerLocation(), [name, optionsOrFn, fn]);
+}
+
+test.skip = function skip(name, optionsOrFn, fn) {
+  registerTest(callerLocation(), [name, optionsOrFn, fn], { skip: true });
+};
+
+test.only = function only(name, optionsOrFn, fn) {
+  registerTest(callerLocation(), [name, optionsOrFn, fn], { only: true });
+};
+
+/**
+ * Group tests: `describe(name, fn)` or `describe(name, { skip, only, timeout }, fn)`.
+ * The function `fn` is called immediately to register tests and hooks of the group.
+ */
+export function describe(name, optionsOrFn, fn) {
+  registerSuite(callerLocation(), [name, optionsOrFn, fn]);
+}
+
+describe.skip = function skip(name, optionsOrFn, fn) {
+  registerSuite(callerLocation(), [name, optionsOrFn, fn], { skip: true });
+};
+
+describe.only = function only(name, optionsOrFn, fn) {
+  registerSuite(callerLocation(), [name, optionsOrFn, fn], { only: true });
+};
+
+/** Run `fn` once before all tests in the current suite. */
+export function before(fn) {
+  registerHook("before", callerLocation(), fn);
+}
+
+/** Run `fn` once after all tests in the current suite. */
+export function after(fn) {
+  registerHook("after", callerLocation(), fn);
+}
+
+/** Run `fn` before each test in the current suite, including tests in nested suites. */
+export function beforeEach(fn) {
+  registerHook("beforeEach", callerLocation(), fn);
+}
 
-  getGlobalRoot().pendingTests.push(new TestCase({ name, fn, location }));
+/** Run `fn` after each test in the current suite, including tests in nested suites. */
+export function afterEach(fn) {
+  registerHook("afterEach", callerLocation(), fn);
 }
 
 //
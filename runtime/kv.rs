@@ -0,0 +1,363 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use deno_core::anyhow::{anyhow, Context, Result};
+use deno_core::{op2, OpState};
+use redb::{Database, ReadableTable, TableDefinition, TableError};
+use serde::Deserialize;
+
+/// Keys and values stored by the module.
+const DATA_TABLE: TableDefinition<&str, &str> = TableDefinition::new("data");
+
+/// Bookkeeping like the total size of the stored data.
+const META_TABLE: TableDefinition<&str, u64> = TableDefinition::new("meta");
+const USAGE_KEY: &str = "usage";
+
+/// The default quota of `Zinnia.kv` stores: 10 MiB.
+pub const DEFAULT_KV_QUOTA: u64 = 10 * 1024 * 1024;
+
+/// The maximum length of a key in bytes.
+const MAX_KEY_LENGTH: usize = 2048;
+
+/// Configuration of the persistent key-value store exposed via `Zinnia.kv`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvStoreConfig {
+    /// Directory where to keep the store, e.g. `{state_root}/{module}` in zinniad.
+    pub dir: PathBuf,
+
+    /// The maximum total size of all keys and values in bytes, see `DEFAULT_KV_QUOTA`.
+    pub quota: u64,
+}
+
+/// A change to apply as part of an atomic commit.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum KvMutation {
+    /// Store the value (JSON-encoded) under the key.
+    Set { key: String, value: String },
+    /// Remove the key from the store.
+    Delete { key: String },
+}
+
+impl KvMutation {
+    fn key(&self) -> &str {
+        match self {
+            KvMutation::Set { key, .. } | KvMutation::Delete { key } => key,
+        }
+    }
+}
+
+/// The key-value store backed by an embedded database file.
+pub struct KvStore {
+    db: Database,
+    quota: u64,
+}
+
+impl KvStore {
+    pub fn open(config: &KvStoreConfig) -> Result<Self> {
+        std::fs::create_dir_all(&config.dir).with_context(|| {
+            format!(
+                "Cannot create the KV store directory {}",
+                config.dir.display()
+            )
+        })?;
+        let path = config.dir.join("kv.redb");
+        let db = Database::create(&path)
+            .with_context(|| format!("Cannot open the KV store {}", path.display()))?;
+        Ok(Self {
+            db,
+            quota: config.quota,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(DATA_TABLE) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let value = table.get(key)?.map(|v| v.value().to_string());
+        Ok(value)
+    }
+
+    /// List entries with keys starting with `prefix`, sorted by key.
+    pub fn list(&self, prefix: &str, limit: Option<usize>) -> Result<Vec<(String, String)>> {
+        let txn = self.db.begin_read()?;
+        let table = match txn.open_table(DATA_TABLE) {
+            Ok(table) => table,
+            Err(TableError::TableDoesNotExist(_)) => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut entries = Vec::new();
+        for entry in table.range(prefix..)? {
+            if limit.is_some_and(|limit| entries.len() >= limit) {
+                break;
+            }
+            let (key, value) = entry?;
+            if !key.value().starts_with(prefix) {
+                break;
+            }
+            entries.push((key.value().to_string(), value.value().to_string()));
+        }
+        Ok(entries)
+    }
+
+    /// Apply all mutations in a single transaction. Either all mutations are applied, or none.
+    pub fn commit(&self, mutations: &[KvMutation]) -> Result<()> {
+        for mutation in mutations {
+            validate_key(mutation.key())?;
+        }
+
+        let txn = self.db.begin_write()?;
+        {
+            let mut data = txn.open_table(DATA_TABLE)?;
+            let mut meta = txn.open_table(META_TABLE)?;
+            let initial_usage = meta.get(USAGE_KEY)?.map(|v| v.value()).unwrap_or_default();
+            let mut usage = initial_usage;
+
+            for mutation in mutations {
+                let key = mutation.key();
+                let old_size = data.get(key)?.map(|old| entry_size(key, old.value()));
+                usage -= old_size.unwrap_or_default();
+                match mutation {
+                    KvMutation::Set { value, .. } => {
+                        data.insert(key, value.as_str())?;
+                        usage += entry_size(key, value);
+                    }
+                    KvMutation::Delete { .. } => {
+                        data.remove(key)?;
+                    }
+                }
+            }
+
+            // Commits that don't increase the usage are always allowed, so that modules can free
+            // up space after the operator lowered the quota below the current usage.
+            if usage > self.quota && usage > initial_usage {
+                // Dropping the transaction without committing discards all changes
+                return Err(anyhow!(
+                    "Zinnia.kv quota exceeded: the store would use {usage} bytes, \
+                     the quota is {} bytes.",
+                    self.quota
+                ));
+            }
+            meta.insert(USAGE_KEY, usage)?;
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// The total size of all keys and values in bytes.
+    #[cfg(test)]
+    pub fn usage(&self) -> Result<u64> {
+        let txn = self.db.begin_read()?;
+        let usage = match txn.open_table(META_TABLE) {
+            Ok(table) => table.get(USAGE_KEY)?.map(|v| v.value()),
+            Err(TableError::TableDoesNotExist(_)) => None,
+            Err(err) => return Err(err.into()),
+        };
+        Ok(usage.unwrap_or_default())
+    }
+}
+
+fn entry_size(key: &str, value: &str) -> u64 {
+    (key.len() + value.len()) as u64
+}
+
+fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() {
+        return Err(anyhow!("Zinnia.kv keys must not be empty."));
+    }
+    if key.len() > MAX_KEY_LENGTH {
+        return Err(anyhow!(
+            "Zinnia.kv keys must not be longer than {MAX_KEY_LENGTH} bytes."
+        ));
+    }
+    Ok(())
+}
+
+/// The store of the current module, opened on the first use.
+pub struct KvState {
+    pub config: Option<KvStoreConfig>,
+    pub store: Option<Arc<KvStore>>,
+}
+
+fn kv_store(state: &Rc<RefCell<OpState>>) -> Result<Arc<KvStore>> {
+    let mut state = state.borrow_mut();
+    let kv = state.borrow_mut::<KvState>();
+    if let Some(store) = &kv.store {
+        return Ok(Arc::clone(store));
+    }
+    let config = kv
+        .config
+        .as_ref()
+        .ok_or_else(|| anyhow!("Zinnia.kv is not available in this runtime."))?;
+    let store = Arc::new(KvStore::open(config)?);
+    kv.store = Some(Arc::clone(&store));
+    Ok(store)
+}
+
+// The database operations block the thread, e.g. when committing changes to the disk,
+// therefore we run them outside of the event loop.
+
+#[op2(async)]
+#[string]
+pub async fn op_kv_get(
+    state: Rc<RefCell<OpState>>,
+    #[string] key: String,
+) -> Result<Option<String>> {
+    let store = kv_store(&state)?;
+    tokio::task::spawn_blocking(move || store.get(&key)).await?
+}
+
+#[op2(async)]
+#[serde]
+pub async fn op_kv_list(
+    state: Rc<RefCell<OpState>>,
+    #[string] prefix: String,
+    #[serde] limit: Option<usize>,
+) -> Result<Vec<(String, String)>> {
+    let store = kv_store(&state)?;
+    tokio::task::spawn_blocking(move || store.list(&prefix, limit)).await?
+}
+
+#[op2(async)]
+pub async fn op_kv_commit(
+    state: Rc<RefCell<OpState>>,
+    #[serde] mutations: Vec<KvMutation>,
+) -> Result<()> {
+    let store = kv_store(&state)?;
+    tokio::task::spawn_blocking(move || store.commit(&mutations)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn open_store(quota: u64) -> (assert_fs::TempDir, KvStore) {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let store = KvStore::open(&KvStoreConfig {
+            dir: temp.path().join("module"),
+            quota,
+        })
+        .unwrap();
+        (temp, store)
+    }
+
+    fn set(key: &str, value: &str) -> KvMutation {
+        KvMutation::Set {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn sets_gets_and_deletes_values() {
+        let (_temp, store) = open_store(DEFAULT_KV_QUOTA);
+        assert_eq!(store.get("answer").unwrap(), None);
+
+        store.commit(&[set("answer", "42")]).unwrap();
+        assert_eq!(store.get("answer").unwrap(), Some("42".to_string()));
+        assert_eq!(store.usage().unwrap(), 8);
+
+        store
+            .commit(&[KvMutation::Delete {
+                key: "answer".to_string(),
+            }])
+            .unwrap();
+        assert_eq!(store.get("answer").unwrap(), None);
+        assert_eq!(store.usage().unwrap(), 0);
+    }
+
+    #[test]
+    fn lists_entries_by_prefix() {
+        let (_temp, store) = open_store(DEFAULT_KV_QUOTA);
+        store
+            .commit(&[
+                set("job:2", "\"b\""),
+                set("job:1", "\"a\""),
+                set("jobs", "2"),
+                set("other", "null"),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            store.list("job:", None).unwrap(),
+            vec![
+                ("job:1".to_string(), "\"a\"".to_string()),
+                ("job:2".to_string(), "\"b\"".to_string()),
+            ]
+        );
+        assert_eq!(store.list("", Some(1)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_commits_exceeding_quota() {
+        let (_temp, store) = open_store(10);
+        store.commit(&[set("a", "1")]).unwrap();
+
+        let err = store
+            .commit(&[set("b", "2"), set("c", "\"too long\"")])
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("quota exceeded"),
+            "unexpected error: {err}"
+        );
+
+        // None of the mutations were applied
+        assert_eq!(store.get("b").unwrap(), None);
+        assert_eq!(store.usage().unwrap(), 2);
+    }
+
+    #[test]
+    fn allows_deleting_entries_when_over_quota() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let mut config = KvStoreConfig {
+            dir: temp.path().to_path_buf(),
+            quota: DEFAULT_KV_QUOTA,
+        };
+        KvStore::open(&config)
+            .unwrap()
+            .commit(&[set("a", "1"), set("b", "2"), set("c", "3")])
+            .unwrap();
+
+        // The operator lowered the quota below the current usage
+        config.quota = 2;
+        let store = KvStore::open(&config).unwrap();
+        let delete = |key: &str| KvMutation::Delete {
+            key: key.to_string(),
+        };
+
+        store.commit(&[delete("a")]).unwrap();
+        assert_eq!(store.usage().unwrap(), 4);
+        store.commit(&[set("b", "0")]).unwrap();
+        assert_eq!(store.get("b").unwrap(), Some("0".to_string()));
+
+        let err = store.commit(&[set("b", "22")]).unwrap_err();
+        assert!(
+            err.to_string().contains("quota exceeded"),
+            "unexpected error: {err}"
+        );
+        assert_eq!(store.usage().unwrap(), 4);
+    }
+
+    #[test]
+    fn persists_data_across_restarts() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config = KvStoreConfig {
+            dir: temp.path().to_path_buf(),
+            quota: DEFAULT_KV_QUOTA,
+        };
+        KvStore::open(&config)
+            .unwrap()
+            .commit(&[set("answer", "42")])
+            .unwrap();
+
+        let store = KvStore::open(&config).unwrap();
+        assert_eq!(store.get("answer").unwrap(), Some("42".to_string()));
+    }
+}
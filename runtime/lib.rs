@@ -3,8 +3,35 @@ pub use deno_core;
 pub mod runtime;
 pub use runtime::*;
 
+mod bundler;
+
+mod dns_resolver;
+pub use bundler::{bundle_module, BundleOptions};
+
+mod kv;
+pub use kv::{KvStoreConfig, DEFAULT_KV_QUOTA};
+
 mod module_loader;
-pub use module_loader::get_module_root;
+pub use module_loader::{get_module_root, read_import_map, LoadedFiles};
+
+mod metrics;
+pub use metrics::{Metrics, ModuleMetrics};
+
+mod manifest;
+pub use manifest::*;
+
+mod permissions;
+pub use permissions::{NetDestination, NetPermissions, NetRule};
+
+mod testing;
+pub use testing::*;
+
+mod remote_modules;
+pub use remote_modules::RemoteModulesConfig;
+
+mod transpiler;
+
+mod watchdog;
 
 mod vendored;
 pub use vendored::colors;
@@ -12,6 +39,7 @@ pub use vendored::fmt_errors;
 
 pub use deno_core::anyhow;
 pub use deno_core::resolve_path;
+pub use import_map;
 
 mod console_reporter;
 mod reporter;
@@ -19,5 +47,6 @@ pub use console_reporter::*;
 pub use reporter::*;
 
 pub use lassie;
+pub use zinnia_libp2p;
 
 mod ext;
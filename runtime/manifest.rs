@@ -0,0 +1,191 @@
+use std::path::{Path, PathBuf};
+
+use deno_core::anyhow::{anyhow, Context, Result};
+use deno_core::serde_json;
+use serde::Deserialize;
+use {once_cell::sync::Lazy, regex::Regex};
+
+use crate::permissions::NetRule;
+
+/// The name of the manifest file describing a Zinnia module.
+pub const MANIFEST_FILE_NAME: &str = "zinnia.json";
+
+/// Module manifest (`zinnia.json`) stored in the root directory of a Zinnia module.
+///
+/// Example:
+///
+/// ```json
+/// {
+///   "name": "saturn-l2",
+///   "version": "1.2.3",
+///   "main": "dist/index.js",
+///   "importMap": "import_map.json",
+///   "permissions": { "net": ["*.example.com:443"] }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModuleManifest {
+    /// Unique name of the module, it must not change across module versions.
+    pub name: String,
+
+    /// Version of the module, reported together with the name.
+    pub version: Option<String>,
+
+    /// The entry point, relative to the module directory. Defaults to `main.js`.
+    #[serde(default = "default_main")]
+    pub main: String,
+
+    /// Import map used to resolve bare specifiers, relative to the module directory.
+    #[serde(rename = "importMap")]
+    pub import_map: Option<String>,
+
+    /// Permissions requested by the module.
+    #[serde(default)]
+    pub permissions: ManifestPermissions,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestPermissions {
+    /// Network destinations the module needs to access, e.g. `example.com` or `1.2.3.4:443`.
+    /// See `NetRule` for the supported formats. When not empty, the module cannot access any
+    /// other destinations.
+    #[serde(default)]
+    pub net: Vec<String>,
+}
+
+impl ManifestPermissions {
+    pub fn net_rules(&self) -> Result<Vec<NetRule>> {
+        self.net
+            .iter()
+            .map(|rule| {
+                rule.parse()
+                    .with_context(|| format!("Invalid network permission {rule:?}"))
+            })
+            .collect()
+    }
+}
+
+fn default_main() -> String {
+    String::from("main.js")
+}
+
+impl ModuleManifest {
+    /// Load the manifest from `zinnia.json` in the given module directory.
+    pub fn load(module_dir: &Path) -> Result<Self> {
+        let manifest_file = module_dir.join(MANIFEST_FILE_NAME);
+        let data = std::fs::read_to_string(&manifest_file)
+            .with_context(|| format!("Cannot read module manifest {}", manifest_file.display()))?;
+        Self::parse(&data)
+            .with_context(|| format!("Invalid module manifest {}", manifest_file.display()))
+    }
+
+    pub fn parse(data: &str) -> Result<Self> {
+        let manifest = serde_json::from_str::<Self>(data)?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// The path of the entry point inside the module directory.
+    pub fn main_path(&self, module_dir: &Path) -> PathBuf {
+        module_dir.join(&self.main)
+    }
+
+    /// The path of the import map inside the module directory, if the module has one.
+    pub fn import_map_path(&self, module_dir: &Path) -> Option<PathBuf> {
+        self.import_map.as_ref().map(|p| module_dir.join(p))
+    }
+
+    fn validate(&self) -> Result<()> {
+        static NAME_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9._-]*$").unwrap());
+
+        if !NAME_RE.is_match(&self.name) {
+            return Err(anyhow!(
+                "Invalid module name {:?}. The name must start with a letter or a digit \
+                 and contain only letters, digits, '.', '_' and '-'.",
+                self.name
+            ));
+        }
+
+        if let Some(version) = &self.version {
+            if version.is_empty() || version.contains(char::is_whitespace) {
+                return Err(anyhow!("Invalid module version {version:?}."));
+            }
+        }
+
+        self.permissions.net_rules()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_full_manifest() {
+        let manifest = ModuleManifest::parse(
+            r#"{
+                "name": "saturn-l2",
+                "version": "1.2.3",
+                "main": "dist/index.js",
+                "importMap": "import_map.json",
+                "permissions": { "net": ["example.com"] }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest,
+            ModuleManifest {
+                name: "saturn-l2".into(),
+                version: Some("1.2.3".into()),
+                main: "dist/index.js".into(),
+                import_map: Some("import_map.json".into()),
+                permissions: ManifestPermissions {
+                    net: vec!["example.com".into()],
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn applies_defaults() {
+        let manifest = ModuleManifest::parse(r#"{ "name": "ping" }"#).unwrap();
+        assert_eq!(manifest.version, None, "version");
+        assert_eq!(manifest.main, "main.js", "main");
+        assert_eq!(manifest.import_map, None, "importMap");
+        assert_eq!(manifest.permissions, ManifestPermissions::default());
+    }
+
+    #[test]
+    fn rejects_invalid_name() {
+        let err = ModuleManifest::parse(r#"{ "name": "../etc" }"#).unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid module name"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_net_permissions() {
+        let err = ModuleManifest::parse(
+            r#"{ "name": "ping", "permissions": { "net": ["example.com:http"] } }"#,
+        )
+        .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("Invalid network permission"),
+            "unexpected error: {err:#}"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let result = ModuleManifest::parse(r#"{ "name": "ping", "entry": "index.js" }"#);
+        assert!(result.is_err(), "unknown field should be rejected");
+    }
+}
@@ -0,0 +1,349 @@
+use std::fmt::Debug;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use deno_core::v8;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::{Registry, Unit};
+use zinnia_libp2p::PeerNodeMetrics;
+
+use crate::{Activity, ActivityLevel, LogLevel, Reporter};
+
+/// How often to sample the V8 heap usage and the event loop lag.
+pub(crate) const METRICS_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ModuleLabels {
+    module: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ActivityLabels {
+    module: String,
+    level: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct StatusLabels {
+    module: String,
+    /// The HTTP status code, or `error` when the request failed without a response.
+    status: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct OutcomeLabels {
+    module: String,
+    outcome: &'static str,
+}
+
+/// Metrics of all modules running in this process, see `Metrics::encode()`.
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    jobs_completed: Family<ModuleLabels, Counter>,
+    activities: Family<ActivityLabels, Counter>,
+    fetch_requests: Family<StatusLabels, Counter>,
+    lassie_retrievals: Family<StatusLabels, Counter>,
+    libp2p_dials: Family<OutcomeLabels, Counter>,
+    libp2p_requests: Family<OutcomeLabels, Counter>,
+    heap_used: Family<ModuleLabels, Gauge>,
+    heap_total: Family<ModuleLabels, Gauge>,
+    event_loop_lag: Family<ModuleLabels, Histogram, fn() -> Histogram>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("zinnia");
+
+        let jobs_completed = Family::default();
+        registry.register(
+            "jobs_completed",
+            "Number of jobs completed by the module",
+            Family::clone(&jobs_completed),
+        );
+        let activities = Family::default();
+        registry.register(
+            "activities",
+            "Number of activities reported by the module, by level",
+            Family::clone(&activities),
+        );
+        let fetch_requests = Family::default();
+        registry.register(
+            "fetch_requests",
+            "Number of `fetch()` requests made by the module, by response status",
+            Family::clone(&fetch_requests),
+        );
+        let lassie_retrievals = Family::default();
+        registry.register(
+            "lassie_retrievals",
+            "Number of IPFS retrievals made by the module via Lassie, by response status",
+            Family::clone(&lassie_retrievals),
+        );
+        let libp2p_dials = Family::default();
+        registry.register(
+            "libp2p_dials",
+            "Number of libp2p peers dialed by the module, by outcome",
+            Family::clone(&libp2p_dials),
+        );
+        let libp2p_requests = Family::default();
+        registry.register(
+            "libp2p_requests",
+            "Number of libp2p requests made by the module, by outcome",
+            Family::clone(&libp2p_requests),
+        );
+        let heap_used = Family::default();
+        registry.register_with_unit(
+            "heap_used",
+            "The V8 heap size used by the module",
+            Unit::Bytes,
+            Family::clone(&heap_used),
+        );
+        let heap_total = Family::default();
+        registry.register_with_unit(
+            "heap_total",
+            "The V8 heap size allocated by the module",
+            Unit::Bytes,
+            Family::clone(&heap_total),
+        );
+        let event_loop_lag: Family<ModuleLabels, Histogram, fn() -> Histogram> =
+            Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.001, 4.0, 8)));
+        registry.register_with_unit(
+            "event_loop_lag",
+            "How late the event loop of the module processed a timer",
+            Unit::Seconds,
+            Family::clone(&event_loop_lag),
+        );
+
+        Self {
+            registry,
+            jobs_completed,
+            activities,
+            fetch_requests,
+            lassie_retrievals,
+            libp2p_dials,
+            libp2p_requests,
+            heap_used,
+            heap_total,
+            event_loop_lag,
+        }
+    }
+
+    /// Encode all metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut output = String::new();
+        prometheus_client::encoding::text::encode(&mut output, &self.registry)
+            .expect("writing to a String cannot fail");
+        output
+    }
+
+    /// Create a handle for recording metrics of the given module.
+    pub fn module(self: &Arc<Self>, module_name: &str) -> ModuleMetrics {
+        ModuleMetrics {
+            metrics: Arc::clone(self),
+            module: module_name.to_string(),
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Records metrics of a single module, all values are labelled with the module name.
+#[derive(Clone)]
+pub struct ModuleMetrics {
+    metrics: Arc<Metrics>,
+    module: String,
+}
+
+impl Debug for ModuleMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModuleMetrics")
+            .field("module", &self.module)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ModuleMetrics {
+    fn module_labels(&self) -> ModuleLabels {
+        ModuleLabels {
+            module: self.module.clone(),
+        }
+    }
+
+    fn status_labels(&self, status: Option<u16>) -> StatusLabels {
+        StatusLabels {
+            module: self.module.clone(),
+            status: match status {
+                Some(status) => status.to_string(),
+                None => String::from("error"),
+            },
+        }
+    }
+
+    fn outcome_labels(&self, succeeded: bool) -> OutcomeLabels {
+        OutcomeLabels {
+            module: self.module.clone(),
+            outcome: if succeeded { "success" } else { "failure" },
+        }
+    }
+
+    pub fn job_completed(&self) {
+        let labels = self.module_labels();
+        self.metrics.jobs_completed.get_or_create(&labels).inc();
+    }
+
+    pub fn activity(&self, level: ActivityLevel) {
+        let labels = ActivityLabels {
+            module: self.module.clone(),
+            level: level.to_string(),
+        };
+        self.metrics.activities.get_or_create(&labels).inc();
+    }
+
+    /// Record a completed `fetch()` request. The `status` is not set when the request failed
+    /// without a response, e.g. because of a network error.
+    pub fn fetch_request(&self, status: Option<u16>) {
+        let labels = self.status_labels(status);
+        self.metrics.fetch_requests.get_or_create(&labels).inc();
+    }
+
+    /// Record a completed `fetch("ipfs://...")` request handled by Lassie.
+    pub fn lassie_retrieval(&self, status: Option<u16>) {
+        let labels = self.status_labels(status);
+        self.metrics.lassie_retrievals.get_or_create(&labels).inc();
+    }
+
+    pub fn heap_statistics(&self, isolate: &mut v8::Isolate) {
+        let mut stats = v8::HeapStatistics::default();
+        isolate.get_heap_statistics(&mut stats);
+        let labels = self.module_labels();
+        self.metrics
+            .heap_used
+            .get_or_create(&labels)
+            .set(stats.used_heap_size() as i64);
+        self.metrics
+            .heap_total
+            .get_or_create(&labels)
+            .set(stats.total_heap_size() as i64);
+    }
+
+    pub fn event_loop_lag(&self, lag: Duration) {
+        let labels = self.module_labels();
+        self.metrics
+            .event_loop_lag
+            .get_or_create(&labels)
+            .observe(lag.as_secs_f64());
+    }
+}
+
+impl PeerNodeMetrics for ModuleMetrics {
+    fn record_dial(&self, succeeded: bool) {
+        let labels = self.outcome_labels(succeeded);
+        self.metrics.libp2p_dials.get_or_create(&labels).inc();
+    }
+
+    fn record_request(&self, succeeded: bool) {
+        let labels = self.outcome_labels(succeeded);
+        self.metrics.libp2p_requests.get_or_create(&labels).inc();
+    }
+}
+
+/// Reporter counting jobs and activities before passing them to the wrapped reporter.
+pub(crate) struct MetricsReporter {
+    inner: Rc<dyn Reporter>,
+    metrics: ModuleMetrics,
+}
+
+impl MetricsReporter {
+    pub fn new(inner: Rc<dyn Reporter>, metrics: ModuleMetrics) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+impl Reporter for MetricsReporter {
+    fn log(&self, level: LogLevel, msg: &str) {
+        self.inner.log(level, msg);
+    }
+
+    fn activity(&self, activity: &Activity) {
+        self.metrics.activity(activity.level);
+        self.inner.activity(activity);
+    }
+
+    fn job_completed(&self) {
+        self.metrics.job_completed();
+        self.inner.job_completed();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecordingReporter;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn encodes_metrics_of_all_modules() {
+        let metrics = Arc::new(Metrics::new());
+        let saturn = metrics.module("saturn");
+        let checker = metrics.module("checker");
+
+        saturn.job_completed();
+        saturn.job_completed();
+        checker.fetch_request(Some(200));
+        checker.fetch_request(None);
+        checker.lassie_retrieval(Some(504));
+        saturn.record_dial(false);
+        saturn.record_request(true);
+        checker.event_loop_lag(Duration::from_millis(20));
+
+        let output = metrics.encode();
+        for line in [
+            "zinnia_jobs_completed_total{module=\"saturn\"} 2",
+            "zinnia_fetch_requests_total{module=\"checker\",status=\"200\"} 1",
+            "zinnia_fetch_requests_total{module=\"checker\",status=\"error\"} 1",
+            "zinnia_lassie_retrievals_total{module=\"checker\",status=\"504\"} 1",
+            "zinnia_libp2p_dials_total{module=\"saturn\",outcome=\"failure\"} 1",
+            "zinnia_libp2p_requests_total{module=\"saturn\",outcome=\"success\"} 1",
+            "zinnia_event_loop_lag_seconds_count{module=\"checker\"} 1",
+        ] {
+            assert!(output.contains(line), "missing {line:?} in:\n{output}");
+        }
+        assert!(
+            !output.contains("zinnia_jobs_completed_total{module=\"checker\"}"),
+            "unexpected jobs of the checker module:\n{output}"
+        );
+    }
+
+    #[test]
+    fn counts_reported_jobs_and_activities() {
+        let metrics = Arc::new(Metrics::new());
+        let recording = Rc::new(RecordingReporter::new());
+        let reporter = MetricsReporter::new(recording.clone(), metrics.module("saturn"));
+
+        reporter.job_completed();
+        reporter.info_activity("started");
+        reporter.error_activity("failed");
+        reporter.error_activity("failed again");
+        reporter.activity(&Activity::new(ActivityLevel::Warn, "slow"));
+
+        let output = metrics.encode();
+        for line in [
+            "zinnia_jobs_completed_total{module=\"saturn\"} 1",
+            "zinnia_activities_total{module=\"saturn\",level=\"info\"} 1",
+            "zinnia_activities_total{module=\"saturn\",level=\"error\"} 2",
+            "zinnia_activities_total{module=\"saturn\",level=\"warn\"} 1",
+        ] {
+            assert!(output.contains(line), "missing {line:?} in:\n{output}");
+        }
+        assert_eq!(recording.events.borrow().len(), 5);
+    }
+}
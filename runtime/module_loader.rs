@@ -1,90 +1,104 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use deno_core::anyhow::anyhow;
+use deno_core::anyhow::{anyhow, Context};
 use deno_core::error::type_error;
+use deno_core::futures::future::LocalBoxFuture;
 use deno_core::futures::FutureExt;
 use deno_core::{
-    resolve_import, ModuleLoader, ModuleSource, ModuleSourceFuture, ModuleSpecifier, ModuleType,
+    resolve_import, resolve_path, ModuleLoader, ModuleSource, ModuleSourceFuture, ModuleSpecifier,
     ResolutionKind,
 };
 
+use import_map::ImportMap;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 
+use crate::remote_modules::RemoteModules;
+use crate::transpiler::{prepare_module_source, ModuleSources};
+
 pub type AnyError = deno_core::anyhow::Error;
 use deno_core::anyhow::Result;
 
 /// Our custom module loader.
 pub struct ZinniaModuleLoader {
     module_root: Option<PathBuf>,
+    import_map: Option<ImportMap>,
+    remote_modules: Option<Rc<RemoteModules>>,
+    sources: ModuleSources,
+    loaded_files: Option<LoadedFiles>,
 }
 
 impl ZinniaModuleLoader {
-    pub fn build(module_root: Option<PathBuf>) -> Result<Self> {
+    pub fn build(
+        module_root: Option<PathBuf>,
+        import_map: Option<ImportMap>,
+        remote_modules: Option<RemoteModules>,
+    ) -> Result<Self> {
         let module_root = match module_root {
             None => None,
             // We must canonicalize the module root path too. It's best to do it once at startup.
             Some(r) => Some(r.canonicalize()?),
         };
 
-        Ok(Self { module_root })
+        Ok(Self {
+            module_root,
+            import_map,
+            remote_modules: remote_modules.map(Rc::new),
+            sources: ModuleSources::default(),
+            loaded_files: None,
+        })
     }
-}
 
-pub fn get_module_root(main_js_module: &ModuleSpecifier) -> Result<PathBuf> {
-    Ok(main_js_module
-        .to_file_path()
-        .map_err(|_| anyhow!("Invalid main module specifier: not a local path."))?
-        .parent()
-        .ok_or_else(|| anyhow!("Invalid main module specifier: it has no parent directory!"))?
-        // Resolve any symlinks inside the path to prevent modules from escaping our sandbox
-        .canonicalize()?)
-}
+    /// The canonical path of the module root directory, if the loader sandboxes imports.
+    pub fn module_root(&self) -> Option<&Path> {
+        self.module_root.as_deref()
+    }
 
-impl ModuleLoader for ZinniaModuleLoader {
-    fn resolve(
-        &self,
-        specifier: &str,
-        referrer: &str,
-        _kind: ResolutionKind,
-    ) -> Result<ModuleSpecifier, AnyError> {
-        if specifier == "zinnia:test" {
-            return Ok(ModuleSpecifier::parse("ext:zinnia_runtime/test.js").unwrap());
-        } else if specifier == "zinnia:assert" {
-            return Ok(
-                ModuleSpecifier::parse("ext:zinnia_runtime/vendored/asserts.bundle.js").unwrap(),
-            );
-        }
+    /// Record paths of all local files the loader reads into `loaded_files`.
+    pub fn with_loaded_files(mut self, loaded_files: Option<LoadedFiles>) -> Self {
+        self.loaded_files = loaded_files;
+        self
+    }
 
-        let resolved = resolve_import(specifier, referrer)?;
-        Ok(resolved)
+    /// Sources of loaded modules, used to map locations in stack traces to the original code.
+    pub fn sources(&self) -> ModuleSources {
+        self.sources.clone()
     }
 
-    fn load(
+    /// Load the original source code of a module, before transpiling it.
+    ///
+    /// This applies the same rules as `load()`: the module root sandbox and the restrictions on
+    /// remote modules.
+    pub fn load_source(
         &self,
         module_specifier: &ModuleSpecifier,
         maybe_referrer: Option<&ModuleSpecifier>,
-        _is_dyn_import: bool,
-    ) -> std::pin::Pin<Box<ModuleSourceFuture>> {
+    ) -> LocalBoxFuture<'static, Result<String>> {
         let module_specifier = module_specifier.clone();
         let module_root = self.module_root.clone();
         let maybe_referrer = maybe_referrer.cloned();
+        let remote_modules = self.remote_modules.clone();
+        let loaded_files = self.loaded_files.clone();
         async move {
             let spec_str = module_specifier.as_str();
 
-            let details = || {
-                let mut msg = format!("\nModule URL: {spec_str}");
-                if let Some(referrer) = &maybe_referrer {
-                    msg.push_str("\nImported from: ");
-                    msg.push_str(referrer.as_str());
-                }
-                msg
-            };
+            let details = || error_details(&module_specifier, maybe_referrer.as_ref());
 
             if spec_str == "https://deno.land/std@0.177.0/testing/asserts.ts" || spec_str == "https://deno.land/std@0.181.0/testing/asserts.ts" {
                 return Err(anyhow!("Zinnia bundles Deno asserts as 'zinnia:assert`. Please update your imports accordingly.{}", details()));
             }
 
+            if module_specifier.scheme() == "https" {
+                if let Some(remote_modules) = remote_modules {
+                    return remote_modules.load(&module_specifier).await.map_err(|err| {
+                        anyhow!("Cannot load remote module: {err:#}{}", details())
+                    });
+                }
+            }
+
             if module_specifier.scheme() != "file" {
                 return Err(anyhow!(
                     "Unsupported scheme: {}. Zinnia can import local modules only.{}",
@@ -93,10 +107,22 @@ impl ModuleLoader for ZinniaModuleLoader {
                 ))
             }
 
+            if maybe_referrer.as_ref().is_some_and(|r| r.scheme() != "file") {
+                return Err(anyhow!(
+                    "Remote modules cannot import local files.{}",
+                    details()
+                ));
+            }
+
             let module_path = module_specifier.to_file_path().map_err(|_|
                anyhow!("Module specifier cannot be converted to a filepath.{}", details())
             )?;
 
+            // Record the file before reading it, so that we can detect when a missing file is created
+            if let Some(loaded_files) = &loaded_files {
+                loaded_files.insert(module_path.clone());
+            }
+
             // Check that the module path is inside the module root directory
             if let Some(canonical_root) = &module_root {
                 // Resolve any symlinks inside the path to prevent modules from escaping our sandbox
@@ -119,13 +145,118 @@ impl ModuleLoader for ZinniaModuleLoader {
                 }
             };
 
-            let code = read_file_to_string(module_path).await?;
-            let module = ModuleSource::new(ModuleType::JavaScript, code.into(), &module_specifier);
-            Ok(module)
+            read_file_to_string(module_path).await
         }.boxed_local()
     }
 }
 
+/// Paths of local files read by the module loader, e.g. to restart the module when they change.
+///
+/// This is shared between the module loader (which records the paths) and the embedder.
+#[derive(Clone, Debug, Default)]
+pub struct LoadedFiles {
+    paths: Rc<RefCell<BTreeSet<PathBuf>>>,
+}
+
+impl LoadedFiles {
+    pub fn insert(&self, path: PathBuf) {
+        self.paths.borrow_mut().insert(path);
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.paths.borrow().iter().cloned().collect()
+    }
+}
+
+pub fn get_module_root(main_js_module: &ModuleSpecifier) -> Result<PathBuf> {
+    Ok(main_js_module
+        .to_file_path()
+        .map_err(|_| anyhow!("Invalid main module specifier: not a local path."))?
+        .parent()
+        .ok_or_else(|| anyhow!("Invalid main module specifier: it has no parent directory!"))?
+        // Resolve any symlinks inside the path to prevent modules from escaping our sandbox
+        .canonicalize()?)
+}
+
+/// Read the import map from a JSON file. Relative URLs in the import map are resolved relatively
+/// to the location of the file.
+pub fn read_import_map(path: &Path) -> Result<ImportMap> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Cannot read import map {}", path.display()))?;
+    let base_url = resolve_path(&path.to_string_lossy(), &std::env::current_dir()?)?;
+    let result = import_map::parse_from_json(&base_url, &json)
+        .with_context(|| format!("Invalid import map {}", path.display()))?;
+    for diagnostic in result.diagnostics {
+        log::warn!("Import map {}: {diagnostic}", path.display());
+    }
+    Ok(result.import_map)
+}
+
+impl ModuleLoader for ZinniaModuleLoader {
+    fn resolve(
+        &self,
+        specifier: &str,
+        referrer: &str,
+        _kind: ResolutionKind,
+    ) -> Result<ModuleSpecifier, AnyError> {
+        if specifier == "zinnia:test" {
+            return Ok(ModuleSpecifier::parse("ext:zinnia_runtime/test.js").unwrap());
+        } else if specifier == "zinnia:assert" {
+            return Ok(
+                ModuleSpecifier::parse("ext:zinnia_runtime/vendored/asserts.bundle.js").unwrap(),
+            );
+        }
+
+        // Mapped modules are still loaded via `load()`, which applies the module root sandbox.
+        // The main module is resolved with the referrer ".", we don't map it.
+        if let Some(import_map) = &self.import_map {
+            if let Ok(referrer) = ModuleSpecifier::parse(referrer) {
+                return Ok(import_map.resolve(specifier, &referrer)?);
+            }
+        }
+
+        let resolved = resolve_import(specifier, referrer)?;
+        Ok(resolved)
+    }
+
+    fn load(
+        &self,
+        module_specifier: &ModuleSpecifier,
+        maybe_referrer: Option<&ModuleSpecifier>,
+        _is_dyn_import: bool,
+    ) -> std::pin::Pin<Box<ModuleSourceFuture>> {
+        let load_source = self.load_source(module_specifier, maybe_referrer);
+        let module_specifier = module_specifier.clone();
+        let maybe_referrer = maybe_referrer.cloned();
+        let sources = self.sources.clone();
+        async move {
+            let code = load_source.await?;
+            let (module_type, code) = prepare_module_source(&module_specifier, code, &sources)
+                .map_err(|err| {
+                    anyhow!(
+                        "Cannot load module: {err}{}",
+                        error_details(&module_specifier, maybe_referrer.as_ref())
+                    )
+                })?;
+            let module = ModuleSource::new(module_type, code.into(), &module_specifier);
+            Ok(module)
+        }
+        .boxed_local()
+    }
+}
+
+fn error_details(
+    module_specifier: &ModuleSpecifier,
+    maybe_referrer: Option<&ModuleSpecifier>,
+) -> String {
+    let mut msg = format!("\nModule URL: {module_specifier}");
+    if let Some(referrer) = maybe_referrer {
+        msg.push_str("\nImported from: ");
+        msg.push_str(referrer.as_str());
+    }
+    msg
+}
+
 async fn read_file_to_string(path: impl AsRef<Path>) -> Result<String, AnyError> {
     let mut f = File::open(&path).await.map_err(|err| {
         type_error(format!(
@@ -145,7 +276,7 @@ async fn read_file_to_string(path: impl AsRef<Path>) -> Result<String, AnyError>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use deno_core::anyhow::Context;
+    use deno_core::ModuleType;
     use pretty_assertions::assert_eq;
 
     #[tokio::test]
@@ -153,7 +284,7 @@ mod tests {
         let mut imported_file = get_js_dir();
         imported_file.push("99_main.js");
 
-        let loader = ZinniaModuleLoader::build(Some(get_js_dir())).unwrap();
+        let loader = ZinniaModuleLoader::build(Some(get_js_dir()), None, None).unwrap();
         let result = loader
             .load(
                 &ModuleSpecifier::from_file_path(&imported_file).unwrap(),
@@ -167,6 +298,54 @@ mod tests {
         assert_eq!(result.module_type, ModuleType::JavaScript);
     }
 
+    #[tokio::test]
+    async fn loads_json_files_as_json_modules() {
+        let mut fixtures_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        fixtures_dir.push("tests/js/module_fixtures");
+        let mut imported_file = fixtures_dir.clone();
+        imported_file.push("config.json");
+
+        let loader = ZinniaModuleLoader::build(Some(fixtures_dir), None, None).unwrap();
+        let result = loader
+            .load(
+                &ModuleSpecifier::from_file_path(&imported_file).unwrap(),
+                None,
+                false,
+            )
+            .await
+            .with_context(|| format!("cannot import {}", imported_file.display()))
+            .unwrap();
+
+        assert_eq!(result.module_type, ModuleType::Json);
+    }
+
+    #[tokio::test]
+    async fn records_loaded_files() {
+        let imported_file = get_js_dir().join("99_main.js");
+        let missing_file = get_js_dir().join("missing.js");
+
+        let loaded_files = LoadedFiles::default();
+        let loader = ZinniaModuleLoader::build(Some(get_js_dir()), None, None)
+            .unwrap()
+            .with_loaded_files(Some(loaded_files.clone()));
+        loader
+            .load_source(
+                &ModuleSpecifier::from_file_path(&imported_file).unwrap(),
+                None,
+            )
+            .await
+            .unwrap();
+        let result = loader
+            .load_source(
+                &ModuleSpecifier::from_file_path(&missing_file).unwrap(),
+                None,
+            )
+            .await;
+        assert!(result.is_err(), "loading a missing file should fail");
+
+        assert_eq!(loaded_files.paths(), vec![imported_file, missing_file]);
+    }
+
     #[tokio::test]
     async fn rejects_import_of_files_outside_sandbox() {
         // project_root is `runtime/tests/js`
@@ -178,7 +357,7 @@ mod tests {
         let mut imported_file = get_js_dir();
         imported_file.push("99_main.js");
 
-        let loader = ZinniaModuleLoader::build(Some(project_root)).unwrap();
+        let loader = ZinniaModuleLoader::build(Some(project_root), None, None).unwrap();
         let result = loader
             .load(
                 &ModuleSpecifier::from_file_path(&imported_file).unwrap(),
@@ -206,6 +385,70 @@ mod tests {
         }
     }
 
+    #[test]
+    fn resolves_bare_specifiers_using_import_map() {
+        let import_map = import_map::parse_from_json(
+            &ModuleSpecifier::parse("file:///project/import_map.json").unwrap(),
+            r#"{ "imports": { "multiformats": "./vendor/multiformats.js" } }"#,
+        )
+        .unwrap()
+        .import_map;
+
+        let loader = ZinniaModuleLoader::build(None, Some(import_map), None).unwrap();
+        let resolved = loader
+            .resolve(
+                "multiformats",
+                "file:///project/main.js",
+                ResolutionKind::Import,
+            )
+            .unwrap();
+        assert_eq!(resolved.as_str(), "file:///project/vendor/multiformats.js");
+
+        let resolved = loader
+            .resolve(
+                "./lib.js",
+                "file:///project/main.js",
+                ResolutionKind::Import,
+            )
+            .unwrap();
+        assert_eq!(resolved.as_str(), "file:///project/lib.js");
+
+        let result = loader.resolve("unknown", "file:///project/main.js", ResolutionKind::Import);
+        assert!(
+            result.is_err(),
+            "unmapped bare specifiers should be rejected"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_mapped_modules_outside_sandbox() {
+        // project_root is `runtime/tests/js`, the import map points to `runtime/js/99_main.js`
+        let mut project_root = get_js_dir().parent().unwrap().to_path_buf();
+        project_root.push("tests");
+        project_root.push("js");
+        let referrer = ModuleSpecifier::from_file_path(project_root.join("main.js")).unwrap();
+
+        let import_map = import_map::parse_from_json(
+            &referrer,
+            r#"{ "imports": { "main": "../../js/99_main.js" } }"#,
+        )
+        .unwrap()
+        .import_map;
+
+        let loader = ZinniaModuleLoader::build(Some(project_root), Some(import_map), None).unwrap();
+        let resolved = loader
+            .resolve("main", referrer.as_str(), ResolutionKind::Import)
+            .unwrap();
+        let Err(err) = loader.load(&resolved, Some(&referrer), false).await else {
+            panic!("import of a mapped module outside of the sandbox should fail");
+        };
+        assert!(
+            err.to_string()
+                .contains("Cannot import files outside of the module root directory"),
+            "unexpected error: {err}"
+        );
+    }
+
     fn get_js_dir() -> PathBuf {
         let mut base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         base_dir.push("js");
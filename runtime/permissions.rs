@@ -0,0 +1,670 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use deno_core::anyhow::{anyhow, Context, Error, Result};
+use deno_core::url::{Host, Url};
+use ipnet::IpNet;
+use once_cell::sync::Lazy;
+use zinnia_libp2p::multiaddr::Protocol;
+use zinnia_libp2p::Multiaddr;
+
+/// A rule matching network destinations, used in allow and deny lists.
+///
+/// Supported formats:
+/// - `example.com`, `1.2.3.4`, `::1` - a host, any port
+/// - `example.com:443`, `1.2.3.4:443`, `[::1]:443` - a host and a port
+/// - `*.example.com` - all subdomains of `example.com`, optionally with a port
+/// - `*:443` - any host, the given port
+/// - `10.0.0.0/8`, `fc00::/7` - IP addresses in the given range (CIDR notation), any port
+/// - `/dns4/example.com/tcp/4001`, `/p2p/12D3KooW...` - libp2p multiaddr prefix, applies to
+///   `Zinnia.requestProtocol()` only
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetRule {
+    Host {
+        host: HostPattern,
+        port: Option<u16>,
+    },
+    Cidr(IpNet),
+    Multiaddr(Multiaddr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostPattern {
+    Any,
+    Domain(String),
+    /// Subdomains of the given domain, excluding the domain itself.
+    Subdomains(String),
+    Ip(IpAddr),
+}
+
+impl FromStr for NetRule {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(anyhow!("Network rule must not be empty."));
+        }
+
+        if value.starts_with('/') {
+            let addr = Multiaddr::from_str(value)
+                .with_context(|| format!("Invalid multiaddr in network rule {value:?}"))?;
+            return Ok(NetRule::Multiaddr(addr));
+        }
+
+        if value.contains('/') {
+            let net = IpNet::from_str(value)
+                .with_context(|| format!("Invalid IP range in network rule {value:?}"))?;
+            return Ok(NetRule::Cidr(net.trunc()));
+        }
+
+        // IPv6 addresses without a port, e.g. `::1`
+        if let Ok(ip) = IpAddr::from_str(value) {
+            return Ok(NetRule::Host {
+                host: HostPattern::Ip(ip.to_canonical()),
+                port: None,
+            });
+        }
+
+        let (host, port) = split_host_port(value)?;
+        Ok(NetRule::Host {
+            host: parse_host_pattern(host)
+                .with_context(|| format!("Invalid host in network rule {value:?}"))?,
+            port,
+        })
+    }
+}
+
+fn split_host_port(value: &str) -> Result<(&str, Option<u16>)> {
+    let (host, port) = if let Some(rest) = value.strip_prefix('[') {
+        // `[::1]` or `[::1]:443`
+        let (host, rest) = rest
+            .split_once(']')
+            .ok_or_else(|| anyhow!("Invalid network rule {value:?}: missing `]`."))?;
+        match rest {
+            "" => (host, None),
+            _ => match rest.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => return Err(anyhow!("Invalid network rule {value:?}.")),
+            },
+        }
+    } else {
+        match value.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (value, None),
+        }
+    };
+
+    let port = port
+        .map(|port| {
+            port.parse::<u16>()
+                .with_context(|| format!("Invalid port in network rule {value:?}"))
+        })
+        .transpose()?;
+    Ok((host, port))
+}
+
+fn parse_host_pattern(host: &str) -> Result<HostPattern> {
+    if host == "*" {
+        return Ok(HostPattern::Any);
+    }
+    if let Ok(ip) = IpAddr::from_str(host) {
+        return Ok(HostPattern::Ip(ip.to_canonical()));
+    }
+
+    let (domain, subdomains) = match host.strip_prefix("*.") {
+        Some(domain) => (domain, true),
+        None => (host, false),
+    };
+    let domain = normalize_domain(domain);
+    if domain.is_empty()
+        || !domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+    {
+        return Err(anyhow!("{host:?} is not a valid host name."));
+    }
+
+    Ok(match subdomains {
+        true => HostPattern::Subdomains(domain),
+        false => HostPattern::Domain(domain),
+    })
+}
+
+fn normalize_domain(domain: &str) -> String {
+    domain.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// A network destination the module wants to access. IPv4-mapped IPv6 addresses like
+/// `::ffff:127.0.0.1` are converted to IPv4 addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetDestination {
+    host: Option<DestinationHost>,
+    port: Option<u16>,
+    /// Set for libp2p destinations only.
+    multiaddr: Option<Multiaddr>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DestinationHost {
+    Domain(String),
+    Ip(IpAddr),
+}
+
+impl NetDestination {
+    pub fn from_url(url: &Url) -> Result<Self> {
+        let host = match url.host() {
+            Some(Host::Domain(domain)) => DestinationHost::Domain(normalize_domain(domain)),
+            Some(Host::Ipv4(ip)) => DestinationHost::Ip(IpAddr::V4(ip)),
+            Some(Host::Ipv6(ip)) => DestinationHost::Ip(IpAddr::V6(ip).to_canonical()),
+            None => return Err(anyhow!("The URL {url} does not have a host.")),
+        };
+        Ok(Self {
+            host: Some(host),
+            port: url.port_or_known_default(),
+            multiaddr: None,
+        })
+    }
+
+    /// A host name looked up by the `fetch()` DNS resolver, the port is not known at that point.
+    pub(crate) fn from_domain(domain: &str) -> Self {
+        Self {
+            host: Some(DestinationHost::Domain(normalize_domain(domain))),
+            port: None,
+            multiaddr: None,
+        }
+    }
+
+    pub(crate) fn from_ip(ip: IpAddr) -> Self {
+        Self {
+            host: Some(DestinationHost::Ip(ip.to_canonical())),
+            port: None,
+            multiaddr: None,
+        }
+    }
+
+    pub fn from_multiaddr(addr: &Multiaddr) -> Self {
+        let mut host = None;
+        let mut port = None;
+        for protocol in addr.iter() {
+            match protocol {
+                Protocol::Ip4(ip) if host.is_none() => {
+                    host = Some(DestinationHost::Ip(IpAddr::V4(ip)));
+                }
+                Protocol::Ip6(ip) if host.is_none() => {
+                    host = Some(DestinationHost::Ip(IpAddr::V6(ip).to_canonical()));
+                }
+                Protocol::Dns(domain)
+                | Protocol::Dns4(domain)
+                | Protocol::Dns6(domain)
+                | Protocol::Dnsaddr(domain)
+                    if host.is_none() =>
+                {
+                    host = Some(DestinationHost::Domain(normalize_domain(&domain)));
+                }
+                Protocol::Tcp(p) | Protocol::Udp(p) if port.is_none() => port = Some(p),
+                _ => {}
+            }
+        }
+        Self {
+            host,
+            port,
+            multiaddr: Some(addr.clone()),
+        }
+    }
+
+    fn is_private(&self) -> bool {
+        match &self.host {
+            Some(DestinationHost::Ip(ip)) => is_private_ip(ip),
+            Some(DestinationHost::Domain(domain)) => {
+                domain == "localhost" || domain.ends_with(".localhost")
+            }
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for NetDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(addr) = &self.multiaddr {
+            return write!(f, "{addr}");
+        }
+        match &self.host {
+            Some(DestinationHost::Domain(domain)) => f.write_str(domain)?,
+            Some(DestinationHost::Ip(IpAddr::V6(ip))) => write!(f, "[{ip}]")?,
+            Some(DestinationHost::Ip(ip)) => write!(f, "{ip}")?,
+            None => f.write_str("(unknown host)")?,
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{port}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Private, loopback, link-local and other non-public address ranges.
+static PRIVATE_RANGES: Lazy<Vec<IpNet>> = Lazy::new(|| {
+    [
+        "0.0.0.0/8",
+        "10.0.0.0/8",
+        "100.64.0.0/10",
+        "127.0.0.0/8",
+        "169.254.0.0/16",
+        "172.16.0.0/12",
+        "192.168.0.0/16",
+        // Benchmarking
+        "198.18.0.0/15",
+        // Multicast and broadcast
+        "224.0.0.0/4",
+        "255.255.255.255/32",
+        "::/128",
+        "::1/128",
+        // NAT64, the embedded IPv4 address can be a private one
+        "64:ff9b::/96",
+        "fc00::/7",
+        "fe80::/10",
+        // Site-local (deprecated)
+        "fec0::/10",
+        // Multicast
+        "ff00::/8",
+    ]
+    .iter()
+    .map(|range| range.parse().unwrap())
+    .collect()
+});
+
+fn is_private_ip(ip: &IpAddr) -> bool {
+    PRIVATE_RANGES.iter().any(|range| range.contains(ip))
+}
+
+impl NetRule {
+    fn matches(&self, dest: &NetDestination) -> bool {
+        match self {
+            NetRule::Host { host, port } => {
+                if port.is_some() && *port != dest.port {
+                    return false;
+                }
+                match (host, &dest.host) {
+                    (HostPattern::Any, _) => true,
+                    (HostPattern::Domain(pattern), Some(DestinationHost::Domain(domain))) => {
+                        pattern == domain
+                    }
+                    (HostPattern::Subdomains(pattern), Some(DestinationHost::Domain(domain))) => {
+                        domain
+                            .strip_suffix(pattern.as_str())
+                            .is_some_and(|sub| sub.ends_with('.'))
+                    }
+                    (HostPattern::Ip(pattern), Some(DestinationHost::Ip(ip))) => pattern == ip,
+                    _ => false,
+                }
+            }
+            NetRule::Cidr(net) => match &dest.host {
+                Some(DestinationHost::Ip(ip)) => net.contains(ip),
+                _ => false,
+            },
+            NetRule::Multiaddr(rule) => match &dest.multiaddr {
+                Some(addr) => multiaddr_matches(rule, addr),
+                None => false,
+            },
+        }
+    }
+}
+
+/// The rule matches addresses starting with the rule's components, e.g. `/ip4/1.2.3.4` matches
+/// `/ip4/1.2.3.4/tcp/4001/p2p/12D3KooW...`. A rule consisting of a `/p2p` component only matches
+/// all addresses of the given peer.
+fn multiaddr_matches(rule: &Multiaddr, addr: &Multiaddr) -> bool {
+    let mut rule_components = rule.iter();
+    if let (Some(Protocol::P2p(peer)), None) = (rule_components.next(), rule_components.next()) {
+        return addr.iter().any(|p| p == Protocol::P2p(peer));
+    }
+
+    let rule_len = rule.iter().count();
+    rule_len <= addr.iter().count() && rule.iter().zip(addr.iter()).all(|(r, a)| r == a)
+}
+
+/// Network access policy of a module.
+///
+/// The destination is allowed when all of the following conditions are met:
+/// 1. It does not match any rule in `deny`.
+/// 2. It matches a rule in `allow`, or `allow` is empty.
+/// 3. It's not a private or loopback address when `deny_private` is set, unless it matches a rule
+///    in `allow`.
+/// 4. It matches a rule in `requested`, or `requested` is empty.
+///
+/// The default policy allows all destinations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetPermissions {
+    /// When not empty, only destinations matching one of these rules are allowed.
+    pub allow: Vec<NetRule>,
+
+    /// Destinations matching any of these rules are denied, regardless of other rules.
+    pub deny: Vec<NetRule>,
+
+    /// Deny access to private, loopback and link-local addresses and to `localhost`. Host names are
+    /// checked again after they are resolved, see `check_resolved()`.
+    pub deny_private: bool,
+
+    /// Destinations requested by the module in its manifest (`permissions.net`). When not empty,
+    /// the module can access only these destinations. Unlike `allow`, the requested destinations
+    /// cannot override `deny_private`.
+    pub requested: Vec<NetRule>,
+}
+
+impl NetPermissions {
+    pub fn check(&self, dest: &NetDestination, api_name: &str) -> Result<()> {
+        let deny = |reason: &str| Err(anyhow!("{api_name} cannot access {dest}: {reason}."));
+
+        if self.deny.iter().any(|rule| rule.matches(dest)) {
+            return deny("the destination is denied by the network policy");
+        }
+
+        let explicitly_allowed = self.allow.iter().any(|rule| rule.matches(dest));
+        if !self.allow.is_empty() && !explicitly_allowed {
+            return deny("the destination is not allowed by the network policy");
+        }
+
+        if self.deny_private && !explicitly_allowed && dest.is_private() {
+            return deny("access to private and loopback addresses is not allowed");
+        }
+
+        if !self.requested.is_empty() && !self.requested.iter().any(|rule| rule.matches(dest)) {
+            return deny("the destination is not listed in the module permissions");
+        }
+
+        Ok(())
+    }
+
+    /// Check the address `resolved` the host name of `dest` resolved to, after `check()` allowed
+    /// `dest`. The deny list and `deny_private` apply to the resolved address too, unless the
+    /// operator allowed the host name or the address explicitly.
+    pub fn check_resolved(
+        &self,
+        dest: &NetDestination,
+        resolved: &NetDestination,
+        api_name: &str,
+    ) -> Result<()> {
+        let deny = |reason: &str| {
+            Err(anyhow!(
+                "{api_name} cannot access {dest}: the host name resolves to {resolved}, {reason}."
+            ))
+        };
+
+        if self.deny.iter().any(|rule| rule.matches(resolved)) {
+            return deny("the address is denied by the network policy");
+        }
+
+        let explicitly_allowed = self
+            .allow
+            .iter()
+            .any(|rule| rule.matches(dest) || rule.matches(resolved));
+        if self.deny_private && !explicitly_allowed && resolved.is_private() {
+            return deny("access to private and loopback addresses is not allowed");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn rules(values: &[&str]) -> Vec<NetRule> {
+        values.iter().map(|v| v.parse().unwrap()).collect()
+    }
+
+    fn url(value: &str) -> NetDestination {
+        NetDestination::from_url(&Url::parse(value).unwrap()).unwrap()
+    }
+
+    fn multiaddr(value: &str) -> NetDestination {
+        NetDestination::from_multiaddr(&value.parse().unwrap())
+    }
+
+    fn check(permissions: &NetPermissions, dest: &NetDestination) -> Result<(), String> {
+        permissions
+            .check(dest, "fetch()")
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!(
+            rules(&[
+                "example.com",
+                "*.Example.com:443",
+                "*:8080",
+                "[::1]:80",
+                "::1"
+            ]),
+            vec![
+                NetRule::Host {
+                    host: HostPattern::Domain("example.com".into()),
+                    port: None
+                },
+                NetRule::Host {
+                    host: HostPattern::Subdomains("example.com".into()),
+                    port: Some(443)
+                },
+                NetRule::Host {
+                    host: HostPattern::Any,
+                    port: Some(8080)
+                },
+                NetRule::Host {
+                    host: HostPattern::Ip("::1".parse().unwrap()),
+                    port: Some(80)
+                },
+                NetRule::Host {
+                    host: HostPattern::Ip("::1".parse().unwrap()),
+                    port: None
+                },
+            ]
+        );
+        assert_eq!(
+            rules(&["10.1.2.3/8", "/dns4/example.com/tcp/4001"]),
+            vec![
+                NetRule::Cidr("10.0.0.0/8".parse().unwrap()),
+                NetRule::Multiaddr("/dns4/example.com/tcp/4001".parse().unwrap()),
+            ]
+        );
+
+        for invalid in [
+            "",
+            "example.com:http",
+            "exa mple.com",
+            "10.0.0.0/33",
+            "/foo/bar",
+        ] {
+            assert!(
+                invalid.parse::<NetRule>().is_err(),
+                "{invalid:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn allows_everything_by_default() {
+        let permissions = NetPermissions::default();
+        assert_eq!(check(&permissions, &url("http://127.0.0.1:8080/")), Ok(()));
+        assert_eq!(
+            check(&permissions, &multiaddr("/ip4/10.0.0.1/tcp/4001")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn denies_private_addresses() {
+        let permissions = NetPermissions {
+            deny_private: true,
+            ..Default::default()
+        };
+        for private in [
+            "http://127.0.0.1/",
+            "http://localhost:8080/",
+            "http://api.localhost/",
+            "http://192.168.1.1/",
+            "http://[::1]/",
+            "http://[::ffff:10.0.0.1]/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://198.18.0.1/",
+            "http://224.0.0.1/",
+            "http://255.255.255.255/",
+            "http://[64:ff9b::a00:1]/",
+            "http://[fec0::1]/",
+            "http://[ff02::1]/",
+        ] {
+            assert_eq!(
+                check(&permissions, &url(private)).map_err(|err| err.contains("private")),
+                Err(true),
+                "{private} should be denied"
+            );
+        }
+        assert_eq!(check(&permissions, &url("https://1.1.1.1/")), Ok(()));
+        assert_eq!(check(&permissions, &url("https://example.com/")), Ok(()));
+
+        let err = check(&permissions, &multiaddr("/ip4/10.0.0.1/tcp/4001")).unwrap_err();
+        assert_eq!(
+            err,
+            "fetch() cannot access /ip4/10.0.0.1/tcp/4001: \
+             access to private and loopback addresses is not allowed."
+        );
+    }
+
+    #[test]
+    fn allow_list_overrides_private_ranges() {
+        let permissions = NetPermissions {
+            allow: rules(&["127.0.0.1:3000", "*.example.com"]),
+            deny_private: true,
+            ..Default::default()
+        };
+        assert_eq!(check(&permissions, &url("http://127.0.0.1:3000/")), Ok(()));
+        assert_eq!(
+            check(&permissions, &url("https://api.example.com/")),
+            Ok(())
+        );
+        assert!(check(&permissions, &url("http://127.0.0.1:3001/")).is_err());
+        assert!(check(&permissions, &url("https://example.com/")).is_err());
+        assert!(check(&permissions, &url("https://example.org/")).is_err());
+    }
+
+    #[test]
+    fn deny_list_takes_precedence() {
+        let permissions = NetPermissions {
+            allow: rules(&["*"]),
+            deny: rules(&["93.184.0.0/16", "evil.example.com", "*:25"]),
+            ..Default::default()
+        };
+        assert!(check(&permissions, &url("http://93.184.216.34/")).is_err());
+        assert!(check(&permissions, &url("https://evil.example.com/")).is_err());
+        assert!(check(&permissions, &url("smtp://mail.example.com:25/")).is_err());
+        assert_eq!(check(&permissions, &url("https://example.com/")), Ok(()));
+    }
+
+    #[test]
+    fn restricts_to_requested_destinations() {
+        let permissions = NetPermissions {
+            deny_private: true,
+            requested: rules(&["example.com:443", "127.0.0.1"]),
+            ..Default::default()
+        };
+        assert_eq!(check(&permissions, &url("https://example.com/")), Ok(()));
+        assert!(check(&permissions, &url("http://example.com/")).is_err());
+        // The module cannot request access to private addresses
+        assert!(check(&permissions, &url("http://127.0.0.1/")).is_err());
+    }
+
+    #[test]
+    fn matches_multiaddr_rules() {
+        let peer = "12D3KooWRH71QRJe5vrMp6zZXoH4K7z5MDSWwTXXPriG9dK8HQXk";
+        let permissions = NetPermissions {
+            allow: rules(&["/dns4/bootstrap.example.com", &format!("/p2p/{peer}")]),
+            ..Default::default()
+        };
+        assert_eq!(
+            check(
+                &permissions,
+                &multiaddr("/dns4/bootstrap.example.com/tcp/4001")
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                &permissions,
+                &multiaddr(&format!("/ip4/1.2.3.4/tcp/4001/p2p/{peer}"))
+            ),
+            Ok(())
+        );
+        assert!(check(&permissions, &multiaddr("/ip4/1.2.3.4/tcp/4001")).is_err());
+        // Multiaddr rules don't apply to URLs
+        assert!(check(&permissions, &url("https://bootstrap.example.com/")).is_err());
+    }
+
+    #[test]
+    fn applies_host_rules_to_multiaddrs() {
+        let permissions = NetPermissions {
+            deny: rules(&["1.2.3.0/24", "*:4002"]),
+            ..Default::default()
+        };
+        assert!(check(&permissions, &multiaddr("/ip4/1.2.3.4/tcp/4001")).is_err());
+        assert!(check(&permissions, &multiaddr("/ip4/5.6.7.8/udp/4002/quic")).is_err());
+        assert_eq!(
+            check(&permissions, &multiaddr("/ip4/5.6.7.8/tcp/4001")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn checks_resolved_addresses() {
+        let permissions = NetPermissions {
+            allow: rules(&["*.example.com", "intranet.local", "10.1.2.3"]),
+            deny: rules(&["93.184.0.0/16"]),
+            deny_private: true,
+            ..Default::default()
+        };
+        let resolved = |dest: &NetDestination, ip: &str| {
+            permissions
+                .check_resolved(
+                    dest,
+                    &NetDestination::from_ip(ip.parse().unwrap()),
+                    "fetch()",
+                )
+                .map_err(|err| err.to_string())
+        };
+
+        let dest = NetDestination::from_domain("rebind.example.org");
+        assert_eq!(
+            resolved(&dest, "127.0.0.1"),
+            Err(
+                "fetch() cannot access rebind.example.org: the host name resolves to 127.0.0.1, \
+                 access to private and loopback addresses is not allowed."
+                    .to_string()
+            )
+        );
+        assert!(resolved(&dest, "::ffff:192.168.1.1").is_err());
+        assert!(resolved(&dest, "93.184.216.34").is_err());
+        assert_eq!(resolved(&dest, "1.1.1.1"), Ok(()));
+
+        // The operator allowed the host name or the address
+        assert_eq!(
+            resolved(&NetDestination::from_domain("intranet.local"), "10.0.0.1"),
+            Ok(())
+        );
+        assert_eq!(resolved(&dest, "10.1.2.3"), Ok(()));
+
+        let dest = multiaddr("/dns4/bootstrap.example.org/tcp/4001");
+        let err = permissions
+            .check_resolved(
+                &dest,
+                &multiaddr("/ip4/127.0.0.1/tcp/4001"),
+                "Zinnia.requestProtocol()",
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("private"),
+            "unexpected error: {err}"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use deno_core::anyhow::{anyhow, Context, Result};
+use deno_core::{serde_json, ModuleSpecifier};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Configuration of remote (`https:`) module imports.
+///
+/// Remote imports are meant for development only, therefore they are enabled by the `zinnia` CLI
+/// and not by `zinniad`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteModulesConfig {
+    /// Directory where to cache the downloaded module sources.
+    pub cache_dir: PathBuf,
+
+    /// The lockfile pinning the integrity hashes of all remote modules, e.g. `zinnia.lock`.
+    pub lockfile: PathBuf,
+
+    /// Refuse to load remote modules that are not pinned in the lockfile instead of adding them.
+    pub frozen: bool,
+}
+
+const LOCKFILE_VERSION: u32 = 1;
+
+/// The content of the lockfile.
+///
+/// Example:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "remote": {
+///     "https://esm.sh/v135/multiformats@12.1.3/es2022/cid.js": "e0a3...5b1c"
+///   }
+/// }
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+struct Lockfile {
+    version: u32,
+    /// Maps the URL of a remote module to the SHA-256 hash (hex-encoded) of its source code.
+    remote: BTreeMap<String, String>,
+}
+
+/// Downloads remote modules, caches their sources and verifies them against the lockfile.
+pub struct RemoteModules {
+    config: RemoteModulesConfig,
+    lockfile: RefCell<Lockfile>,
+    client: reqwest::Client,
+}
+
+impl RemoteModules {
+    pub fn new(config: RemoteModulesConfig, user_agent: &str) -> Result<Self> {
+        let lockfile = read_lockfile(&config.lockfile)?;
+        let client = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .build()
+            .context("Cannot create the HTTP client for remote imports")?;
+        Ok(Self {
+            config,
+            lockfile: RefCell::new(lockfile),
+            client,
+        })
+    }
+
+    /// Load the source code of a remote module, from the cache when possible.
+    pub async fn load(&self, specifier: &ModuleSpecifier) -> Result<String> {
+        let url = specifier.as_str();
+        let pinned_hash = self.lockfile.borrow().remote.get(url).cloned();
+        if pinned_hash.is_none() && self.config.frozen {
+            return Err(anyhow!(
+                "The remote module is not pinned in the lockfile {}. \
+                 Run without --frozen to add it.",
+                self.config.lockfile.display()
+            ));
+        }
+
+        let cache_path = self.cache_path(specifier);
+        let code = match tokio::fs::read(&cache_path).await {
+            Ok(code) => code,
+            Err(_) => self.download(specifier, &cache_path).await?,
+        };
+
+        let hash = sha256_hex(&code);
+        match pinned_hash {
+            Some(pinned) if pinned != hash => {
+                return Err(anyhow!(
+                    "Integrity check failed for remote module.\n\
+                     Lockfile {}: {pinned}\n\
+                     Actual: {hash}\n\
+                     Remove the module from the lockfile if the change is expected.",
+                    self.config.lockfile.display()
+                ));
+            }
+            Some(_) => {}
+            None => {
+                let mut lockfile = self.lockfile.borrow_mut();
+                lockfile.remote.insert(url.to_string(), hash);
+                write_lockfile(&self.config.lockfile, &lockfile)?;
+            }
+        }
+
+        String::from_utf8(code).map_err(|_| anyhow!("The remote module is not valid UTF-8."))
+    }
+
+    async fn download(&self, specifier: &ModuleSpecifier, cache_path: &Path) -> Result<Vec<u8>> {
+        log::debug!("Downloading {specifier}");
+        let response = self
+            .client
+            .get(specifier.clone())
+            .send()
+            .await
+            .context("Cannot download the remote module")?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Cannot download the remote module: HTTP status {}",
+                response.status()
+            ));
+        }
+        let code = response
+            .bytes()
+            .await
+            .context("Cannot download the remote module")?
+            .to_vec();
+
+        if let Some(dir) = cache_path.parent() {
+            tokio::fs::create_dir_all(dir).await.with_context(|| {
+                format!("Cannot create the module cache directory {}", dir.display())
+            })?;
+        }
+        tokio::fs::write(cache_path, &code)
+            .await
+            .with_context(|| format!("Cannot write the module cache {}", cache_path.display()))?;
+
+        Ok(code)
+    }
+
+    /// Remote modules are cached as `{cache_dir}/{scheme}/{host}/{sha256 of the URL}`.
+    fn cache_path(&self, specifier: &ModuleSpecifier) -> PathBuf {
+        let mut host = specifier.host_str().unwrap_or("_").to_string();
+        if let Some(port) = specifier.port() {
+            host.push_str(&format!("_PORT{port}"));
+        }
+        self.config
+            .cache_dir
+            .join(specifier.scheme())
+            .join(host)
+            .join(sha256_hex(specifier.as_str().as_bytes()))
+    }
+}
+
+fn read_lockfile(path: &Path) -> Result<Lockfile> {
+    if !path.exists() {
+        return Ok(Lockfile {
+            version: LOCKFILE_VERSION,
+            ..Default::default()
+        });
+    }
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("Cannot read lockfile {}", path.display()))?;
+    let lockfile = serde_json::from_str::<Lockfile>(&data)
+        .with_context(|| format!("Invalid lockfile {}", path.display()))?;
+    if lockfile.version != LOCKFILE_VERSION {
+        return Err(anyhow!(
+            "Unsupported version {} of lockfile {}",
+            lockfile.version,
+            path.display()
+        ));
+    }
+    Ok(lockfile)
+}
+
+fn write_lockfile(path: &Path, lockfile: &Lockfile) -> Result<()> {
+    let mut data = serde_json::to_string_pretty(lockfile)?;
+    data.push('\n');
+    std::fs::write(path, data).with_context(|| format!("Cannot write lockfile {}", path.display()))
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use pretty_assertions::assert_eq;
+
+    const MODULE_URL: &str = "https://example.com/mod.js";
+    const MODULE_CODE: &str = "export const answer = 42;\n";
+
+    fn setup(frozen: bool) -> (assert_fs::TempDir, RemoteModules) {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let config = RemoteModulesConfig {
+            cache_dir: temp.path().join("cache"),
+            lockfile: temp.path().join("zinnia.lock"),
+            frozen,
+        };
+        let remote_modules = RemoteModules::new(config, "zinnia/test").unwrap();
+        let cache_path = remote_modules.cache_path(&ModuleSpecifier::parse(MODULE_URL).unwrap());
+        std::fs::create_dir_all(cache_path.parent().unwrap()).unwrap();
+        std::fs::write(cache_path, MODULE_CODE).unwrap();
+        (temp, remote_modules)
+    }
+
+    #[tokio::test]
+    async fn pins_new_modules_in_lockfile() {
+        let (temp, remote_modules) = setup(false);
+        let code = remote_modules
+            .load(&ModuleSpecifier::parse(MODULE_URL).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(code, MODULE_CODE);
+
+        let lockfile = read_lockfile(&temp.path().join("zinnia.lock")).unwrap();
+        assert_eq!(
+            lockfile.remote.get(MODULE_URL),
+            Some(&sha256_hex(MODULE_CODE.as_bytes()))
+        );
+    }
+
+    #[tokio::test]
+    async fn frozen_rejects_unpinned_modules() {
+        let (_temp, remote_modules) = setup(true);
+        let err = remote_modules
+            .load(&ModuleSpecifier::parse(MODULE_URL).unwrap())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("not pinned in the lockfile"),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_modules_with_changed_hash() {
+        let (temp, _) = setup(false);
+        temp.child("zinnia.lock")
+            .write_str(&format!(
+                r#"{{ "version": 1, "remote": {{ "{MODULE_URL}": "{}" }} }}"#,
+                "0".repeat(64)
+            ))
+            .unwrap();
+        let remote_modules = RemoteModules::new(
+            RemoteModulesConfig {
+                cache_dir: temp.path().join("cache"),
+                lockfile: temp.path().join("zinnia.lock"),
+                frozen: true,
+            },
+            "zinnia/test",
+        )
+        .unwrap();
+
+        let err = remote_modules
+            .load(&ModuleSpecifier::parse(MODULE_URL).unwrap())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("Integrity check failed"),
+            "unexpected error: {err}"
+        );
+    }
+}
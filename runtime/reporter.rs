@@ -1,6 +1,8 @@
 use std::cell::RefCell;
 use std::fmt::Display;
 
+use deno_core::serde_json;
+use serde::Deserialize;
 use serde_repr::Deserialize_repr;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize_repr)]
@@ -47,19 +49,81 @@ impl From<i32> for LogLevel {
     }
 }
 
+/// Severity of an activity reported by the module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+impl Display for ActivityLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            ActivityLevel::Info => "info",
+            ActivityLevel::Warn => "warn",
+            ActivityLevel::Error => "error",
+        };
+        f.write_str(str)
+    }
+}
+
+/// An activity log entry, see `Zinnia.activity.info()` and friends.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Activity {
+    pub level: ActivityLevel,
+    /// Important: this message DOES NOT include the final newline.
+    pub message: String,
+    /// Machine-readable identifier of the activity, e.g. `RETRIEVAL_TIMEOUT`.
+    pub code: Option<String>,
+    /// Additional data describing the activity.
+    pub details: Option<serde_json::Value>,
+}
+
+impl Activity {
+    pub fn new(level: ActivityLevel, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            message: message.into(),
+            code: None,
+            details: None,
+        }
+    }
+}
+
+/// Formats the activity as a single line: the message followed by the code and details, if any.
+impl Display for Activity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)?;
+        if let Some(code) = &self.code {
+            write!(f, " [{code}]")?;
+        }
+        if let Some(details) = &self.details {
+            write!(f, " {details}")?;
+        }
+        Ok(())
+    }
+}
+
 // Report events, activities and messages from the running module
 pub trait Reporter {
     /// Print a debug log message. This is typically triggered by Console APIs like `console.log`.
     /// Important: these messages include the final newline, the reporter SHOULD NOT add EOL.
     fn log(&self, level: LogLevel, msg: &str);
 
-    /// Record an activity log entry with level "info".
-    /// Important: this message DOES NOT include the final newline. The reporter should add EOL.
-    fn info_activity(&self, msg: &str);
+    /// Record an activity log entry. The reporter should add EOL after the message.
+    fn activity(&self, activity: &Activity);
 
-    /// Record an activity log entry with level "error".
-    /// Important: this message DOES NOT include the final newline. The reporter should add EOL.
-    fn error_activity(&self, msg: &str);
+    /// Record an activity log entry with level "info" and no structured data.
+    fn info_activity(&self, msg: &str) {
+        self.activity(&Activity::new(ActivityLevel::Info, msg));
+    }
+
+    /// Record an activity log entry with level "error" and no structured data.
+    fn error_activity(&self, msg: &str) {
+        self.activity(&Activity::new(ActivityLevel::Error, msg));
+    }
 
     /// Report that module completed another job.
     fn job_completed(&self);
@@ -94,14 +158,10 @@ impl Reporter for RecordingReporter {
         self.record(format!("console.{level}: {msg}"));
     }
 
-    fn info_activity(&self, msg: &str) {
-        println!("INFO: {msg}");
-        self.record(format!("INFO: {msg}"));
-    }
-
-    fn error_activity(&self, msg: &str) {
-        println!("ERROR: {msg}");
-        self.record(format!("ERROR: {msg}"));
+    fn activity(&self, activity: &Activity) {
+        let level = activity.level.to_string().to_uppercase();
+        println!("{level}: {activity}");
+        self.record(format!("{level}: {activity}"));
     }
 
     fn job_completed(&self) {
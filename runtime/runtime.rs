@@ -1,18 +1,33 @@
-use std::path::PathBuf;
+use std::cell::Cell;
+use std::future::poll_fn;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::Arc;
+use std::task::Poll;
 use std::time::Duration;
 
-use deno_core::{located_script_name, serde_json, JsRuntime, ModuleSpecifier, RuntimeOptions};
+use deno_core::url::Url;
+use deno_core::{located_script_name, serde_json, v8, JsRuntime, ModuleSpecifier, RuntimeOptions};
 
 use deno_web::BlobStore;
+use import_map::ImportMap;
+use tokio::sync::{mpsc, Notify};
+use tokio::time::MissedTickBehavior;
 
 use {once_cell::sync::Lazy, regex::Regex};
 
-use crate::module_loader::ZinniaModuleLoader;
-use crate::{colors, Reporter};
+use crate::dns_resolver::create_http_client;
+use crate::kv::KvStoreConfig;
+use crate::metrics::{MetricsReporter, ModuleMetrics, METRICS_SAMPLE_INTERVAL};
+use crate::module_loader::{LoadedFiles, ZinniaModuleLoader};
+use crate::permissions::NetPermissions;
+use crate::remote_modules::{RemoteModules, RemoteModulesConfig};
+use crate::vendored::inspector_server::InspectorServer;
+use crate::watchdog::Watchdog;
+use crate::{colors, Reporter, TestResults};
 
-use crate::ext::ZinniaPermissions;
+use crate::ext::{ExitCode, ExitSignal, ZinniaPermissions};
 
 use zinnia_libp2p;
 
@@ -34,6 +49,58 @@ pub struct BootstrapOptions {
     /// Module root if you want to sandbox `import` of ES modules
     pub module_root: Option<PathBuf>,
 
+    /// Import map used to resolve bare specifiers like `multiformats`, see `read_import_map()`.
+    /// Mapped modules are subject to the `module_root` sandbox too.
+    pub import_map: Option<ImportMap>,
+
+    /// Allow `https:` imports, see `RemoteModulesConfig`. The `zinnia` CLI enables remote imports
+    /// for development, `zinniad` keeps them disabled.
+    pub remote_modules: Option<RemoteModulesConfig>,
+
+    /// Record paths of all local files loaded by the module, see `zinnia run --watch`.
+    pub loaded_files: Option<LoadedFiles>,
+
+    /// Persistent key-value store exposed via `Zinnia.kv`. The API throws when not configured.
+    pub kv_store: Option<KvStoreConfig>,
+
+    /// The maximum size of the V8 heap in bytes. When the module gets close to the limit, the
+    /// module is terminated and `run_js_module` returns an error. Uses V8 defaults when not set.
+    pub max_heap_size: Option<usize>,
+
+    /// Terminate the module when its code does not yield to the event loop for this long, e.g.
+    /// when it's stuck in `while (true) {}`. The watchdog is disabled when not set, and when the
+    /// inspector is enabled because the module may be paused in the debugger.
+    pub watchdog_timeout: Option<Duration>,
+
+    /// Record metrics like completed jobs, `fetch()` requests and the V8 heap usage, see
+    /// `Metrics`. Metrics are not collected when not set.
+    pub metrics: Option<ModuleMetrics>,
+
+    /// Network access policy enforced for `fetch`, `EventSource` and `Zinnia.requestProtocol`.
+    /// All destinations are allowed by default.
+    pub net_permissions: NetPermissions,
+
+    /// The libp2p identity of the module reported by `Zinnia.peerId`. A new random identity is
+    /// created on every start when not set.
+    pub peer_keypair: Option<zinnia_libp2p::Keypair>,
+
+    /// Module name reported by `Zinnia.module.name` API.
+    pub module_name: Option<String>,
+
+    /// Module version reported by `Zinnia.module.version` API.
+    pub module_version: Option<String>,
+
+    /// Run only tests with a name matching this filter, see `zinnia test --filter`.
+    pub test_filter: Option<String>,
+
+    /// Start the V8 inspector server on this address to allow debuggers like Chrome DevTools
+    /// to attach, see `zinnia run --inspect`.
+    pub inspect: Option<SocketAddr>,
+
+    /// Wait for a debugger to attach and pause before the main module is evaluated.
+    /// Requires `inspect` to be set.
+    pub inspect_brk: bool,
+
     /// Filecoin wallet address - typically the built-in wallet in Filecoin Station
     pub wallet_address: String,
 
@@ -65,6 +132,20 @@ impl BootstrapOptions {
             agent_version,
             rng_seed: None,
             module_root,
+            import_map: None,
+            remote_modules: None,
+            loaded_files: None,
+            kv_store: None,
+            net_permissions: NetPermissions::default(),
+            max_heap_size: None,
+            watchdog_timeout: None,
+            metrics: None,
+            peer_keypair: None,
+            module_name: None,
+            module_version: None,
+            test_filter: None,
+            inspect: None,
+            inspect_brk: false,
             wallet_address: String::from("0x000000000000000000000000000000000000dEaD"),
             // Station ID must look like a public key - 88 hexadecimal characters.
             // Let's use all-zeroes value to make it easy to distinguish data reported
@@ -82,28 +163,89 @@ impl BootstrapOptions {
           "isTty": self.is_tty,
           "walletAddress": self.wallet_address,
           "stationId": self.station_id,
-          "lassieUrl": format!("http://127.0.0.1:{}/", self.lassie_daemon.port()),
+          "lassieUrl": self.lassie_url(),
           "lassieAuth": match self.lassie_daemon.access_token() {
             Some(token) => serde_json::Value::String(format!("Bearer {token}")),
             None => serde_json::Value::Null,
           },
           "zinniaVersion": self.zinnia_version,
+          "moduleName": self.module_name,
+          "moduleVersion": self.module_version,
+          "testFilter": self.test_filter,
+          "inspectFlag": self.inspect.is_some(),
           "v8Version": deno_core::v8_version(),
         });
         serde_json::to_string_pretty(&payload).unwrap()
     }
+
+    fn lassie_url(&self) -> String {
+        format!("http://127.0.0.1:{}/", self.lassie_daemon.port())
+    }
+}
+
+/// The outcome of running a JS module
+#[derive(Debug, Default)]
+pub struct ModuleOutput {
+    /// Results of tests executed by the `zinnia:test` runner, if the module defined any tests.
+    pub test_results: Option<TestResults>,
+
+    /// The exit code set via `Zinnia.exitCode` or `Zinnia.exit()`, if any.
+    pub exit_code: Option<i32>,
+}
+
+impl ModuleOutput {
+    /// The exit code to report to the operating system. A non-zero exit code set by the module
+    /// takes precedence, failed tests are reported as `1`.
+    pub fn process_exit_code(&self) -> i32 {
+        match (self.exit_code, &self.test_results) {
+            (Some(code), _) if code != 0 => code,
+            (_, Some(results)) if results.failed > 0 => 1,
+            _ => 0,
+        }
+    }
 }
 
 pub async fn run_js_module(
     module_specifier: &ModuleSpecifier,
     bootstrap_options: &BootstrapOptions,
-) -> Result<(), AnyError> {
+) -> Result<ModuleOutput, AnyError> {
     if !validate_station_id(&bootstrap_options.station_id) {
         return Err(anyhow!("Invalid station_id format"));
     }
 
     let blob_store = Arc::new(BlobStore::default());
-    let reporter = Rc::clone(&bootstrap_options.reporter);
+    let reporter: Rc<dyn Reporter> = match &bootstrap_options.metrics {
+        Some(metrics) => Rc::new(MetricsReporter::new(
+            Rc::clone(&bootstrap_options.reporter),
+            metrics.clone(),
+        )),
+        None => Rc::clone(&bootstrap_options.reporter),
+    };
+
+    let module_loader = Rc::new(
+        ZinniaModuleLoader::build(
+            bootstrap_options.module_root.clone(),
+            bootstrap_options.import_map.clone(),
+            bootstrap_options
+                .remote_modules
+                .clone()
+                .map(|config| RemoteModules::new(config, &bootstrap_options.agent_version))
+                .transpose()?,
+        )?
+        .with_loaded_files(bootstrap_options.loaded_files.clone()),
+    );
+
+    // The server must outlive the runtime, it's shut down when dropped
+    let inspector_server = bootstrap_options
+        .inspect
+        .map(|addr| InspectorServer::new(addr, "zinnia"));
+
+    let (resolver_denials_tx, resolver_denials_rx) = mpsc::unbounded_channel();
+    let http_client = create_http_client(
+        &bootstrap_options.agent_version,
+        bootstrap_options.net_permissions.clone(),
+        resolver_denials_tx,
+    )?;
 
     // Initialize a runtime instance
     let mut runtime = JsRuntime::new(RuntimeOptions {
@@ -122,37 +264,178 @@ pub async fn run_js_module(
             }),
             deno_crypto::deno_crypto::init_ops_and_esm(bootstrap_options.rng_seed),
             // Zinnia-specific APIs
-            zinnia_libp2p::zinnia_libp2p::init_ops_and_esm(zinnia_libp2p::PeerNodeConfig {
-                agent_version: bootstrap_options.agent_version.clone(),
-                ..Default::default()
-            }),
-            crate::ext::zinnia_runtime::init_ops_and_esm(reporter),
+            zinnia_libp2p::zinnia_libp2p::init_ops_and_esm::<ZinniaPermissions>(
+                zinnia_libp2p::PeerNodeConfig {
+                    agent_version: bootstrap_options.agent_version.clone(),
+                    keypair: bootstrap_options.peer_keypair.clone(),
+                    metrics: bootstrap_options.metrics.clone().map(|metrics| {
+                        Arc::new(metrics) as Arc<dyn zinnia_libp2p::PeerNodeMetrics>
+                    }),
+                    ..Default::default()
+                },
+            ),
+            crate::ext::zinnia_runtime::init_ops_and_esm(
+                ZinniaPermissions {
+                    net: bootstrap_options.net_permissions.clone(),
+                    resolver_denials: resolver_denials_rx,
+                    lassie_url: Url::parse(&bootstrap_options.lassie_url())?,
+                    module_root: module_loader.module_root().map(Path::to_path_buf),
+                    reporter,
+                },
+                bootstrap_options.kv_store.clone(),
+                bootstrap_options.metrics.clone(),
+            ),
         ],
-        inspector: false,
-        module_loader: Some(Rc::new(ZinniaModuleLoader::build(
-            bootstrap_options.module_root.clone(),
-        )?)),
+        create_params: bootstrap_options
+            .max_heap_size
+            .map(|max| v8::CreateParams::default().heap_limits(0, max)),
+        inspector: inspector_server.is_some(),
+        source_map_getter: Some(Box::new(module_loader.sources())),
+        module_loader: Some(module_loader),
         ..Default::default()
     });
 
+    // deno_fetch uses the client found in the op state instead of creating its own
+    runtime
+        .op_state()
+        .borrow_mut()
+        .put::<deno_fetch::reqwest::Client>(http_client);
+
+    let exit_notify = Rc::new(Notify::new());
+    let isolate = runtime.v8_isolate().thread_safe_handle();
+    runtime.op_state().borrow_mut().put(ExitSignal {
+        isolate,
+        notify: Rc::clone(&exit_notify),
+        requested: false,
+    });
+
+    // Terminate the module instead of letting V8 crash the whole process when the module runs
+    // out of memory
+    let heap_limit_reached = Rc::new(Cell::new(false));
+    if bootstrap_options.max_heap_size.is_some() {
+        let isolate = runtime.v8_isolate().thread_safe_handle();
+        let heap_limit_reached = Rc::clone(&heap_limit_reached);
+        let exit_notify = Rc::clone(&exit_notify);
+        runtime.add_near_heap_limit_callback(move |current_limit, _initial_limit| {
+            heap_limit_reached.set(true);
+            isolate.terminate_execution();
+            exit_notify.notify_one();
+            // Give V8 enough room to unwind the stack, it aborts the process otherwise
+            current_limit * 2
+        });
+    }
+
+    if let Some(server) = &inspector_server {
+        server.register_inspector(
+            module_specifier.to_string(),
+            &mut runtime,
+            bootstrap_options.inspect_brk,
+        );
+    }
+
     let script = format!("bootstrap.mainRuntime({})", bootstrap_options.as_json());
     runtime.execute_script(located_script_name!(), script.into())?;
 
-    // Load and run the module
-    let main_module_id = runtime.load_main_module(module_specifier, None).await?;
-    let res = runtime.mod_evaluate(main_module_id);
-    runtime.run_event_loop(false).await?;
-    res.await??;
+    let watchdog = match (bootstrap_options.watchdog_timeout, &inspector_server) {
+        (Some(timeout), None) => Some(Watchdog::start(
+            runtime.v8_isolate().thread_safe_handle(),
+            runtime.main_context(),
+            timeout,
+        )),
+        _ => None,
+    };
+
+    // Load and run the module, stop early when the module calls `Zinnia.exit()`
+    let run_module = async {
+        let main_module_id = runtime.load_main_module(module_specifier, None).await?;
+        if inspector_server.is_some() && bootstrap_options.inspect_brk {
+            runtime
+                .inspector()
+                .borrow_mut()
+                .wait_for_session_and_break_on_next_statement();
+        }
+        let res = runtime.mod_evaluate(main_module_id);
+        run_event_loop(&mut runtime, bootstrap_options.metrics.as_ref()).await?;
+        res.await??;
+        Ok::<(), AnyError>(())
+    };
+    let run_module = async {
+        match &watchdog {
+            Some(watchdog) => watchdog.watch(run_module).await,
+            None => run_module.await,
+        }
+    };
+    let result = tokio::select! {
+        biased;
+        _ = exit_notify.notified() => Ok(()),
+        result = run_module => result,
+    };
+
+    if let Some(watchdog) = &watchdog {
+        if let Some(stack) = watchdog.terminated_stack() {
+            return Err(anyhow!(
+                "The module blocked the event loop for more than {:?} and was terminated. \
+                 Last known stack: {stack}",
+                watchdog.timeout(),
+            ));
+        }
+    }
+
+    if heap_limit_reached.get() {
+        let mut stats = v8::HeapStatistics::default();
+        runtime.v8_isolate().get_heap_statistics(&mut stats);
+        return Err(anyhow!(
+            "The module ran out of memory and was terminated. Heap limit: {}, used heap size: {}, \
+             total heap size: {}.",
+            format_mib(bootstrap_options.max_heap_size.unwrap_or_default()),
+            format_mib(stats.used_heap_size()),
+            format_mib(stats.total_heap_size()),
+        ));
+    }
+
+    // The module may call `Zinnia.exit()` while its code is being evaluated. In that case,
+    // `run_module` finishes with an error about the terminated execution, which we can ignore.
+    let exit_requested = runtime.op_state().borrow().borrow::<ExitSignal>().requested;
+    if !exit_requested {
+        result?;
+    }
 
     // TODO: it would be nicer to have this exposed as another Deno op
     // and call it from the JavaScript side as part of the regular runtime shutdown
     zinnia_libp2p::shutdown(runtime.op_state()).await?;
 
-    Ok(())
+    let op_state = runtime.op_state();
+    let mut op_state = op_state.borrow_mut();
+    Ok(ModuleOutput {
+        test_results: op_state.try_take::<TestResults>(),
+        exit_code: op_state.try_take::<ExitCode>().map(|ExitCode(code)| code),
+    })
+}
+
+/// Run the event loop until there is no more work to do. When configured, sample the V8 heap usage
+/// and the event loop lag - how late the event loop processes a periodic timer.
+async fn run_event_loop(runtime: &mut JsRuntime, metrics: Option<&ModuleMetrics>) -> Result<()> {
+    let Some(metrics) = metrics else {
+        return runtime.run_event_loop(false).await;
+    };
+
+    let mut sample_interval = tokio::time::interval(METRICS_SAMPLE_INTERVAL);
+    sample_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    poll_fn(|cx| {
+        while let Poll::Ready(scheduled) = sample_interval.poll_tick(cx) {
+            metrics.event_loop_lag(scheduled.elapsed());
+            metrics.heap_statistics(runtime.v8_isolate());
+        }
+        runtime.poll_event_loop(cx, false)
+    })
+    .await
 }
 
 use deno_crypto::rand::{self, distributions::Alphanumeric, Rng};
 
+/// The default time a module can block the event loop before it's terminated.
+pub const DEFAULT_WATCHDOG_TIMEOUT: Duration = Duration::from_secs(30);
+
 const ONE_DAY: Duration = Duration::from_secs(24 * 3600);
 
 /// A baseline configuration for Lassie shared by `zinnia` and `zinniad` CLIs.
@@ -172,6 +455,10 @@ pub fn lassie_config() -> lassie::DaemonConfig {
     }
 }
 
+fn format_mib(bytes: usize) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
 fn validate_station_id(station_id: &str) -> bool {
     static RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[0-9a-fA-F]{88}$").unwrap());
     RE.is_match(station_id)
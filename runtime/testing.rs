@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// Results of tests executed by the `zinnia:test` runner in a single module.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestResults {
+    pub passed: u32,
+    pub failed: u32,
+    #[serde(default)]
+    pub skipped: u32,
+    pub duration_ms: u64,
+    /// Outcomes of individual tests in the order in which they finished. Failed `before` and
+    /// `after` hooks are reported as failed tests too.
+    #[serde(default)]
+    pub tests: Vec<TestOutcome>,
+}
+
+impl TestResults {
+    pub fn total(&self) -> u32 {
+        self.passed + self.failed
+    }
+
+    /// Add results from another test file.
+    pub fn add(&mut self, other: &TestResults) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.skipped += other.skipped;
+        self.duration_ms += other.duration_ms;
+        self.tests.extend(other.tests.iter().cloned());
+    }
+}
+
+/// The outcome of a single test.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestOutcome {
+    /// Full name of the test, including names of `describe()` groups, e.g. `fetch > GET request`.
+    pub name: String,
+    pub status: TestStatus,
+    pub duration_ms: u64,
+    /// Where the test was defined.
+    pub location: Option<TestLocation>,
+    /// The formatted error, including the stack trace, when the test failed.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestLocation {
+    /// URL of the file, e.g. `file:///home/user/project/test/smoke.js`
+    pub file_name: String,
+    pub line_number: u32,
+    pub column_number: u32,
+}
@@ -1,12 +1,15 @@
 // Integration tests making Fetch API request against a custom HTTP server
 
 use std::rc::Rc;
+use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use assert_fs::prelude::*;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
-use zinnia_runtime::{anyhow, deno_core, run_js_module, BootstrapOptions, RecordingReporter};
+use zinnia_runtime::{
+    anyhow, deno_core, run_js_module, BootstrapOptions, Metrics, NetPermissions, RecordingReporter,
+};
 
 mod helpers;
 
@@ -44,6 +47,153 @@ assertArrayIncludes(request_lines, ["user-agent: {user_agent}"]);
     Ok(())
 }
 
+#[tokio::test]
+async fn fetch_enforces_net_permissions() -> Result<()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let server_port = start_echo_server().await?;
+
+    let mod_js = assert_fs::NamedTempFile::new("fetch-permissions-test.js")?;
+    mod_js.write_str(&format!(
+        r#"
+import {{ assertRejects, assertStringIncludes, assertThrows }} from "zinnia:assert";
+const url = "http://127.0.0.1:{server_port}/echo";
+
+const fetchError = await assertRejects(() => fetch(url));
+assertStringIncludes(fetchError.message, "fetch() cannot access 127.0.0.1:{server_port}");
+
+const sseError = assertThrows(() => new EventSource(url));
+assertStringIncludes(sseError.message, "EventSource cannot access 127.0.0.1:{server_port}");
+
+// localtest.me resolves to 127.0.0.1
+const resolvedError = await assertRejects(() => fetch("http://localtest.me:{server_port}/echo"));
+assertStringIncludes(resolvedError.message, "fetch() cannot access localtest.me");
+"#,
+    ))?;
+
+    let main_module = deno_core::resolve_path(
+        &mod_js.to_string_lossy(),
+        &std::env::current_dir().context("unable to get current working directory")?,
+    )?;
+    let reporter = Rc::new(RecordingReporter::new());
+    let config = BootstrapOptions {
+        net_permissions: NetPermissions {
+            deny_private: true,
+            ..Default::default()
+        },
+        ..BootstrapOptions::new(
+            "zinnia_fetch_api_tests".into(),
+            reporter.clone(),
+            helpers::lassie_daemon(),
+            None,
+        )
+    };
+    run_js_module(&main_module, &config).await?;
+
+    let denied = |api_name: &str| {
+        format!(
+            "ERROR: Permission denied: {api_name} cannot access 127.0.0.1:{server_port}: \
+             access to private and loopback addresses is not allowed."
+        )
+    };
+    assert_eq!(
+        reporter.events.take(),
+        [
+            denied("fetch()"),
+            denied("EventSource"),
+            "ERROR: Permission denied: fetch() cannot access localtest.me: the host name resolves \
+             to 127.0.0.1, access to private and loopback addresses is not allowed."
+                .to_string(),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_cannot_read_files_outside_module_root() -> Result<()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let temp = assert_fs::TempDir::new()?;
+    let module_root = temp.child("module");
+    temp.child("secret.txt").write_str("outside")?;
+
+    let mod_js = module_root.child("mod.js");
+    mod_js.write_str(
+        r#"
+import { assertRejects, assertStringIncludes } from "zinnia:assert";
+
+const err = await assertRejects(() => fetch(new URL("../secret.txt", import.meta.url)));
+assertStringIncludes(err.message, "fetch() cannot read files outside of the module root");
+"#,
+    )?;
+
+    let main_module = deno_core::resolve_path(
+        &mod_js.to_string_lossy(),
+        &std::env::current_dir().context("unable to get current working directory")?,
+    )?;
+    let reporter = Rc::new(RecordingReporter::new());
+    let config = BootstrapOptions::new(
+        "zinnia_fetch_api_tests".into(),
+        reporter.clone(),
+        helpers::lassie_daemon(),
+        Some(module_root.to_path_buf()),
+    );
+    run_js_module(&main_module, &config).await?;
+
+    let events = reporter.events.take();
+    assert_eq!(events.len(), 1, "unexpected events: {events:?}");
+    assert!(
+        events[0].starts_with("ERROR: Permission denied: fetch() cannot read files outside"),
+        "unexpected event: {}",
+        events[0]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn fetch_records_metrics() -> Result<()> {
+    let _ = env_logger::builder().is_test(true).try_init();
+
+    let server_port = start_echo_server().await?;
+    // Bind a port and close it right away to get an address refusing connections
+    let closed_port = TcpListener::bind("127.0.0.1:0").await?.local_addr()?.port();
+
+    let mod_js = assert_fs::NamedTempFile::new("fetch-metrics-test.js")?;
+    mod_js.write_str(&format!(
+        r#"
+import {{ assertRejects }} from "zinnia:assert";
+await (await fetch("http://127.0.0.1:{server_port}/echo")).text();
+await assertRejects(() => fetch("http://127.0.0.1:{closed_port}/"));
+"#,
+    ))?;
+
+    let main_module = deno_core::resolve_path(
+        &mod_js.to_string_lossy(),
+        &std::env::current_dir().context("unable to get current working directory")?,
+    )?;
+    let metrics = Arc::new(Metrics::new());
+    let config = BootstrapOptions {
+        metrics: Some(metrics.module("fetch-test")),
+        ..BootstrapOptions::new(
+            "zinnia_fetch_api_tests".into(),
+            Rc::new(RecordingReporter::new()),
+            helpers::lassie_daemon(),
+            None,
+        )
+    };
+    run_js_module(&main_module, &config).await?;
+
+    let output = metrics.encode();
+    for line in [
+        "zinnia_fetch_requests_total{module=\"fetch-test\",status=\"200\"} 1",
+        "zinnia_fetch_requests_total{module=\"fetch-test\",status=\"error\"} 1",
+        "zinnia_heap_used_bytes{module=\"fetch-test\"}",
+    ] {
+        assert!(output.contains(line), "missing {line:?} in:\n{output}");
+    }
+    Ok(())
+}
+
 // TODO: return something that will allow the caller to stop the server
 async fn start_echo_server() -> Result<u16> {
     let listener = TcpListener::bind("127.0.0.1:0")
@@ -0,0 +1,6 @@
+// The pending timer must not prevent the module from exiting
+setTimeout(() => Zinnia.activity.error("the timer should have been abandoned"), 60_000);
+
+Zinnia.activity.info("before exit");
+Zinnia.exit(4);
+Zinnia.activity.error("Zinnia.exit() should have stopped the module");
@@ -0,0 +1,4 @@
+await new Promise((resolve) => setTimeout(resolve, 1));
+Zinnia.exitCode = 5;
+setTimeout(() => Zinnia.activity.error("the timer should have been abandoned"), 60_000);
+Zinnia.exit();
@@ -0,0 +1 @@
+Zinnia.exitCode = 3;
@@ -1,5 +1,5 @@
 import { test } from "zinnia:test";
-import { assert, assertEquals } from "zinnia:assert";
+import { assert, assertEquals, assertMatch, assertRejects } from "zinnia:assert";
 
 test("fetch", async () => {
   const res = await fetch("https://google.com/");
@@ -33,3 +33,208 @@ test("Response", async () => {
   const response = new Response();
   await response.arrayBuffer();
 });
+
+// A module exporting `add(a: i32, b: i32): i32`
+const ADD_WASM_BASE64 = "AGFzbQEAAAABBwFgAn9/AX8DAgEABwcBA2FkZAAACgkBBwAgACABags=";
+
+test("WebAssembly.instantiateStreaming", async () => {
+  const { instance } = await WebAssembly.instantiateStreaming(
+    fetch(`data:application/wasm;base64,${ADD_WASM_BASE64}`),
+  );
+  assertEquals(instance.exports.add(2, 3), 5);
+});
+
+// The `add` module stored as a single raw block, and the CAR file Lassie returns for it
+const ADD_WASM_CID = "bafkreihwd7lc6v6ecju4hqr7gyhov4ijbmo3tq4gkeigm5gurpdf3ouixi";
+const ADD_WASM_CAR_BASE64 =
+  "OqJlcm9vdHOB2CpYJQABVRIg9h/WL1fEEmnDwj82DurxCQsducOGURBmdNSLxl26iLpndmVyc2lvbgFNAVUSIPYf1i9XxBJpw8I/Ng7q8QkLHbnDhlEQZnTUi8Zduoi6AGFzbQEAAAABBwFgAn9/AX8DAgEABwcBA2FkZAAACgkBBwAgACABags=";
+
+// Retrievals from IPFS need network access, we simulate the response of `fetch("ipfs://...")`
+// to test the extraction of WebAssembly bytes from the CAR data.
+class IpfsResponse extends Response {
+  #url;
+  constructor(url, body) {
+    super(body);
+    this.#url = url;
+  }
+  get url() {
+    return this.#url;
+  }
+}
+
+function decodeBase64(encoded) {
+  return Uint8Array.from(atob(encoded), (c) => c.charCodeAt(0));
+}
+
+test("WebAssembly.instantiateStreaming extracts IPFS content from CAR", async () => {
+  const response = new IpfsResponse(`ipfs://${ADD_WASM_CID}`, decodeBase64(ADD_WASM_CAR_BASE64));
+  const { instance } = await WebAssembly.instantiateStreaming(response);
+  assertEquals(instance.exports.add(2, 3), 5);
+});
+
+test("WebAssembly.compileStreaming rejects IPFS content not matching its CID", async () => {
+  const car = decodeBase64(ADD_WASM_CAR_BASE64);
+  car[car.length - 1] ^= 0xff;
+  const err = await assertRejects(() =>
+    WebAssembly.compileStreaming(new IpfsResponse(`ipfs://${ADD_WASM_CID}`, car)),
+  );
+  assertMatch(err.message, /does not match its CID/);
+});
+
+// Encoders for the CAR, dag-pb and UnixFS formats used to build the CAR data Lassie would return
+const CODEC_RAW = 0x55;
+const CODEC_DAG_PB = 0x70;
+const UNIXFS_DIRECTORY = 1;
+const UNIXFS_FILE = 2;
+// The CIDv0 of the directory created by `createAddWasmDirectory()`
+const ADD_WASM_DIRECTORY_CID_V0 = "QmZtNXQUHDHHNstf7NiwrhgEWTs8hLhW7ZwWsR6pKnZy4L";
+
+function concatBytes(...parts) {
+  const result = new Uint8Array(parts.reduce((length, part) => length + part.length, 0));
+  let offset = 0;
+  for (const part of parts) {
+    result.set(part, offset);
+    offset += part.length;
+  }
+  return result;
+}
+
+function encodeVarint(value) {
+  const bytes = [];
+  while (value >= 0x80) {
+    bytes.push((value & 0x7f) | 0x80);
+    value >>>= 7;
+  }
+  bytes.push(value);
+  return bytes;
+}
+
+function encodeProtobufField(field, value) {
+  return typeof value === "number"
+    ? concatBytes(encodeVarint(field << 3), encodeVarint(value))
+    : concatBytes(encodeVarint((field << 3) | 2), encodeVarint(value.length), value);
+}
+
+// A block identified by a CIDv1 with the sha2-256 multihash
+async function createBlock(codec, data) {
+  const digest = new Uint8Array(await crypto.subtle.digest("SHA-256", data));
+  return { cid: concatBytes([1, codec, 0x12, digest.length], digest), data };
+}
+
+async function createUnixFsBlock(type, children, names = []) {
+  const links = children.map((child, index) =>
+    encodeProtobufField(
+      2,
+      concatBytes(
+        encodeProtobufField(1, child.cid),
+        encodeProtobufField(2, new TextEncoder().encode(names[index] ?? "")),
+        encodeProtobufField(3, child.data.length),
+      ),
+    ),
+  );
+  const data = encodeProtobufField(1, encodeProtobufField(1, type));
+  return createBlock(CODEC_DAG_PB, concatBytes(...links, data));
+}
+
+function createCar(blocks) {
+  // DAG-CBOR encoding of `{ roots: [], version: 1 }`, Zinnia doesn't read the header
+  const header = concatBytes(
+    [0xa2, 0x65],
+    new TextEncoder().encode("roots"),
+    [0x80, 0x67],
+    new TextEncoder().encode("version"),
+    [0x01],
+  );
+  const sections = blocks.map(({ cid, data }) =>
+    concatBytes(encodeVarint(cid.length + data.length), cid, data),
+  );
+  return concatBytes(encodeVarint(header.length), header, ...sections);
+}
+
+// Format the CID using the multibase `base32`
+function formatCid(cid) {
+  const alphabet = "abcdefghijklmnopqrstuvwxyz234567";
+  let output = "b";
+  let value = 0;
+  let bits = 0;
+  for (const byte of cid) {
+    value = (value << 8) | byte;
+    bits += 8;
+    while (bits >= 5) {
+      output += alphabet[(value >>> (bits - 5)) & 31];
+      bits -= 5;
+    }
+    value &= (1 << bits) - 1;
+  }
+  return bits > 0 ? output + alphabet[(value << (5 - bits)) & 31] : output;
+}
+
+// The `add` module extended with a custom section, so that it doesn't fit into a single block
+function createLargeAddWasm() {
+  const name = new TextEncoder().encode("padding");
+  const payload = new Uint8Array(1536 * 1024);
+  return concatBytes(
+    decodeBase64(ADD_WASM_BASE64),
+    [0, ...encodeVarint(1 + name.length + payload.length), name.length],
+    name,
+    payload,
+  );
+}
+
+test("WebAssembly.instantiateStreaming extracts UnixFS files split into blocks", async () => {
+  const wasm = createLargeAddWasm();
+  const leaves = [];
+  for (let offset = 0; offset < wasm.length; offset += 256 * 1024) {
+    leaves.push(await createBlock(CODEC_RAW, wasm.subarray(offset, offset + 256 * 1024)));
+  }
+  const file = await createUnixFsBlock(UNIXFS_FILE, leaves);
+
+  const response = new IpfsResponse(`ipfs://${formatCid(file.cid)}`, createCar([file, ...leaves]));
+  const { instance } = await WebAssembly.instantiateStreaming(response);
+  assertEquals(instance.exports.add(2, 3), 5);
+});
+
+// A UnixFS directory with the `add` module stored as `add.wasm`
+async function createAddWasmDirectory() {
+  const file = await createBlock(CODEC_RAW, decodeBase64(ADD_WASM_BASE64));
+  const directory = await createUnixFsBlock(UNIXFS_DIRECTORY, [file], ["add.wasm"]);
+  return { directory, car: createCar([directory, file]) };
+}
+
+test("WebAssembly.instantiateStreaming resolves paths in UnixFS directories", async () => {
+  const { directory, car } = await createAddWasmDirectory();
+  const response = new IpfsResponse(`ipfs://${formatCid(directory.cid)}/add.wasm`, car);
+  const { instance } = await WebAssembly.instantiateStreaming(response);
+  assertEquals(instance.exports.add(2, 3), 5);
+});
+
+test("WebAssembly.instantiateStreaming supports CIDv0", async () => {
+  const { car } = await createAddWasmDirectory();
+  const response = new IpfsResponse(`ipfs://${ADD_WASM_DIRECTORY_CID_V0}/add.wasm`, car);
+  const { instance } = await WebAssembly.instantiateStreaming(response);
+  assertEquals(instance.exports.add(2, 3), 5);
+});
+
+test("WebAssembly.compileStreaming rejects paths not found in UnixFS directories", async () => {
+  const { directory, car } = await createAddWasmDirectory();
+  const response = new IpfsResponse(`ipfs://${formatCid(directory.cid)}/sub.wasm`, car);
+  const err = await assertRejects(() => WebAssembly.compileStreaming(response));
+  assertMatch(err.message, /Cannot resolve 'sub.wasm': no such entry/);
+});
+
+test("WebAssembly.compileStreaming rejects CAR data with missing blocks", async () => {
+  const { directory } = await createAddWasmDirectory();
+  const response = new IpfsResponse(
+    `ipfs://${formatCid(directory.cid)}/add.wasm`,
+    createCar([directory]),
+  );
+  const err = await assertRejects(() => WebAssembly.compileStreaming(response));
+  assertMatch(err.message, /does not contain the block/);
+});
+
+test("WebAssembly.compileStreaming rejects responses with invalid content type", async () => {
+  const err = await assertRejects(() =>
+    WebAssembly.compileStreaming(fetch(`data:text/plain;base64,${ADD_WASM_BASE64}`)),
+  );
+  assertMatch(err.message, /Invalid WebAssembly content type/);
+});
@@ -83,6 +83,17 @@ test("rejects user-provided Authorization header", async () => {
   assertMatch(error.message, /authorization/i);
 });
 
+test("WebAssembly.compileStreaming extracts UnixFS files retrieved from IPFS", async () => {
+  // The CID points to a text file, the extraction succeeds and the compilation fails
+  await assertRejects(
+    () =>
+      WebAssembly.compileStreaming(
+        fetch("ipfs://bafybeib36krhffuh3cupjml4re2wfxldredkir5wti3dttulyemre7xkni"),
+      ),
+    WebAssembly.CompileError,
+  );
+});
+
 /**
  * @param {Response} response Fetch API response
  */
@@ -0,0 +1,76 @@
+import { test } from "zinnia:test";
+import { assertEquals, assertRejects, assertStrictEquals, assertThrows } from "zinnia:assert";
+
+// The runtime tests configure a fresh store with the quota of 1024 bytes for each test file
+
+test("Zinnia.kv get returns undefined for missing keys", async () => {
+  assertStrictEquals(await Zinnia.kv.get("missing"), undefined);
+});
+
+test("Zinnia.kv set and get", async () => {
+  await Zinnia.kv.set("config", { retries: 3, hosts: ["a", "b"] });
+  assertEquals(await Zinnia.kv.get("config"), { retries: 3, hosts: ["a", "b"] });
+
+  await Zinnia.kv.set("config", null);
+  assertStrictEquals(await Zinnia.kv.get("config"), null);
+});
+
+test("Zinnia.kv delete", async () => {
+  await Zinnia.kv.set("to-delete", 1);
+  await Zinnia.kv.delete("to-delete");
+  assertStrictEquals(await Zinnia.kv.get("to-delete"), undefined);
+
+  // Deleting a missing key is not an error
+  await Zinnia.kv.delete("to-delete");
+});
+
+test("Zinnia.kv list by prefix", async () => {
+  await Zinnia.kv.set("job:2", "second");
+  await Zinnia.kv.set("job:1", "first");
+  await Zinnia.kv.set("jobs", 2);
+
+  assertEquals(await Zinnia.kv.list({ prefix: "job:" }), [
+    { key: "job:1", value: "first" },
+    { key: "job:2", value: "second" },
+  ]);
+  assertEquals(await Zinnia.kv.list({ prefix: "job:", limit: 1 }), [
+    { key: "job:1", value: "first" },
+  ]);
+});
+
+test("Zinnia.kv atomic commits", async () => {
+  await Zinnia.kv.set("balance:alice", 10);
+  await Zinnia.kv
+    .atomic()
+    .set("balance:alice", 5)
+    .set("balance:bob", 5)
+    .delete("jobs")
+    .commit();
+
+  assertStrictEquals(await Zinnia.kv.get("balance:alice"), 5);
+  assertStrictEquals(await Zinnia.kv.get("balance:bob"), 5);
+  assertStrictEquals(await Zinnia.kv.get("jobs"), undefined);
+});
+
+test("Zinnia.kv rejects commits exceeding the quota", async () => {
+  await assertRejects(
+    () =>
+      Zinnia.kv
+        .atomic()
+        .set("small", 1)
+        .set("large", "x".repeat(2000))
+        .commit(),
+    Error,
+    "quota exceeded",
+  );
+
+  // No changes were applied
+  assertStrictEquals(await Zinnia.kv.get("small"), undefined);
+});
+
+test("Zinnia.kv validates keys and values", async () => {
+  await assertRejects(() => Zinnia.kv.get(1), TypeError, "Key must be a string");
+  await assertRejects(() => Zinnia.kv.set("fn", () => {}), TypeError, "serializable to JSON");
+  await assertRejects(() => Zinnia.kv.set("", 1), Error, "must not be empty");
+  assertThrows(() => Zinnia.kv.atomic().set(undefined, 1), TypeError);
+});
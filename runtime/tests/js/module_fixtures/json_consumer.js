@@ -0,0 +1,3 @@
+import config from "./config.json" with { type: "json" };
+
+export const name = config.name;
@@ -0,0 +1,11 @@
+interface Greeting {
+  name: string;
+}
+
+export function greet({ name }: Greeting): string {
+  return `Hello, ${name}!`;
+}
+
+export function fail(): never {
+  throw new Error("typed failure");
+}
@@ -1,5 +1,5 @@
 import { test } from "zinnia:test";
-import { assertEquals, assertMatch, assertRejects } from "zinnia:assert";
+import { assertEquals, assertMatch, assertRejects, assertThrows } from "zinnia:assert";
 
 test("dynamically import file next to the main module file", async () => {
   const { KEY } = await import("./empty_module.js");
@@ -20,3 +20,45 @@ test("cannot import files over http", async () => {
   let err = await assertRejects(() => import("https://deno.land/std@0.181.0/version.ts"));
   assertMatch(err.message, /Zinnia can import local modules only/);
 });
+
+test("import TypeScript modules", async () => {
+  const { greet } = await import("./module_fixtures/typed.ts");
+  assertEquals(greet({ name: "Zinnia" }), "Hello, Zinnia!");
+});
+
+test("stack traces point to the original TypeScript code", async () => {
+  const { fail } = await import("./module_fixtures/typed.ts");
+  const err = assertThrows(() => fail());
+  assertMatch(err.stack, /module_fixtures\/typed\.ts:10:9/);
+});
+
+test("statically import JSON modules", async () => {
+  const { name } = await import("./module_fixtures/json_consumer.js");
+  assertEquals(name, "zinnia");
+});
+
+test("dynamically import JSON modules", async () => {
+  const { default: config } = await import("./module_fixtures/config.json", {
+    with: { type: "json" },
+  });
+  assertEquals(config, { name: "zinnia", retries: 3 });
+});
+
+test("cannot import JSON modules without the type attribute", async () => {
+  const err = await assertRejects(() => import("./module_fixtures/config.json"));
+  assertMatch(err.message, /Expected a "JavaScriptOrWasm" module but loaded a "JSON" module/);
+});
+
+test("cannot import JavaScript modules as JSON", async () => {
+  const err = await assertRejects(() =>
+    import("./module_fixtures/log.js", { with: { type: "json" } }),
+  );
+  assertMatch(err.message, /Expected a "JSON" module but loaded a "JavaScriptOrWasm" module/);
+});
+
+test("cannot import modules with unsupported type attributes", async () => {
+  const err = await assertRejects(() =>
+    import("./module_fixtures/config.json", { with: { type: "css" } }),
+  );
+  assertMatch(err.message, /"css" is not a valid module type/);
+});
@@ -0,0 +1,5 @@
+// Keep allocating memory until the runtime terminates the module
+const leak = [];
+while (true) {
+  leak.push(new Array(100_000).fill("leak"));
+}
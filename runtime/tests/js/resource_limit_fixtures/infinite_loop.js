@@ -0,0 +1,6 @@
+// Block the event loop until the watchdog terminates the module
+function spin() {
+  while (true) {}
+}
+
+spin();
@@ -1,5 +1,5 @@
 import { test } from "zinnia:test";
-import { assertStrictEquals } from "zinnia:assert";
+import { assert, assertStrictEquals, assertThrows } from "zinnia:assert";
 
 test("Zinnia.walletAddress", () => {
   // Runtime JS tests are executed with the default configuration
@@ -11,3 +11,21 @@ test("Zinnia.walletAddress", () => {
 test("Zinnia.stationId", () => {
   assertStrictEquals(Zinnia.stationId, "0".repeat(88));
 });
+
+test("Zinnia.module", () => {
+  // The runtime tests don't configure any module name or version
+  assertStrictEquals(Zinnia.module.name, null);
+  assertStrictEquals(Zinnia.module.version, null);
+  assert(Object.isFrozen(Zinnia.module), "Zinnia.module should be frozen");
+});
+
+test("Zinnia.exitCode", () => {
+  assertStrictEquals(Zinnia.exitCode, 0);
+  assertThrows(() => (Zinnia.exitCode = 1.5), TypeError);
+  assertThrows(() => (Zinnia.exitCode = 256), TypeError);
+  assertThrows(() => (Zinnia.exitCode = "1"), TypeError);
+  assertStrictEquals(Zinnia.exitCode, 0);
+
+  Zinnia.exitCode = 0;
+  assertStrictEquals(Zinnia.exitCode, 0);
+});
@@ -7,4 +7,13 @@ console.log("console.log");
 console.error("console.error");
 Zinnia.activity.info("activity.info");
 Zinnia.activity.error("activity.error");
+Zinnia.activity.warn("activity.warn");
+Zinnia.activity.warn({
+  message: "activity.warn with data",
+  code: "SLOW_RETRIEVAL",
+  details: { cid: "bafy", duration: 1500 },
+});
+Zinnia.activity.error({ message: "activity.error with code", code: "TIMEOUT" });
+Zinnia.activity.error(new Error("activity.error with Error"));
+Zinnia.activity.error(new DOMException("activity.error with DOMException", "AbortError"));
 Zinnia.jobCompleted();
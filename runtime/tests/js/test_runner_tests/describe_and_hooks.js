@@ -0,0 +1,24 @@
+import { after, afterEach, before, beforeEach, describe, test } from "zinnia:test";
+
+before(() => console.log("before (root)"));
+after(() => console.log("after (root)"));
+beforeEach(() => console.log("beforeEach (root)"));
+
+test("top-level test", () => {
+  // pass
+});
+
+describe("a group", () => {
+  before(() => console.log("before (group)"));
+  afterEach(() => console.log("afterEach (group)"));
+
+  test("first test", () => {
+    // pass
+  });
+
+  describe("a nested group", () => {
+    test("nested test", async () => {
+      await new Promise((resolve) => setTimeout(resolve, 1));
+    });
+  });
+});
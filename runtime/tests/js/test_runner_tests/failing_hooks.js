@@ -0,0 +1,17 @@
+import { before, beforeEach, describe, test } from "zinnia:test";
+
+describe("a group with a failing before hook", () => {
+  before(() => {
+    throw new Error("before failed");
+  });
+  test("is not executed", () => {});
+});
+
+describe("a group with a failing beforeEach hook", () => {
+  beforeEach(() => {
+    throw new Error("beforeEach failed");
+  });
+  test("fails", () => {});
+});
+
+test("passes", () => {});
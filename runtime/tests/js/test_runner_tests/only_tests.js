@@ -0,0 +1,21 @@
+import { describe, test } from "zinnia:test";
+
+test("ignores tests without only", () => {
+  throw new Error("should have been ignored");
+});
+
+test.only("runs tests marked with only", () => {
+  // pass
+});
+
+describe.only("a group marked with only", () => {
+  test("runs all tests in the group", () => {
+    // pass
+  });
+});
+
+describe("another group", () => {
+  test("ignores tests in other groups", () => {
+    throw new Error("should have been ignored");
+  });
+});
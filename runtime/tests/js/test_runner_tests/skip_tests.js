@@ -0,0 +1,19 @@
+import { describe, test } from "zinnia:test";
+
+test("runs this test", () => {
+  // pass
+});
+
+test.skip("skips this test", () => {
+  throw new Error("should have been skipped");
+});
+
+test("skips tests with the skip option", { skip: true }, () => {
+  throw new Error("should have been skipped");
+});
+
+describe.skip("skipped group", () => {
+  test("skips tests in skipped groups", () => {
+    throw new Error("should have been skipped");
+  });
+});
@@ -0,0 +1,11 @@
+import { describe, test } from "zinnia:test";
+
+test("passes before the timeout", { timeout: 1000 }, async () => {
+  await new Promise((resolve) => setTimeout(resolve, 1));
+});
+
+test("fails when the test takes too long", { timeout: 50 }, () => new Promise(() => {}));
+
+describe("a group with a timeout", { timeout: 50 }, () => {
+  test("inherits the timeout", () => new Promise(() => {}));
+});
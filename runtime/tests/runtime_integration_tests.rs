@@ -5,12 +5,15 @@
 //   deno run runtime/tests/js/timers_tests.js
 use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context};
-use deno_core::error::JsError;
 use deno_core::ModuleSpecifier;
 use zinnia_runtime::RecordingReporter;
-use zinnia_runtime::{anyhow, deno_core, run_js_module, AnyError, BootstrapOptions};
+use zinnia_runtime::{
+    anyhow, deno_core, run_js_module, AnyError, BootstrapOptions, KvStoreConfig, ModuleOutput,
+    TestResults,
+};
 
 use pretty_assertions::assert_eq;
 
@@ -20,22 +23,16 @@ macro_rules! js_tests(
     ( $name:ident ) => {
     #[tokio::test]
     async fn $name() -> Result<(), AnyError> {
-        let (_activities, run_error) = run_js_test_file(&format!("{}.js", stringify!($name))).await?;
-        if let Some(err) = run_error {
-            return Err(err);
-        }
-
-        Ok(())
+        let (_activities, run_result) = run_js_test_file(&format!("{}.js", stringify!($name))).await?;
+        check_no_failed_tests(&run_result?)
     }
     };
 
     ( $name:ident check_activity) => {
     #[tokio::test]
     async fn $name() -> Result<(), AnyError> {
-        let (activities, run_error) = run_js_test_file(&format!("{}.js", stringify!($name))).await?;
-        if let Some(err) = run_error {
-            return Err(err);
-        }
+        let (activities, run_result) = run_js_test_file(&format!("{}.js", stringify!($name))).await?;
+        check_no_failed_tests(&run_result?)?;
 
         let actual_output = format_recorded_activities(&activities);
         let expected_output = load_activity_log(&format!("{}.activity.txt", stringify!($name)));
@@ -49,10 +46,9 @@ macro_rules! test_runner_tests(
     ( $name:ident ) => {
     #[tokio::test]
     async fn $name() -> Result<(), AnyError> {
-        let (activities, run_error) = run_js_test_file(&format!("test_runner_tests/{}.js", stringify!($name))).await?;
-        if let Some(err) = run_error {
-            return Err(err);
-        }
+        let (activities, run_result) = run_js_test_file(&format!("test_runner_tests/{}.js", stringify!($name))).await?;
+        let test_results = get_test_results(run_result?)?;
+        assert_eq!(test_results.failed, 0, "failed tests");
 
         let actual_output = format_test_activities(&activities);
         let expected_output = load_activity_log(&format!("test_runner_tests/{}.activity.txt", stringify!($name)));
@@ -65,11 +61,10 @@ macro_rules! test_runner_tests(
     ( $name:ident expect_failure ) => {
         #[tokio::test]
         async fn $name() -> Result<(), AnyError> {
-            let (activities, run_error) = run_js_test_file(&format!("test_runner_tests/{}.js", stringify!($name))).await?;
-
-            match run_error {
-                None => return Err(anyhow!("The test runner was expected to throw an error. Success was reported instead.")),
-                Some(err) => assert_test_runner_failure(err),
+            let (activities, run_result) = run_js_test_file(&format!("test_runner_tests/{}.js", stringify!($name))).await?;
+            let test_results = get_test_results(run_result?)?;
+            if test_results.failed == 0 {
+                return Err(anyhow!("The test runner was expected to report failed tests. Success was reported instead."));
             }
 
             let actual_output = format_test_activities(&activities);
@@ -91,12 +86,89 @@ js_tests!(station_apis_tests);
 js_tests!(station_reporting_tests check_activity);
 js_tests!(module_loader_tests);
 js_tests!(ipfs_retrieval_tests);
+js_tests!(kv_tests);
 
 test_runner_tests!(passing_tests);
 test_runner_tests!(failing_tests expect_failure);
+test_runner_tests!(describe_and_hooks);
+test_runner_tests!(skip_tests);
+test_runner_tests!(only_tests);
+test_runner_tests!(timeouts expect_failure);
+test_runner_tests!(failing_hooks expect_failure);
+
+#[tokio::test]
+async fn exit_code_is_reported() -> Result<(), AnyError> {
+    let (_, run_result) = run_js_test_file("exit_code_fixtures/set_exit_code.js").await?;
+    assert_eq!(run_result?.exit_code, Some(3));
+    Ok(())
+}
+
+#[tokio::test]
+async fn exit_stops_the_module() -> Result<(), AnyError> {
+    let (activities, run_result) = run_js_test_file("exit_code_fixtures/exit.js").await?;
+    assert_eq!(run_result?.exit_code, Some(4));
+    assert_eq!(activities, ["INFO: before exit"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn exit_from_async_code_uses_exit_code() -> Result<(), AnyError> {
+    let (activities, run_result) =
+        run_js_test_file("exit_code_fixtures/exit_from_async_code.js").await?;
+    assert_eq!(run_result?.exit_code, Some(5));
+    assert_eq!(activities, Vec::<String>::new());
+    Ok(())
+}
+
+#[tokio::test]
+async fn module_exceeding_heap_limit_is_terminated() -> Result<(), AnyError> {
+    let (_, run_result) =
+        run_js_test_file_with_options("resource_limit_fixtures/heap_leak.js", |options| {
+            options.max_heap_size = Some(32 * 1024 * 1024);
+        })
+        .await?;
+    let err = run_result.expect_err("the module should be terminated");
+    let msg = err.to_string();
+    assert!(
+        msg.starts_with("The module ran out of memory and was terminated. Heap limit: 32.0 MiB"),
+        "unexpected error: {msg}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn module_blocking_event_loop_is_terminated() -> Result<(), AnyError> {
+    let (_, run_result) =
+        run_js_test_file_with_options("resource_limit_fixtures/infinite_loop.js", |options| {
+            options.watchdog_timeout = Some(Duration::from_millis(200));
+        })
+        .await?;
+    let err = run_result.expect_err("the module should be terminated");
+    let msg = err.to_string();
+    assert!(
+        msg.starts_with(
+            "The module blocked the event loop for more than 200ms and was terminated."
+        ),
+        "unexpected error: {msg}"
+    );
+    assert!(
+        msg.contains("spin (") && msg.contains("infinite_loop.js:"),
+        "the error should include the stack: {msg}"
+    );
+    Ok(())
+}
 
 // Run all tests in a single JS file
-async fn run_js_test_file(name: &str) -> Result<(Vec<String>, Option<AnyError>), AnyError> {
+async fn run_js_test_file(
+    name: &str,
+) -> Result<(Vec<String>, Result<ModuleOutput, AnyError>), AnyError> {
+    run_js_test_file_with_options(name, |_| {}).await
+}
+
+async fn run_js_test_file_with_options(
+    name: &str,
+    configure: impl FnOnce(&mut BootstrapOptions),
+) -> Result<(Vec<String>, Result<ModuleOutput, AnyError>), AnyError> {
     let _ = env_logger::builder().is_test(true).try_init();
 
     let mut full_path = get_base_dir();
@@ -107,21 +179,38 @@ async fn run_js_test_file(name: &str) -> Result<(Vec<String>, Option<AnyError>),
         &std::env::current_dir().context("unable to get current working directory")?,
     )?;
     let reporter = Rc::new(RecordingReporter::new());
-    let config = BootstrapOptions::new(
-        format!("zinnia_runtime_tests/{}", env!("CARGO_PKG_VERSION")),
-        reporter.clone(),
-        helpers::lassie_daemon(),
-        None,
-    );
+    let kv_dir = assert_fs::TempDir::new()?;
+    let mut config = BootstrapOptions {
+        kv_store: Some(KvStoreConfig {
+            dir: kv_dir.path().to_path_buf(),
+            quota: 1024,
+        }),
+        ..BootstrapOptions::new(
+            format!("zinnia_runtime_tests/{}", env!("CARGO_PKG_VERSION")),
+            reporter.clone(),
+            helpers::lassie_daemon(),
+            None,
+        )
+    };
+    configure(&mut config);
     let run_result = run_js_module(&main_module, &config).await;
     let events = reporter.events.take();
+    Ok((events, run_result))
+}
 
-    match run_result {
-        Ok(()) => Ok((events, None)),
-        Err(err) => Ok((events, Some(err))),
+fn check_no_failed_tests(output: &ModuleOutput) -> Result<(), AnyError> {
+    match &output.test_results {
+        Some(results) if results.failed > 0 => Err(anyhow!("{} test(s) failed", results.failed)),
+        _ => Ok(()),
     }
 }
 
+fn get_test_results(output: ModuleOutput) -> Result<TestResults, AnyError> {
+    output
+        .test_results
+        .ok_or_else(|| anyhow!("The test runner did not report any results."))
+}
+
 fn get_base_dir() -> PathBuf {
     let mut base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     base_dir.push("tests");
@@ -188,17 +277,3 @@ fn format_test_activities(events: &[String]) -> String {
         .collect::<Vec<String>>()
         .join("")
 }
-
-fn assert_test_runner_failure(error: AnyError) {
-    if let Some(e) = error.downcast_ref::<JsError>() {
-        assert_eq!(
-            e.name,
-            Some("[some tests failed]\u{001b}[2K\x0D".to_string()),
-            "error.name"
-        );
-        assert_eq!(e.message, None, "error.message");
-        assert_eq!(e.stack, None, "error.stack");
-    } else {
-        panic!("The test runner threw unexpected error: {}", error);
-    }
-}
@@ -0,0 +1,185 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use deno_ast::{EmitOptions, MediaType, ParseParams, SourceTextInfo};
+use deno_core::anyhow::{anyhow, Result};
+use deno_core::{ModuleSpecifier, ModuleType, SourceMapGetter};
+
+/// Prepare the source code of a module for execution.
+///
+/// TypeScript modules are transpiled to JavaScript by removing type annotations, we don't perform
+/// any type checking. JavaScript and JSON modules are returned unchanged.
+///
+/// Returns the type of the module together with the code to execute. JSON modules can be imported
+/// only with the `type: "json"` import attribute, V8 rejects imports where the attribute does not
+/// match the module type.
+///
+/// The original source code and the source map are recorded in `sources`, so that we can report
+/// errors with locations and source lines pointing to the original TypeScript code.
+pub fn prepare_module_source(
+    specifier: &ModuleSpecifier,
+    code: String,
+    sources: &ModuleSources,
+) -> Result<(ModuleType, String)> {
+    let media_type = MediaType::from_specifier(specifier);
+    match media_type {
+        MediaType::TypeScript
+        | MediaType::Mts
+        | MediaType::Cts
+        | MediaType::Dts
+        | MediaType::Dmts
+        | MediaType::Dcts => {
+            let parsed = deno_ast::parse_module(ParseParams {
+                specifier: specifier.to_string(),
+                text_info: SourceTextInfo::from_string(code.clone()),
+                media_type,
+                capture_tokens: false,
+                scope_analysis: false,
+                maybe_syntax: None,
+            })?;
+            let transpiled = parsed.transpile(&EmitOptions {
+                inline_source_map: false,
+                inline_sources: false,
+                source_map: true,
+                ..Default::default()
+            })?;
+            sources.insert(
+                specifier,
+                code,
+                transpiled.source_map.map(String::into_bytes),
+            );
+            Ok((ModuleType::JavaScript, transpiled.text))
+        }
+        MediaType::Jsx | MediaType::Tsx => Err(anyhow!(
+            "JSX is not supported. Please convert the module to a .js or .ts file."
+        )),
+        MediaType::Json => {
+            sources.insert(specifier, code.clone(), None);
+            Ok((ModuleType::Json, code))
+        }
+        _ => {
+            sources.insert(specifier, code.clone(), None);
+            Ok((ModuleType::JavaScript, code))
+        }
+    }
+}
+
+struct StoredSource {
+    original_code: String,
+    source_map: Option<Vec<u8>>,
+}
+
+/// Original source code and source maps of all modules loaded by the runtime.
+///
+/// This is shared between the module loader (which records the sources) and the JS runtime
+/// (which uses them to apply source maps to stack traces).
+#[derive(Clone, Default)]
+pub struct ModuleSources {
+    sources: Rc<RefCell<HashMap<String, StoredSource>>>,
+}
+
+impl ModuleSources {
+    fn insert(
+        &self,
+        specifier: &ModuleSpecifier,
+        original_code: String,
+        source_map: Option<Vec<u8>>,
+    ) {
+        self.sources.borrow_mut().insert(
+            specifier.to_string(),
+            StoredSource {
+                original_code,
+                source_map,
+            },
+        );
+    }
+}
+
+impl SourceMapGetter for ModuleSources {
+    fn get_source_map(&self, file_name: &str) -> Option<Vec<u8>> {
+        self.sources
+            .borrow()
+            .get(file_name)
+            .and_then(|s| s.source_map.clone())
+    }
+
+    fn get_source_line(&self, file_name: &str, line_number: usize) -> Option<String> {
+        self.sources
+            .borrow()
+            .get(file_name)
+            .and_then(|s| s.original_code.split('\n').nth(line_number))
+            .map(|line| line.trim_end_matches('\r').to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn strips_types_from_typescript() {
+        let specifier = ModuleSpecifier::parse("file:///project/mod.ts").unwrap();
+        let sources = ModuleSources::default();
+        let (module_type, code) = prepare_module_source(
+            &specifier,
+            "const answer: number = 42;\nexport { answer };\n".into(),
+            &sources,
+        )
+        .unwrap();
+
+        assert_eq!(module_type, ModuleType::JavaScript);
+        assert!(
+            code.starts_with("const answer = 42;"),
+            "unexpected transpiled code:\n{code}"
+        );
+        assert!(
+            sources.get_source_map(specifier.as_str()).is_some(),
+            "source map should be recorded"
+        );
+        assert_eq!(
+            sources.get_source_line(specifier.as_str(), 0).as_deref(),
+            Some("const answer: number = 42;")
+        );
+    }
+
+    #[test]
+    fn returns_javascript_unchanged() {
+        let specifier = ModuleSpecifier::parse("file:///project/mod.js").unwrap();
+        let sources = ModuleSources::default();
+        let (module_type, code) =
+            prepare_module_source(&specifier, "export const answer = 42;\r\n".into(), &sources)
+                .unwrap();
+
+        assert_eq!(module_type, ModuleType::JavaScript);
+        assert_eq!(code, "export const answer = 42;\r\n");
+        assert_eq!(sources.get_source_map(specifier.as_str()), None);
+        assert_eq!(
+            sources.get_source_line(specifier.as_str(), 0).as_deref(),
+            Some("export const answer = 42;")
+        );
+    }
+
+    #[test]
+    fn loads_json_as_json_module() {
+        let specifier = ModuleSpecifier::parse("file:///project/config.json").unwrap();
+        let (module_type, code) =
+            prepare_module_source(&specifier, "{ \"answer\": 42 }".into(), &Default::default())
+                .unwrap();
+
+        assert_eq!(module_type, ModuleType::Json);
+        assert_eq!(code, "{ \"answer\": 42 }");
+    }
+
+    #[test]
+    fn rejects_jsx() {
+        let specifier = ModuleSpecifier::parse("file:///project/component.tsx").unwrap();
+        let result = prepare_module_source(
+            &specifier,
+            "export default <div />;".into(),
+            &Default::default(),
+        );
+        assert!(result.is_err(), "TSX modules should be rejected");
+    }
+}
@@ -1,3 +1,4 @@
 pub mod cli_tools;
 pub mod colors;
 pub mod fmt_errors;
+pub mod inspector_server;
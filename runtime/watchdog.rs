@@ -0,0 +1,212 @@
+use std::ffi::c_void;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use deno_core::v8;
+
+/// How many stack frames to include in the report about a blocked module.
+const STACK_FRAME_LIMIT: usize = 10;
+
+/// How long to wait for the isolate to capture its stack before terminating it anyway.
+const STACK_CAPTURE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Terminates JavaScript code that does not yield to the event loop for too long, e.g.
+/// `while (true) {}`. Such code blocks the thread running the module, including all timers and
+/// network requests.
+///
+/// The runtime tells the watchdog when it starts and stops polling the module (see `watch()`),
+/// and the watchdog thread checks how long the current poll takes. When the poll takes longer than
+/// the timeout, the watchdog interrupts the isolate to capture the current stack and terminates
+/// the execution.
+pub struct Watchdog {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+    /// Owned by the watchdog, passed to the interrupt callback running on the isolate thread.
+    interrupt_data: *mut InterruptData,
+}
+
+struct Shared {
+    timeout: Duration,
+    started: Instant,
+    /// When the current poll started, in milliseconds since `started` plus one. Zero when the
+    /// runtime is not polling the module.
+    busy_since: AtomicU64,
+    stopped: AtomicBool,
+    interrupt_requested: AtomicBool,
+    interrupt_handled: AtomicBool,
+    /// The last known stack of the module, set when the watchdog terminated the execution.
+    stack: Mutex<Option<String>>,
+}
+
+struct InterruptData {
+    context: v8::Global<v8::Context>,
+    shared: Arc<Shared>,
+}
+
+impl Watchdog {
+    /// Start the watchdog thread. The `context` is used to capture the stack of the module.
+    pub fn start(
+        isolate: v8::IsolateHandle,
+        context: v8::Global<v8::Context>,
+        timeout: Duration,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            timeout,
+            started: Instant::now(),
+            busy_since: AtomicU64::new(0),
+            stopped: AtomicBool::new(false),
+            interrupt_requested: AtomicBool::new(false),
+            interrupt_handled: AtomicBool::new(false),
+            stack: Mutex::new(None),
+        });
+        let interrupt_data = Box::into_raw(Box::new(InterruptData {
+            context,
+            shared: Arc::clone(&shared),
+        }));
+
+        // Raw pointers are not `Send`, the pointer is dereferenced on the isolate thread only
+        let data_addr = interrupt_data as usize;
+        let thread_shared = Arc::clone(&shared);
+        let thread = std::thread::Builder::new()
+            .name("zinnia-watchdog".into())
+            .spawn(move || run_watchdog(&thread_shared, &isolate, data_addr))
+            .expect("cannot spawn the watchdog thread");
+
+        Self {
+            shared,
+            thread: Some(thread),
+            interrupt_data,
+        }
+    }
+
+    /// Poll the future while tracking how long each poll takes.
+    pub async fn watch<T>(&self, fut: impl Future<Output = T>) -> T {
+        let mut fut = std::pin::pin!(fut);
+        std::future::poll_fn(|cx| {
+            let now = self.shared.started.elapsed().as_millis() as u64;
+            self.shared.busy_since.store(now + 1, Ordering::SeqCst);
+            let result = fut.as_mut().poll(cx);
+            self.shared.busy_since.store(0, Ordering::SeqCst);
+            result
+        })
+        .await
+    }
+
+    /// The timeout configured for this watchdog.
+    pub fn timeout(&self) -> Duration {
+        self.shared.timeout
+    }
+
+    /// When the watchdog terminated the execution, return the last known stack of the module.
+    pub fn terminated_stack(&self) -> Option<String> {
+        self.shared.stack.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+
+        // Keep the data alive when the isolate may still run the interrupt callback. The callback
+        // runs on the isolate thread, it cannot run concurrently with this code.
+        let interrupt_pending = self.shared.interrupt_requested.load(Ordering::SeqCst)
+            && !self.shared.interrupt_handled.load(Ordering::SeqCst);
+        if !interrupt_pending {
+            // SAFETY: the pointer was created by `Box::into_raw()` and the callback won't run
+            drop(unsafe { Box::from_raw(self.interrupt_data) });
+        }
+    }
+}
+
+fn run_watchdog(shared: &Shared, isolate: &v8::IsolateHandle, interrupt_data: usize) {
+    let check_interval =
+        (shared.timeout / 4).clamp(Duration::from_millis(10), Duration::from_secs(1));
+    loop {
+        std::thread::park_timeout(check_interval);
+        if shared.stopped.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let busy_since = shared.busy_since.load(Ordering::SeqCst);
+        if busy_since == 0 {
+            continue;
+        }
+        let busy_for = shared
+            .started
+            .elapsed()
+            .saturating_sub(Duration::from_millis(busy_since - 1));
+        if busy_for < shared.timeout {
+            continue;
+        }
+
+        log::debug!("The module has been blocking the event loop for {busy_for:?}, terminating");
+        shared.interrupt_requested.store(true, Ordering::SeqCst);
+        isolate.request_interrupt(capture_stack_and_terminate, interrupt_data as *mut c_void);
+
+        // The isolate handles the interrupt on the next stack guard check, e.g. in a loop
+        let deadline = Instant::now() + STACK_CAPTURE_TIMEOUT;
+        while shared.stack.lock().unwrap().is_none() && Instant::now() < deadline {
+            std::thread::park_timeout(Duration::from_millis(10));
+        }
+        if shared.stack.lock().unwrap().is_none() {
+            // The isolate did not run the interrupt, e.g. because it's blocked in native code
+            *shared.stack.lock().unwrap() = Some(String::from("(unknown)"));
+            isolate.terminate_execution();
+        }
+        return;
+    }
+}
+
+extern "C" fn capture_stack_and_terminate(isolate: &mut v8::Isolate, data: *mut c_void) {
+    // SAFETY: the watchdog keeps the data alive while the interrupt is pending
+    let data = unsafe { &*(data as *const InterruptData) };
+    // SAFETY: the interrupt callback runs on the isolate thread while JavaScript is executing
+    let scope = &mut unsafe { v8::CallbackScope::new(isolate) };
+    let scope = &mut v8::HandleScope::new(scope);
+    let context = v8::Local::new(scope, &data.context);
+    let scope = &mut v8::ContextScope::new(scope, context);
+    let stack = match v8::StackTrace::current_stack_trace(scope, STACK_FRAME_LIMIT) {
+        Some(trace) => format_stack(scope, trace),
+        None => String::from("(unknown)"),
+    };
+    *data.shared.stack.lock().unwrap() = Some(stack);
+    data.shared.interrupt_handled.store(true, Ordering::SeqCst);
+    scope.terminate_execution();
+}
+
+/// Format the stack as a single line, e.g. `loop (file:///mod.js:2:3) < file:///mod.js:5:1`.
+fn format_stack(scope: &mut v8::HandleScope, trace: v8::Local<v8::StackTrace>) -> String {
+    let mut frames = Vec::new();
+    for index in 0..trace.get_frame_count() {
+        let Some(frame) = trace.get_frame(scope, index) else {
+            continue;
+        };
+        let script = frame
+            .get_script_name(scope)
+            .map(|name| name.to_rust_string_lossy(scope))
+            .unwrap_or_else(|| String::from("<anonymous>"));
+        let location = format!(
+            "{script}:{}:{}",
+            frame.get_line_number(),
+            frame.get_column()
+        );
+        frames.push(match frame.get_function_name(scope) {
+            Some(name) if name.length() > 0 => {
+                format!("{} ({location})", name.to_rust_string_lossy(scope))
+            }
+            _ => location,
+        });
+    }
+
+    match frames.is_empty() {
+        true => String::from("(unknown)"),
+        false => frames.join(" < "),
+    }
+}